@@ -0,0 +1,198 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+pub const USAGE: &str = "Usage: fir [OPTIONS] [LEFT] [RIGHT]
+
+Arguments:
+  [LEFT]                 Directory of left panel
+  [RIGHT]                Directory of right panel
+
+Options:
+  -e, --edit FILE        Open FILE in editor
+  -v, --view FILE        Open FILE in viewer
+  -l, --list FILE        List files named in FILE, one per line, in panel
+  -c, --config FILE      Use FILE instead of default config file
+      --no-mouse         Leave mouse to terminal, e.g. for selecting text
+      --fresh            Start with default panels, session is still saved on exit
+      --no-session       Neither restore nor save session
+      --session FILE     Restore and save session in FILE
+  -h, --help             Print help
+  -V, --version          Print version";
+
+/// Command line options
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub left: Option<PathBuf>,
+    pub right: Option<PathBuf>,
+    pub edit: Option<PathBuf>,
+    pub view: Option<PathBuf>,
+    /// File with paths shown in panel instead of directory
+    pub list: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub mouse: bool,
+    pub fresh: bool,
+    pub no_session: bool,
+    pub session_file: Option<PathBuf>,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            left: None,
+            right: None,
+            edit: None,
+            view: None,
+            list: None,
+            config: None,
+            mouse: true,
+            fresh: false,
+            no_session: false,
+            session_file: None,
+            help: false,
+            version: false,
+        }
+    }
+}
+
+impl Args {
+    /// Parses arguments without program name. Values can follow option as next
+    /// argument or after `=`, everything after `--` is taken as directories.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Args> {
+        let mut parsed = Args::default();
+        let mut directories = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                directories.extend(args.by_ref());
+                break;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                directories.push(arg);
+                continue;
+            }
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(String::from(value))),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |option: &str| -> anyhow::Result<PathBuf> {
+                let value = match inline_value.clone() {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .with_context(|| format!("{} needs a file name", option))?,
+                };
+                Ok(PathBuf::from(value))
+            };
+            match name {
+                "-e" | "--edit" => parsed.edit = Some(value(name)?),
+                "-v" | "--view" => parsed.view = Some(value(name)?),
+                "-l" | "--list" => parsed.list = Some(value(name)?),
+                "-c" | "--config" => parsed.config = Some(value(name)?),
+                "--session" => parsed.session_file = Some(value(name)?),
+                "--no-mouse" => parsed.mouse = false,
+                "--fresh" => parsed.fresh = true,
+                "--no-session" => parsed.no_session = true,
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                _ => anyhow::bail!("Unknown option: {}", arg),
+            }
+        }
+        if directories.len() > 2 {
+            anyhow::bail!("Unexpected argument: {}", directories[2]);
+        }
+        let mut directories = directories.into_iter().map(PathBuf::from);
+        parsed.left = directories.next();
+        parsed.right = directories.next();
+        Ok(parsed)
+    }
+
+    /// Makes paths absolute, checking that directories and files exist
+    pub fn checked(self) -> anyhow::Result<Args> {
+        let directory = |path: Option<PathBuf>| -> anyhow::Result<Option<PathBuf>> {
+            path.map(|path| {
+                let dir = absolute(&path)?;
+                anyhow::ensure!(dir.is_dir(), "{} is not a directory", path.display());
+                Ok(dir)
+            })
+            .transpose()
+        };
+        let file = |path: Option<PathBuf>| -> anyhow::Result<Option<PathBuf>> {
+            path.map(|path| {
+                let file = absolute(&path)?;
+                anyhow::ensure!(file.is_file(), "{} is not a file", path.display());
+                Ok(file)
+            })
+            .transpose()
+        };
+        Ok(Args {
+            left: directory(self.left)?,
+            right: directory(self.right)?,
+            edit: file(self.edit)?,
+            view: file(self.view)?,
+            list: file(self.list)?,
+            ..self
+        })
+    }
+}
+
+/// Paths in list file, relative ones are taken from current directory. Empty lines
+/// are skipped.
+pub fn read_list(file: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let content =
+        fs::read_to_string(file).with_context(|| format!("Can't read {}", file.display()))?;
+    let dir = env::current_dir()?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| dir.join(line))
+        .collect())
+}
+
+fn absolute(path: &Path) -> anyhow::Result<PathBuf> {
+    path.canonicalize()
+        .with_context(|| format!("Can't open {}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> anyhow::Result<Args> {
+        Args::parse(args.iter().map(|arg| String::from(*arg)))
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(parse(&[]).unwrap(), Args::default());
+        let args = parse(&["--fresh", "--session", "s.toml"]).unwrap();
+        assert!(args.fresh);
+        assert_eq!(args.session_file, Some(PathBuf::from("s.toml")));
+        let args = parse(&["--session=other.toml", "--no-session"]).unwrap();
+        assert!(args.no_session);
+        assert_eq!(args.session_file, Some(PathBuf::from("other.toml")));
+        assert!(parse(&["--session"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+
+    #[test]
+    fn directories_test() {
+        let args = parse(&["/tmp", "-e", "notes.txt", "--no-mouse", "src"]).unwrap();
+        assert_eq!(args.left, Some(PathBuf::from("/tmp")));
+        assert_eq!(args.right, Some(PathBuf::from("src")));
+        assert_eq!(args.edit, Some(PathBuf::from("notes.txt")));
+        assert!(!args.mouse);
+        let args = parse(&["-c", "fir.toml", "--", "-dir"]).unwrap();
+        assert_eq!(args.config, Some(PathBuf::from("fir.toml")));
+        assert_eq!(args.left, Some(PathBuf::from("-dir")));
+        assert!(parse(&["a", "b", "c"]).is_err());
+        assert!(parse(&["-v"]).is_err());
+        let args = parse(&["--list=found.txt"]).unwrap();
+        assert_eq!(args.list, Some(PathBuf::from("found.txt")));
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    highlight::HighlightSettings,
+    keymap::{KeySettings, Keymap},
+    state_management::{default_associations, Association},
+    theme::{ColorDepth, Theme, DEFAULT_THEME},
+};
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Settings loaded from `$XDG_CONFIG_HOME/fir/config.toml`. Missing values take defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub ui: UiSettings,
+    pub panels: PanelSettings,
+    pub editor: EditorSettings,
+    pub shell: ShellSettings,
+    pub keys: KeySettings,
+    pub highlight: HighlightSettings,
+    pub associations: Vec<Association>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiSettings {
+    /// How often screen is redrawn without any events
+    pub tick_rate_ms: u64,
+    /// Bundled theme or theme file name from `themes` in config directory
+    pub theme: String,
+    pub color_depth: ColorDepth,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        UiSettings {
+            tick_rate_ms: 250,
+            theme: String::from(DEFAULT_THEME),
+            color_depth: ColorDepth::Auto,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Name,
+    Extension,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewMode {
+    /// File names only
+    #[default]
+    Brief,
+    /// File names with size and modification time
+    Full,
+}
+
+impl ViewMode {
+    pub fn next(self) -> Self {
+        match self {
+            ViewMode::Brief => ViewMode::Full,
+            ViewMode::Full => ViewMode::Brief,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanelSettings {
+    pub sort: SortOrder,
+    pub sort_reverse: bool,
+    pub view_mode: ViewMode,
+    pub show_hidden: bool,
+    pub directories_first: bool,
+}
+
+impl Default for PanelSettings {
+    fn default() -> Self {
+        PanelSettings {
+            sort: SortOrder::Name,
+            sort_reverse: false,
+            view_mode: ViewMode::Brief,
+            show_hidden: true,
+            directories_first: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorSettings {
+    pub tab_width: u8,
+    /// Indent with tabs even if file does not use them yet
+    pub hard_tabs: bool,
+    /// External editor and viewer, `$VISUAL`/`$EDITOR` and `$PAGER` are used when empty
+    pub external_editor: String,
+    pub external_viewer: String,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        EditorSettings {
+            tab_width: 4,
+            hard_tabs: false,
+            external_editor: String::new(),
+            external_viewer: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellSettings {
+    /// Run command line in persistent subshell instead of one shell per command
+    pub subshell: bool,
+}
+
+impl Default for ShellSettings {
+    fn default() -> Self {
+        ShellSettings { subshell: true }
+    }
+}
+
+impl Settings {
+    /// Loads settings from config file. If file is missing defaults are returned,
+    /// if it is invalid defaults are returned together with error description.
+    pub fn load(path: Option<&Path>) -> (Settings, Option<String>) {
+        let Some(path) = path.filter(|path| path.exists()) else {
+            return (Settings::defaults(), None);
+        };
+        match Settings::read(path) {
+            Ok(settings) => (settings, None),
+            Err(err) => (
+                Settings::defaults(),
+                Some(format!("Error in {}: {:#}", path.display(), err)),
+            ),
+        }
+    }
+
+    /// Settings used without config file
+    pub fn defaults() -> Settings {
+        Settings {
+            associations: default_associations(),
+            ..Default::default()
+        }
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Settings> {
+        let content = fs::read_to_string(path)?;
+        let settings: Settings = toml::from_str(&content)?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(10..=5000).contains(&self.ui.tick_rate_ms) {
+            anyhow::bail!("ui.tick_rate_ms should be between 10 and 5000");
+        }
+        if !(1..=16).contains(&self.editor.tab_width) {
+            anyhow::bail!("editor.tab_width should be between 1 and 16");
+        }
+        self.theme()?;
+        Keymap::new(&self.keys).context("Invalid key binding")?;
+        for association in &self.associations {
+            if association.mask.trim().is_empty() {
+                anyhow::bail!("association mask can't be empty");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn theme(&self) -> anyhow::Result<Theme> {
+        Theme::load(&self.ui.theme, self.ui.color_depth)
+    }
+
+    pub fn tick_rate(&self) -> Duration {
+        Duration::from_millis(self.ui.tick_rate_ms)
+    }
+
+    /// Simple values as `section.key` and value in TOML syntax, for settings dialog
+    pub fn fields(&self) -> Vec<(String, String)> {
+        let mut fields = vec![];
+        let Ok(toml::Value::Table(sections)) = toml::Value::try_from(self) else {
+            return fields;
+        };
+        for (section, values) in sections {
+            let toml::Value::Table(values) = values else {
+                continue;
+            };
+            for (key, value) in values {
+                let value = match value {
+                    // key bindings and highlighting groups are edited in config file only
+                    toml::Value::Table(_) | toml::Value::Array(_) => continue,
+                    toml::Value::String(text) => text,
+                    value => value.to_string(),
+                };
+                fields.push((format!("{}.{}", section, key), value));
+            }
+        }
+        fields
+    }
+
+    /// Changes one value by `section.key` name. Value is parsed as TOML, if that fails it is taken as string.
+    pub fn set_field(&self, name: &str, value: &str) -> anyhow::Result<Settings> {
+        let (section, key) = name.split_once('.').context("Unknown setting")?;
+        let mut root = toml::Value::try_from(self)?;
+        let table = root
+            .get_mut(section)
+            .and_then(|section| section.as_table_mut())
+            .context("Unknown setting")?;
+        let old = table.get(key).context("Unknown setting")?;
+        let parsed = format!("value = {}", value)
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .filter(|parsed| parsed.same_type(old))
+            .unwrap_or_else(|| toml::Value::String(String::from(value)));
+        table.insert(String::from(key), parsed);
+        let settings: Settings = root
+            .try_into()
+            .with_context(|| format!("Invalid value for {}", name))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+}
+
+/// Directory where fir keeps its configuration
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ if cfg!(target_os = "windows") => PathBuf::from(env::var_os("APPDATA")?),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("fir"))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keymap::Command;
+
+    #[test]
+    fn parse_test() {
+        let settings: Settings = toml::from_str(
+            r##"
+            [panels]
+            sort = "size"
+            [ui]
+            theme = "far"
+            color_depth = "256"
+            [[associations]]
+            mask = "*.png"
+            view = "feh !.!"
+            [keys.panel]
+            "Ctrl+q" = "quit"
+            [[highlight.groups]]
+            mask = "*.bak"
+            style = { fg = "darkgray" }
+            marker = "~"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(settings.panels.sort, SortOrder::Size);
+        assert_eq!(settings.ui.theme, "far");
+        assert_eq!(settings.ui.color_depth, ColorDepth::Indexed);
+        assert_eq!(settings.ui.tick_rate_ms, 250);
+        assert_eq!(settings.associations[0].view.as_deref(), Some("feh !.!"));
+        assert_eq!(settings.keys.panel["Ctrl+q"], Command::Quit);
+        assert_eq!(settings.highlight.groups[0].marker, Some('~'));
+        assert!(settings.highlight.use_ls_colors);
+        assert!(toml::from_str::<Settings>("[panels]\nsort = \"color\"").is_err());
+        assert!(toml::from_str::<Settings>("[keys.panel]\nF1 = \"fly\"").is_err());
+    }
+
+    #[test]
+    fn set_field_test() {
+        let settings = Settings::defaults();
+        let changed = settings.set_field("ui.tick_rate_ms", "100").unwrap();
+        assert_eq!(changed.ui.tick_rate_ms, 100);
+        let changed = settings.set_field("editor.external_editor", "nano -w").unwrap();
+        assert_eq!(changed.editor.external_editor, "nano -w");
+        assert!(settings.set_field("ui.tick_rate_ms", "0").is_err());
+        assert!(settings.set_field("ui.theme", "no such theme").is_err());
+        assert!(settings.set_field("ui.color_depth", "8").is_err());
+        let changed = settings.set_field("ui.color_depth", "256").unwrap();
+        assert_eq!(changed.ui.color_depth, ColorDepth::Indexed);
+        assert!(settings.set_field("ui.unknown", "1").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::vfs::{DavAuth, FtpFs, FtpOptions, S3Fs, SftpAuth, SftpFs, Vfs, VfsHandle, WebDavFs};
+
+/// Field of connection dialog shown masked
+pub const PASSWORD_FIELD: &str = "password";
+
+/// Protocol of remote filesystem
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Sftp,
+    Ftp,
+    /// FTP secured with explicit TLS
+    Ftps,
+    /// S3 compatible object storage, host is its endpoint
+    S3,
+    /// Share of WebDAV server, host is its URL
+    WebDav,
+}
+
+impl Protocol {
+    /// None if the port is part of endpoint URL
+    fn default_port(self) -> Option<u16> {
+        match self {
+            Protocol::Sftp => Some(22),
+            Protocol::Ftp | Protocol::Ftps => Some(21),
+            Protocol::S3 | Protocol::WebDav => None,
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::Sftp => write!(f, "sftp"),
+            Protocol::Ftp => write!(f, "ftp"),
+            Protocol::Ftps => write!(f, "ftps"),
+            Protocol::S3 => write!(f, "s3"),
+            Protocol::WebDav => write!(f, "webdav"),
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> anyhow::Result<Self> {
+        match text.to_lowercase().as_str() {
+            "sftp" => Ok(Protocol::Sftp),
+            "ftp" => Ok(Protocol::Ftp),
+            "ftps" => Ok(Protocol::Ftps),
+            "s3" => Ok(Protocol::S3),
+            "webdav" => Ok(Protocol::WebDav),
+            _ => anyhow::bail!(
+                "Unknown protocol {}, use sftp, ftp, ftps, s3 or webdav",
+                text
+            ),
+        }
+    }
+}
+
+/// Remote server a panel connects to, edited in connection dialog
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Connection {
+    pub protocol: Protocol,
+    /// Host name, or URL of S3 endpoint or WebDAV share where `https://` is added
+    /// without scheme
+    pub host: String,
+    /// Default port of the protocol if None
+    pub port: Option<u16>,
+    /// Local user name if empty, anonymous for FTP. Access key for S3,
+    /// AWS_ACCESS_KEY_ID if empty. Without user WebDAV password is sent as
+    /// bearer token.
+    pub user: String,
+    /// Password, or passphrase of key file. Secret key for S3, AWS_SECRET_ACCESS_KEY if empty.
+    /// Saved apart from the other fields.
+    #[serde(skip)]
+    pub password: String,
+    /// Private key for SFTP. Without key and password keys of ssh-agent are tried.
+    pub key_file: String,
+    /// Region signed in S3 requests, AWS_REGION or `us-east-1` if empty
+    pub region: String,
+    /// FTP server opens data connections to us instead of passive mode
+    pub active_mode: bool,
+    /// Directory shown after connecting, home directory if empty.
+    /// For S3 bucket and prefix, e.g. `/bucket/dir`, list of buckets if empty.
+    pub path: String,
+    /// SHA256 fingerprint of FTPS certificate user trusted, for servers whose
+    /// certificate isn't signed by known authority. Not shown in dialog.
+    pub certificate: String,
+}
+
+impl Connection {
+    /// Names and values of dialog fields used by the protocol
+    pub fn fields(&self) -> Vec<(String, String)> {
+        let option = match self.protocol {
+            Protocol::Sftp => Some(("key file", self.key_file.clone())),
+            Protocol::Ftp | Protocol::Ftps => Some((
+                "mode",
+                String::from(match self.active_mode {
+                    true => "active",
+                    false => "passive",
+                }),
+            )),
+            Protocol::S3 => Some(("region", self.region.clone())),
+            Protocol::WebDav => None,
+        };
+        [
+            ("protocol", self.protocol.to_string()),
+            ("host", self.host.clone()),
+            (
+                "port",
+                self.port.map(|port| port.to_string()).unwrap_or_default(),
+            ),
+            ("user", self.user.clone()),
+            (PASSWORD_FIELD, self.password.clone()),
+        ]
+        .into_iter()
+        .chain(option)
+        .chain([("path", self.path.clone())])
+        .map(|(name, value)| (String::from(name), value))
+        .collect()
+    }
+
+    pub fn set_field(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        let value = String::from(value);
+        match name {
+            "protocol" => self.protocol = value.parse()?,
+            "host" => self.host = value,
+            "port" if value.is_empty() => self.port = None,
+            "port" => self.port = Some(value.parse().context("Port must be a number up to 65535")?),
+            "user" => self.user = value,
+            PASSWORD_FIELD => self.password = value,
+            "key file" => self.key_file = value,
+            "region" => self.region = value,
+            "mode" => {
+                self.active_mode = match value.to_lowercase().as_str() {
+                    "active" => true,
+                    "passive" | "" => false,
+                    _ => anyhow::bail!("Unknown mode {}, use passive or active", value),
+                }
+            }
+            "path" => self.path = value,
+            _ => anyhow::bail!("Unknown field {}", name),
+        }
+        Ok(())
+    }
+
+    /// Connects to the server, blocking until it is done. Unknown host is trusted
+    /// when its fingerprint is `trusted`, or is the saved certificate. Returns
+    /// filesystem and directory to show in panel.
+    pub fn open(&self, trusted: Option<&str>) -> anyhow::Result<(VfsHandle, PathBuf)> {
+        if self.host.is_empty() {
+            anyhow::bail!("Host is required");
+        }
+        match self.protocol {
+            Protocol::S3 => return self.open_s3(),
+            Protocol::WebDav => return self.open_webdav(),
+            _ => {}
+        }
+        let port = self
+            .port
+            .or(self.protocol.default_port())
+            .unwrap_or_default();
+        let user = match (self.user.as_str(), self.protocol) {
+            ("", Protocol::Ftp | Protocol::Ftps) => String::from("anonymous"),
+            ("", _) => env::var("USER").context("User is required")?,
+            (user, _) => String::from(user),
+        };
+        match self.protocol {
+            Protocol::S3 | Protocol::WebDav => unreachable!("HTTP protocols are opened above"),
+            Protocol::Sftp => {
+                let auth = match (self.key_file.as_str(), self.password.as_str()) {
+                    ("", "") => SftpAuth::Agent,
+                    ("", password) => SftpAuth::Password(String::from(password)),
+                    (key, passphrase) => SftpAuth::KeyFile(
+                        expand_home(key),
+                        Some(String::from(passphrase)).filter(|text| !text.is_empty()),
+                    ),
+                };
+                let sftp = SftpFs::connect(&self.host, port, &user, &auth, trusted)?;
+                let home = sftp.home()?;
+                let path = home.join(&self.path);
+                Ok((VfsHandle::new(sftp), path))
+            }
+            Protocol::Ftp | Protocol::Ftps => {
+                let options = FtpOptions {
+                    host: self.host.clone(),
+                    port,
+                    user,
+                    password: self.password.clone(),
+                    tls: self.protocol == Protocol::Ftps,
+                    active: self.active_mode,
+                };
+                let ftp = FtpFs::connect(
+                    &options,
+                    trusted.or(Some(self.certificate.as_str()).filter(|text| !text.is_empty())),
+                )?;
+                let home = ftp.home()?;
+                let path = home.join(&self.path);
+                Ok((VfsHandle::new(ftp), path))
+            }
+        }
+    }
+
+    /// Host as URL, with port after host name if it is set
+    fn url(&self) -> String {
+        let host = self.host.trim_end_matches('/');
+        let (scheme, rest) = host.split_once("://").unwrap_or(("https", host));
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        match self.port {
+            Some(port) => format!("{}://{}:{}{}", scheme, authority, port, path),
+            None => format!("{}://{}{}", scheme, authority, path),
+        }
+    }
+
+    fn open_s3(&self) -> anyhow::Result<(VfsHandle, PathBuf)> {
+        let endpoint = self.url();
+        let value_or_env = |value: &str, name: &str| match value {
+            "" => env::var(name).ok(),
+            value => Some(String::from(value)),
+        };
+        let access_key = value_or_env(&self.user, "AWS_ACCESS_KEY_ID")
+            .context("Access key is required as user")?;
+        let secret_key = value_or_env(&self.password, "AWS_SECRET_ACCESS_KEY")
+            .context("Secret key is required as password")?;
+        let region =
+            value_or_env(&self.region, "AWS_REGION").unwrap_or_else(|| String::from("us-east-1"));
+        let s3 = S3Fs::new(&endpoint, &region, &access_key, &secret_key)?;
+        let path = Path::new("/").join(&self.path);
+        // checks credentials and path before panel shows it
+        if !s3.stat(&path)?.is_dir {
+            anyhow::bail!("{} is not a bucket or directory", path.display());
+        }
+        Ok((VfsHandle::new(s3), path))
+    }
+
+    fn open_webdav(&self) -> anyhow::Result<(VfsHandle, PathBuf)> {
+        let auth = match (self.user.as_str(), self.password.as_str()) {
+            ("", "") => DavAuth::None,
+            ("", token) => DavAuth::Bearer(String::from(token)),
+            (user, password) => DavAuth::Basic {
+                user: String::from(user),
+                password: String::from(password),
+            },
+        };
+        let dav = WebDavFs::new(&self.url(), auth)?;
+        let path = Path::new("/").join(&self.path);
+        // checks credentials and path before panel shows it
+        if !dav.stat(&path)?.is_dir {
+            anyhow::bail!("{} is not a directory", path.display());
+        }
+        Ok((VfsHandle::new(dav), path))
+    }
+}
+
+/// Path with leading `~` replaced by home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fields_test() {
+        let mut connection = Connection::default();
+        connection.set_field("host", "example.com").unwrap();
+        connection.set_field("port", "2222").unwrap();
+        assert!(connection.set_field("port", "ssh").is_err());
+        assert!(connection.set_field("protocol", "gopher").is_err());
+        connection.set_field("port", "").unwrap();
+        assert_eq!(connection.port, None);
+        let fields = connection.fields();
+        assert_eq!(
+            fields[1],
+            (String::from("host"), String::from("example.com"))
+        );
+        for (name, value) in fields {
+            connection.set_field(&name, &value).unwrap();
+        }
+        connection.set_field("protocol", "FTP").unwrap();
+        connection.set_field("mode", "active").unwrap();
+        assert!(connection.set_field("mode", "fast").is_err());
+        assert!(connection
+            .fields()
+            .contains(&(String::from("mode"), String::from("active"))));
+        assert!(Connection::default().open(None).is_err());
+
+        connection
+            .set_field("host", "cloud.example.com/dav/")
+            .unwrap();
+        connection.set_field("port", "8443").unwrap();
+        assert_eq!(connection.url(), "https://cloud.example.com:8443/dav");
+        connection.set_field("host", "http://localhost").unwrap();
+        connection.set_field("port", "").unwrap();
+        assert_eq!(connection.url(), "http://localhost");
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::config_dir, connection::Connection};
+
+const CONNECTIONS_FILE: &str = "connections.toml";
+/// Passwords of saved connections, readable by the user only
+const SECRETS_FILE: &str = "secrets.toml";
+
+/// Field of connection dialog editing saved connection
+pub const NAME_FIELD: &str = "name";
+
+/// Named connection shown in network panel
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    #[serde(flatten)]
+    pub connection: Connection,
+}
+
+impl Profile {
+    /// Columns of network panel after the name
+    pub fn details(&self) -> String {
+        let connection = &self.connection;
+        let host = match connection.port {
+            Some(port) => format!("{}:{}", connection.host, port),
+            None => connection.host.clone(),
+        };
+        format!(
+            "{:<6} {:<24} {:<12} {}",
+            connection.protocol, host, connection.user, connection.path
+        )
+    }
+}
+
+/// Saved connections, with passwords kept apart from the rest
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Connections {
+    pub profiles: Vec<Profile>,
+    /// Where profiles and passwords are saved, kept in memory only if None
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    secrets_path: Option<PathBuf>,
+}
+
+/// Passwords by profile name
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Secrets {
+    passwords: BTreeMap<String, String>,
+}
+
+impl Connections {
+    /// Missing files give empty list and no passwords
+    pub fn load(
+        path: Option<PathBuf>,
+        secrets_path: Option<PathBuf>,
+    ) -> anyhow::Result<Connections> {
+        let mut connections: Connections = match &path {
+            Some(path) if path.exists() => toml::from_str(&fs::read_to_string(path)?)?,
+            _ => Connections::default(),
+        };
+        let secrets: Secrets = match &secrets_path {
+            Some(path) if path.exists() => toml::from_str(&fs::read_to_string(path)?)?,
+            _ => Secrets::default(),
+        };
+        for profile in connections.profiles.iter_mut() {
+            if let Some(password) = secrets.passwords.get(&profile.name) {
+                profile.connection.password = password.clone();
+            }
+        }
+        connections.path = path;
+        connections.secrets_path = secrets_path;
+        Ok(connections)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let (Some(path), Some(secrets_path)) = (&self.path, &self.secrets_path) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        let secrets = Secrets {
+            passwords: self
+                .profiles
+                .iter()
+                .filter(|profile| !profile.connection.password.is_empty())
+                .map(|profile| (profile.name.clone(), profile.connection.password.clone()))
+                .collect(),
+        };
+        write_private(secrets_path, &toml::to_string_pretty(&secrets)?)?;
+        Ok(())
+    }
+
+    /// Adds new profile, or replaces the one at `index`. Names must be unique.
+    pub fn set(&mut self, index: Option<usize>, profile: Profile) -> anyhow::Result<()> {
+        let name = profile.name.trim();
+        if name.is_empty() {
+            anyhow::bail!("Name is required");
+        }
+        if name.contains('/') {
+            anyhow::bail!("Name can't contain /");
+        }
+        let taken = self
+            .profiles
+            .iter()
+            .enumerate()
+            .any(|(other, saved)| saved.name == name && Some(other) != index);
+        if taken {
+            anyhow::bail!("Connection {} already exists", name);
+        }
+        let profile = Profile {
+            name: String::from(name),
+            ..profile
+        };
+        match index.and_then(|index| self.profiles.get_mut(index)) {
+            Some(saved) => *saved = profile,
+            None => self.profiles.push(profile),
+        }
+        Ok(())
+    }
+
+    /// Copies profile after itself, named with the first free ` (n)` suffix
+    pub fn duplicate(&mut self, index: usize) {
+        let Some(profile) = self.profiles.get(index) else {
+            return;
+        };
+        let name = (2..)
+            .map(|n| format!("{} ({})", profile.name, n))
+            .find(|name| self.profiles.iter().all(|saved| saved.name != *name))
+            .expect("Some suffix should be free");
+        let copy = Profile {
+            name,
+            ..profile.clone()
+        };
+        self.profiles.insert(index + 1, copy);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.profiles.len() {
+            self.profiles.remove(index);
+        }
+    }
+}
+
+/// Writes file other users can't read, also when it already existed
+#[cfg(unix)]
+fn write_private(path: &Path, content: &str) -> anyhow::Result<()> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, content: &str) -> anyhow::Result<()> {
+    fs::File::create(path)?.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+pub fn connections_path() -> Option<PathBuf> {
+    Some(config_dir()?.join(CONNECTIONS_FILE))
+}
+
+pub fn secrets_path() -> Option<PathBuf> {
+    Some(config_dir()?.join(SECRETS_FILE))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{connection::Protocol, test_dir::TestDir};
+
+    use super::*;
+
+    #[test]
+    fn save_load_test() {
+        let dir = TestDir::new("connections");
+        let path = dir.path().join(CONNECTIONS_FILE);
+        let secrets = dir.path().join(SECRETS_FILE);
+        let mut connections = Connections::load(Some(path.clone()), Some(secrets.clone())).unwrap();
+        assert!(connections.profiles.is_empty());
+        let mut connection = Connection {
+            protocol: Protocol::Ftps,
+            host: String::from("files.example.com"),
+            port: Some(2121),
+            user: String::from("backup"),
+            password: String::from("hunter2"),
+            ..Default::default()
+        };
+        connections
+            .set(
+                None,
+                Profile {
+                    name: String::from(" Backup "),
+                    connection: connection.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(connections.profiles[0].name, "Backup");
+        connection.password = String::new();
+        let profile = Profile {
+            name: String::from("Backup"),
+            connection,
+        };
+        assert!(connections.set(None, profile.clone()).is_err());
+        connections.duplicate(0);
+        assert_eq!(connections.profiles[1].name, "Backup (2)");
+        assert_eq!(connections.profiles[1].connection.password, "hunter2");
+        connections
+            .set(
+                Some(1),
+                Profile {
+                    name: String::from("Mirror"),
+                    ..profile
+                },
+            )
+            .unwrap();
+        connections.save().unwrap();
+
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("protocol = \"ftps\""));
+        assert!(!saved.contains("hunter2"));
+        let saved = fs::read_to_string(&secrets).unwrap();
+        assert!(saved.contains("Backup = \"hunter2\""));
+        assert!(!saved.contains("Mirror"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&secrets).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert_eq!(
+            Connections::load(Some(path), Some(secrets)).unwrap(),
+            connections
+        );
+        connections.remove(0);
+        assert_eq!(connections.profiles[0].name, "Mirror");
+    }
+}
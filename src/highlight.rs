@@ -0,0 +1,311 @@
+use std::{
+    env,
+    time::{Duration, SystemTime},
+};
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    state_management::{mask, PanelItem},
+    theme::{ColorDepth, StyleSpec},
+};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// File property highlighting group can require
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Attribute {
+    Directory,
+    Executable,
+    Symlink,
+    BrokenLink,
+    Hidden,
+    Setuid,
+    Setgid,
+    Pipe,
+    Socket,
+    Device,
+}
+
+/// Files matching all conditions of the group are drawn with its style and marker.
+/// Groups are checked in order, first match wins.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighlightGroup {
+    pub description: String,
+    /// File mask list like `*.zip,*.tar`, empty mask matches everything
+    pub mask: String,
+    /// Attributes file must have
+    pub attributes: Vec<Attribute>,
+    /// Attributes file must not have
+    pub exclude_attributes: Vec<Attribute>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Modified less than given number of days ago
+    pub newer_than_days: Option<u64>,
+    pub older_than_days: Option<u64>,
+    pub style: StyleSpec,
+    /// Character shown before file name
+    pub marker: Option<char>,
+}
+
+impl HighlightGroup {
+    fn new(description: &str, attributes: &[Attribute], style: StyleSpec) -> Self {
+        HighlightGroup {
+            description: String::from(description),
+            attributes: attributes.to_vec(),
+            style,
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, item: &PanelItem, now: SystemTime) -> bool {
+        if !self.mask.is_empty() && !mask::matches(&self.mask, &item.current_file_name()) {
+            return false;
+        }
+        if !self.attributes.iter().all(|a| item.has_attribute(*a))
+            || self.exclude_attributes.iter().any(|a| item.has_attribute(*a))
+        {
+            return false;
+        }
+        let size = item.size();
+        if self.min_size.is_some_and(|min| size < min) || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        if self.newer_than_days.is_some() || self.older_than_days.is_some() {
+            let Some(age) = item
+                .modified()
+                .map(|modified| now.duration_since(modified).unwrap_or_default())
+            else {
+                return false;
+            };
+            if self.newer_than_days.is_some_and(|days| age >= DAY * days as u32)
+                || self.older_than_days.is_some_and(|days| age < DAY * days as u32)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighlightSettings {
+    /// Add groups made from `LS_COLORS` after user's groups
+    pub use_ls_colors: bool,
+    pub groups: Vec<HighlightGroup>,
+}
+
+impl Default for HighlightSettings {
+    fn default() -> Self {
+        HighlightSettings {
+            use_ls_colors: true,
+            groups: vec![],
+        }
+    }
+}
+
+/// Highlighting groups in effect
+#[derive(Debug, Clone, Default)]
+pub struct Highlighting {
+    groups: Vec<HighlightGroup>,
+}
+
+impl Highlighting {
+    pub fn new(settings: &HighlightSettings, depth: ColorDepth) -> Self {
+        let mut groups = settings.groups.clone();
+        if settings.use_ls_colors {
+            let ls_colors = env::var("LS_COLORS").unwrap_or_default();
+            groups.extend(ls_colors_groups(&ls_colors));
+        }
+        let depth = depth.resolve();
+        for group in groups.iter_mut() {
+            group.style = group.style.with_depth(depth);
+        }
+        Highlighting { groups }
+    }
+
+    pub fn find(&self, item: &PanelItem) -> Option<&HighlightGroup> {
+        let now = SystemTime::now();
+        self.groups.iter().find(|group| group.matches(item, now))
+    }
+
+    /// Whether panels need a column for markers
+    pub fn has_markers(&self) -> bool {
+        self.groups.iter().any(|group| group.marker.is_some())
+    }
+}
+
+/// Converts `LS_COLORS` value to groups, following `ls` precedence: file types first,
+/// then permissions, then name patterns for plain files.
+pub fn ls_colors_groups(ls_colors: &str) -> Vec<HighlightGroup> {
+    use Attribute::*;
+
+    let mut types = vec![];
+    let mut patterns = vec![];
+    for entry in ls_colors.split(':') {
+        let Some((key, codes)) = entry.split_once('=') else {
+            continue;
+        };
+        let Some(style) = sgr_style(codes) else {
+            continue;
+        };
+        let group = match key {
+            "or" => HighlightGroup::new("Broken links", &[BrokenLink], style),
+            "ln" => HighlightGroup::new("Symbolic links", &[Symlink], style),
+            "di" => HighlightGroup::new("Directories", &[Directory], style),
+            "pi" => HighlightGroup::new("Pipes", &[Pipe], style),
+            "so" => HighlightGroup::new("Sockets", &[Socket], style),
+            "bd" | "cd" => HighlightGroup::new("Devices", &[Device], style),
+            "su" => HighlightGroup::new("Setuid files", &[Setuid], style),
+            "sg" => HighlightGroup::new("Setgid files", &[Setgid], style),
+            "ex" => HighlightGroup::new("Executables", &[Executable], style),
+            key if key.starts_with('*') => {
+                patterns.push(HighlightGroup {
+                    description: String::from(key),
+                    mask: String::from(key),
+                    exclude_attributes: vec![Directory, Executable],
+                    style,
+                    ..Default::default()
+                });
+                continue;
+            }
+            _ => continue,
+        };
+        types.push(group);
+    }
+    let order = [
+        "Broken links",
+        "Symbolic links",
+        "Directories",
+        "Pipes",
+        "Sockets",
+        "Devices",
+        "Setuid files",
+        "Setgid files",
+        "Executables",
+    ];
+    types.sort_by_key(|group| order.iter().position(|d| *d == group.description));
+    types.dedup_by(|a, b| a.description == b.description);
+    types.extend(patterns);
+    types
+}
+
+/// Parses SGR parameters like `01;38;5;208`. Returns None when they set nothing.
+fn sgr_style(codes: &str) -> Option<StyleSpec> {
+    let mut style = StyleSpec::default();
+    let mut params = codes.split(';').map(|p| p.parse::<u8>().unwrap_or(0));
+    while let Some(code) = params.next() {
+        match code {
+            0 => style = StyleSpec::default(),
+            1 => style.bold = true,
+            3 => style.italic = true,
+            4 => style.underline = true,
+            7 => style.reversed = true,
+            30..=37 => style.fg = Some(ansi_color(code - 30)),
+            40..=47 => style.bg = Some(ansi_color(code - 40)),
+            90..=97 => style.fg = Some(ansi_color(code - 90 + 8)),
+            100..=107 => style.bg = Some(ansi_color(code - 100 + 8)),
+            38 | 48 => {
+                let color = match params.next() {
+                    Some(5) => params.next().map(Color::Indexed),
+                    Some(2) => match (params.next(), params.next(), params.next()) {
+                        (Some(r), Some(g), Some(b)) => Some(Color::Rgb(r, g, b)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if code == 38 {
+                    style.fg = color.or(style.fg);
+                } else {
+                    style.bg = color.or(style.bg);
+                }
+            }
+            _ => {}
+        }
+    }
+    (style != StyleSpec::default()).then_some(style)
+}
+
+fn ansi_color(index: u8) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::Gray,
+        8 => Color::DarkGray,
+        9 => Color::LightRed,
+        10 => Color::LightGreen,
+        11 => Color::LightYellow,
+        12 => Color::LightBlue,
+        13 => Color::LightMagenta,
+        14 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        test_dir::TestDir,
+        vfs::{LocalFs, Vfs},
+    };
+
+    #[test]
+    fn ls_colors_test() {
+        let groups = ls_colors_groups("rs=0:*.tar=01;31:ex=01;32:di=01;34:no=00:*.png=38;5;208");
+        let descriptions: Vec<&str> = groups.iter().map(|g| g.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["Directories", "Executables", "*.tar", "*.png"]);
+        assert_eq!(groups[0].style.fg, Some(Color::Blue));
+        assert!(groups[0].style.bold);
+        assert_eq!(groups[3].style.fg, Some(Color::Indexed(208)));
+        assert_eq!(sgr_style("48;2;1;2;3").unwrap().bg, Some(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn group_match_test() {
+        let test_dir = TestDir::new("highlight");
+        let dir = test_dir.path();
+        let file = dir.join("notes.txt");
+        fs::write(&file, "12345").unwrap();
+        let item = PanelItem::with_info(file.clone(), LocalFs.stat(&file).unwrap());
+        let now = SystemTime::now();
+
+        let group = HighlightGroup {
+            mask: String::from("*.txt"),
+            exclude_attributes: vec![Attribute::Directory],
+            min_size: Some(5),
+            newer_than_days: Some(1),
+            marker: Some('~'),
+            ..Default::default()
+        };
+        assert!(group.matches(&item, now));
+        let big = HighlightGroup {
+            min_size: Some(6),
+            ..group.clone()
+        };
+        assert!(!big.matches(&item, now));
+        let old = HighlightGroup {
+            newer_than_days: None,
+            older_than_days: Some(1),
+            ..group.clone()
+        };
+        assert!(!old.matches(&item, now));
+        assert!(!old.matches(&item, now - DAY));
+        assert!(old.matches(&item, now + DAY * 2));
+        let dirs = HighlightGroup::new("Directories", &[Attribute::Directory], StyleSpec::default());
+        assert!(dirs.matches(&PanelItem::with_info(dir.to_path_buf(), LocalFs.stat(dir).unwrap()), now));
+        assert!(!dirs.matches(&item, now));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::config_dir;
+
+const HOTLIST_FILE: &str = "hotlist.toml";
+
+/// Named directory in hotlist
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bookmark {
+    pub name: String,
+    pub path: PathBuf,
+    /// Digit of quick bookmark, jumped to with Ctrl+digit
+    pub key: Option<u8>,
+}
+
+impl Bookmark {
+    /// Line shown in hotlist popup
+    pub fn label(&self) -> String {
+        let key = self.key.map(|key| key.to_string()).unwrap_or_default();
+        format!("{:>1} {:<20} {}", key, self.name, self.path.display())
+    }
+}
+
+/// Directory bookmarks, saved after every change
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hotlist {
+    pub bookmarks: Vec<Bookmark>,
+    /// Where list is saved, kept in memory only if None
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Hotlist {
+    /// Missing file gives empty list
+    pub fn load(path: Option<PathBuf>) -> anyhow::Result<Hotlist> {
+        let mut hotlist = match &path {
+            Some(path) if path.exists() => toml::from_str(&fs::read_to_string(path)?)?,
+            _ => Hotlist::default(),
+        };
+        hotlist.path = path;
+        Ok(hotlist)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Adds directory, empty name is replaced with directory name
+    pub fn add(&mut self, name: &str, path: PathBuf) {
+        let name = match name.trim() {
+            "" => default_name(&path),
+            name => String::from(name),
+        };
+        self.bookmarks.push(Bookmark {
+            name,
+            path,
+            key: None,
+        });
+    }
+
+    pub fn rename(&mut self, index: usize, name: &str) {
+        if let Some(bookmark) = self.bookmarks.get_mut(index) {
+            bookmark.name = match name.trim() {
+                "" => default_name(&bookmark.path),
+                name => String::from(name),
+            };
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.bookmarks.len() {
+            self.bookmarks.remove(index);
+        }
+    }
+
+    pub fn quick(&self, key: u8) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|bookmark| bookmark.key == Some(key))
+    }
+
+    /// Points quick bookmark to directory, adding it to the list if it's not there yet
+    pub fn set_quick(&mut self, key: u8, path: PathBuf) {
+        for bookmark in self.bookmarks.iter_mut() {
+            if bookmark.key == Some(key) {
+                bookmark.key = None;
+            }
+        }
+        match self.bookmarks.iter_mut().find(|bookmark| bookmark.path == path) {
+            Some(bookmark) => bookmark.key = Some(key),
+            None => self.bookmarks.push(Bookmark {
+                name: default_name(&path),
+                path,
+                key: Some(key),
+            }),
+        }
+    }
+}
+
+fn default_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+pub fn hotlist_path() -> Option<PathBuf> {
+    Some(config_dir()?.join(HOTLIST_FILE))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn quick_bookmark_test() {
+        let mut hotlist = Hotlist::default();
+        hotlist.add("", PathBuf::from("/usr/src"));
+        hotlist.add("Logs", PathBuf::from("/var/log"));
+        assert_eq!(hotlist.bookmarks[0].name, "src");
+
+        hotlist.set_quick(1, PathBuf::from("/var/log"));
+        assert_eq!(hotlist.quick(1).unwrap().name, "Logs");
+        assert_eq!(hotlist.bookmarks.len(), 2);
+        hotlist.set_quick(1, PathBuf::from("/tmp"));
+        assert_eq!(hotlist.quick(1).unwrap().path, PathBuf::from("/tmp"));
+        assert_eq!(hotlist.bookmarks[1].key, None);
+        assert_eq!(hotlist.bookmarks.len(), 3);
+        assert!(hotlist.quick(2).is_none());
+    }
+
+    #[test]
+    fn save_load_test() {
+        let dir = TestDir::new("hotlist");
+        let path = dir.path().join(HOTLIST_FILE);
+        let mut hotlist = Hotlist::load(Some(path.clone())).unwrap();
+        assert!(hotlist.bookmarks.is_empty());
+        hotlist.add("Home", PathBuf::from("/home"));
+        hotlist.set_quick(3, PathBuf::from("/etc"));
+        hotlist.save().unwrap();
+        assert_eq!(Hotlist::load(Some(path.clone())).unwrap(), hotlist);
+    }
+}
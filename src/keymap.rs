@@ -0,0 +1,566 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use anyhow::Context;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+/// Part of UI key bindings apply to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyContext {
+    Panel,
+    Editor,
+    Popup,
+    /// Output of commands shown with Ctrl+O
+    Viewer,
+}
+
+/// Named command key can be bound to. Meaning depends on context,
+/// e.g. `copy` copies files in panels and text in editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    /// Removes default binding of the key
+    #[serde(rename = "none")]
+    Unbound,
+    CursorUp,
+    CursorDown,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Open,
+    /// Enters directory or archive under cursor
+    EnterArchive,
+    /// Packs marked files into archive
+    Pack,
+    /// Opens dialog connecting active panel to remote server
+    Connect,
+    /// Lists saved connections in active panel
+    Network,
+    /// Opens dialog searching files below current directory
+    Find,
+    SwitchPanel,
+    /// Marks or unmarks file under cursor
+    Mark,
+    /// Marks all files of panel, or unmarks them when all are marked
+    MarkAll,
+    View,
+    Edit,
+    ExternalView,
+    ExternalEdit,
+    OpenWith,
+    Copy,
+    /// Asks where to move files, the other panel's directory by default
+    Move,
+    /// Asks for new name of file under cursor
+    Rename,
+    MakeDir,
+    Delete,
+    Settings,
+    Quit,
+    ToggleOutput,
+    InsertFileName,
+    InsertPath,
+    HistoryPrev,
+    HistoryNext,
+    Close,
+    Save,
+    Undo,
+    Redo,
+    Cut,
+    Paste,
+    DuplicateLine,
+    DeleteLine,
+    MoveLineUp,
+    MoveLineDown,
+    Confirm,
+    Cancel,
+    FocusPrev,
+    FocusNext,
+    SortByName,
+    SortByExtension,
+    SortBySize,
+    SortByModified,
+    ToggleViewMode,
+    Hotlist,
+    /// Jumps to quick bookmark numbered by digit of the key
+    QuickBookmark,
+    /// Sets quick bookmark numbered by digit of the key to current directory
+    SetQuickBookmark,
+    AddItem,
+    HistoryBack,
+    HistoryForward,
+    FolderHistory,
+}
+
+/// One key with modifiers, written as `Ctrl+Shift+F5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // terminals differ in reporting shift with characters, so it is kept in the character only.
+        // Digits are the exception, with shift they are only reported by terminals that keep
+        // the key unshifted, e.g. with kitty keyboard protocol.
+        match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) && !c.is_ascii_digit() => KeyChord {
+                code: KeyCode::Char(c.to_ascii_uppercase()),
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            KeyCode::BackTab => KeyChord {
+                code,
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            _ => KeyChord { code, modifiers },
+        }
+    }
+
+    /// Character typed as text, without Ctrl or Alt
+    pub fn is_text(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_))
+            && !self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+
+    /// Compact form for help line, e.g. `^o` or `5`
+    pub fn short_label(&self) -> String {
+        let mut label = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            label.push('^');
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            label.push_str("M-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            label.push_str("S-");
+        }
+        let key = match self.code {
+            KeyCode::F(n) => n.to_string(),
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => {
+                c.to_lowercase().to_string()
+            }
+            KeyCode::Up => String::from("↑"),
+            KeyCode::Down => String::from("↓"),
+            KeyCode::Left => String::from("←"),
+            KeyCode::Right => String::from("→"),
+            KeyCode::Tab => String::from("↹"),
+            KeyCode::Enter => String::from("↵"),
+            KeyCode::Esc => String::from("ESC"),
+            code => KeyChord::new(code, KeyModifiers::NONE).to_string(),
+        };
+        label.push_str(&key);
+        label
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(key: KeyEvent) -> Self {
+        KeyChord::new(key.code, key.modifiers)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // last part is a key, so `Ctrl++` is Ctrl with plus
+        while let Some((modifier, tail)) = rest.split_once('+').filter(|(_, tail)| !tail.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => anyhow::bail!("Unknown modifier {} in {}", modifier, text),
+            };
+            rest = tail;
+        }
+        let code = match rest.to_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "del" | "delete" => KeyCode::Delete,
+            "ins" | "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pgup" | "pageup" => KeyCode::PageUp,
+            "pgdn" | "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            key if key.len() > 1 && key.starts_with('f') => {
+                let n: u8 = key[1..]
+                    .parse()
+                    .with_context(|| format!("Unknown key {}", text))?;
+                KeyCode::F(n)
+            }
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => anyhow::bail!("Unknown key {}", text),
+                }
+            }
+        };
+        Ok(KeyChord::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if c.is_ascii_uppercase() => write!(f, "Shift+{}", c.to_ascii_lowercase()),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::Delete => write!(f, "Del"),
+            KeyCode::Insert => write!(f, "Ins"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Key bindings from config file. Keys are chords separated by space, e.g.
+/// `"Ctrl+K Ctrl+D" = "duplicate_line"`, they replace default bindings of the same keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeySettings {
+    pub panel: BTreeMap<String, Command>,
+    pub editor: BTreeMap<String, Command>,
+    pub popup: BTreeMap<String, Command>,
+    pub viewer: BTreeMap<String, Command>,
+}
+
+/// Result of looking up keys pressed so far
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolved {
+    Command(Command),
+    /// Keys are beginning of longer sequence
+    Pending,
+    /// Sequence did not match, keys are dropped
+    Ignored,
+    /// Single key without binding, it can be used as text input
+    Unbound,
+}
+
+type Binding = (Vec<KeyChord>, Command);
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: BTreeMap<KeyContext, Vec<Binding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&KeySettings::default()).expect("Default key bindings should be valid")
+    }
+}
+
+impl Keymap {
+    /// Default bindings with user's bindings applied on top
+    pub fn new(settings: &KeySettings) -> anyhow::Result<Keymap> {
+        let mut bindings = BTreeMap::new();
+        for (context, defaults, overrides) in [
+            (KeyContext::Panel, PANEL_KEYS, &settings.panel),
+            (KeyContext::Editor, EDITOR_KEYS, &settings.editor),
+            (KeyContext::Popup, POPUP_KEYS, &settings.popup),
+            (KeyContext::Viewer, VIEWER_KEYS, &settings.viewer),
+        ] {
+            let mut list: Vec<Binding> = vec![];
+            for (keys, command) in overrides {
+                let typed = parse_sequence(keys)?[0].is_text();
+                // command line gets these keys before panel bindings are looked up
+                if context == KeyContext::Panel && typed && *command != Command::Unbound {
+                    anyhow::bail!("{} is typed into command line, bind it with Ctrl or Alt", keys);
+                }
+            }
+            let user = overrides.iter().map(|(keys, command)| (keys.as_str(), *command));
+            for (keys, command) in defaults.iter().copied().chain(user) {
+                let keys = parse_sequence(keys)?;
+                list.retain(|(bound, _)| *bound != keys);
+                list.push((keys, command));
+            }
+            list.retain(|(_, command)| *command != Command::Unbound);
+            for (keys, _) in &list {
+                if let Some((longer, _)) = list
+                    .iter()
+                    .find(|(other, _)| other.len() > keys.len() && other.starts_with(keys))
+                {
+                    anyhow::bail!(
+                        "{} is bound, so it can't start {}",
+                        format_sequence(keys),
+                        format_sequence(longer)
+                    );
+                }
+            }
+            bindings.insert(context, list);
+        }
+        Ok(Keymap { bindings })
+    }
+
+    pub fn resolve(&self, context: KeyContext, keys: &[KeyChord]) -> Resolved {
+        let list = &self.bindings[&context];
+        if let Some((_, command)) = list.iter().find(|(bound, _)| bound == keys) {
+            return Resolved::Command(*command);
+        }
+        if list.iter().any(|(bound, _)| bound.starts_with(keys)) {
+            return Resolved::Pending;
+        }
+        if keys.len() > 1 {
+            Resolved::Ignored
+        } else {
+            Resolved::Unbound
+        }
+    }
+
+    /// First keys bound to the command
+    pub fn keys_for(&self, context: KeyContext, command: Command) -> Option<&[KeyChord]> {
+        self.bindings[&context]
+            .iter()
+            .find(|(_, bound)| *bound == command)
+            .map(|(keys, _)| keys.as_slice())
+    }
+
+    /// Help line label of the command keys
+    pub fn label(&self, context: KeyContext, command: Command) -> Option<String> {
+        let keys = self.keys_for(context, command)?;
+        Some(
+            keys.iter()
+                .map(|key| key.short_label())
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+}
+
+/// Keys of multi key sequence typed so far
+#[derive(Debug, Clone, Default)]
+pub struct PendingKeys {
+    keys: Vec<KeyChord>,
+}
+
+impl PendingKeys {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn resolve(&mut self, keymap: &Keymap, context: KeyContext, key: KeyEvent) -> Resolved {
+        self.keys.push(KeyChord::from(key));
+        let resolved = keymap.resolve(context, &self.keys);
+        if resolved != Resolved::Pending {
+            self.keys.clear();
+        }
+        resolved
+    }
+}
+
+fn parse_sequence(text: &str) -> anyhow::Result<Vec<KeyChord>> {
+    let keys = text
+        .split_whitespace()
+        .map(KeyChord::from_str)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if keys.is_empty() {
+        anyhow::bail!("Key binding can't be empty");
+    }
+    Ok(keys)
+}
+
+fn format_sequence(keys: &[KeyChord]) -> String {
+    keys.iter()
+        .map(|key| key.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// First binding of a command is shown in help line
+const PANEL_KEYS: &[(&str, Command)] = &[
+    ("Left", Command::PageUp),
+    ("Right", Command::PageDown),
+    ("Up", Command::CursorUp),
+    ("Down", Command::CursorDown),
+    ("PgUp", Command::PageUp),
+    ("PgDn", Command::PageDown),
+    ("Home", Command::Home),
+    ("End", Command::End),
+    ("Enter", Command::Open),
+    ("Ctrl+PgDn", Command::EnterArchive),
+    ("Tab", Command::SwitchPanel),
+    ("Ins", Command::Mark),
+    ("Ctrl+a", Command::MarkAll),
+    ("F3", Command::View),
+    ("Alt+F3", Command::ExternalView),
+    ("F4", Command::Edit),
+    ("Alt+F4", Command::ExternalEdit),
+    ("Alt+o", Command::OpenWith),
+    ("Shift+F1", Command::Pack),
+    ("Alt+c", Command::Connect),
+    ("Alt+n", Command::Network),
+    ("F5", Command::Copy),
+    ("F6", Command::Move),
+    ("Shift+F6", Command::Rename),
+    ("F7", Command::MakeDir),
+    ("Alt+F7", Command::Find),
+    ("F8", Command::Delete),
+    ("F9", Command::Settings),
+    ("F10", Command::Quit),
+    ("Ctrl+c", Command::Quit),
+    ("Ctrl+o", Command::ToggleOutput),
+    ("Ctrl+Enter", Command::InsertFileName),
+    ("Ctrl+j", Command::InsertFileName),
+    ("Ctrl+f", Command::InsertPath),
+    ("Ctrl+e", Command::HistoryPrev),
+    ("Ctrl+x", Command::HistoryNext),
+    ("Ctrl+F3", Command::SortByName),
+    ("Ctrl+F4", Command::SortByExtension),
+    ("Ctrl+F5", Command::SortByModified),
+    ("Ctrl+F6", Command::SortBySize),
+    ("Ctrl+t", Command::ToggleViewMode),
+    ("Alt+Left", Command::HistoryBack),
+    ("Alt+Right", Command::HistoryForward),
+    ("Alt+F12", Command::FolderHistory),
+    ("Ctrl+\\", Command::Hotlist),
+    ("Ctrl+0", Command::QuickBookmark),
+    ("Ctrl+1", Command::QuickBookmark),
+    ("Ctrl+2", Command::QuickBookmark),
+    ("Ctrl+3", Command::QuickBookmark),
+    ("Ctrl+4", Command::QuickBookmark),
+    ("Ctrl+5", Command::QuickBookmark),
+    ("Ctrl+6", Command::QuickBookmark),
+    ("Ctrl+7", Command::QuickBookmark),
+    ("Ctrl+8", Command::QuickBookmark),
+    ("Ctrl+9", Command::QuickBookmark),
+    ("Ctrl+Shift+0", Command::SetQuickBookmark),
+    ("Ctrl+Shift+1", Command::SetQuickBookmark),
+    ("Ctrl+Shift+2", Command::SetQuickBookmark),
+    ("Ctrl+Shift+3", Command::SetQuickBookmark),
+    ("Ctrl+Shift+4", Command::SetQuickBookmark),
+    ("Ctrl+Shift+5", Command::SetQuickBookmark),
+    ("Ctrl+Shift+6", Command::SetQuickBookmark),
+    ("Ctrl+Shift+7", Command::SetQuickBookmark),
+    ("Ctrl+Shift+8", Command::SetQuickBookmark),
+    ("Ctrl+Shift+9", Command::SetQuickBookmark),
+];
+
+const EDITOR_KEYS: &[(&str, Command)] = &[
+    ("Esc", Command::Close),
+    ("Ctrl+s", Command::Save),
+    ("F2", Command::Save),
+    ("Ctrl+z", Command::Undo),
+    // legacy terminals send Ctrl+Shift+z as Ctrl+z
+    ("Ctrl+r", Command::Redo),
+    ("Ctrl+Shift+z", Command::Redo),
+    ("Ctrl+c", Command::Copy),
+    ("Ctrl+Ins", Command::Copy),
+    ("Ctrl+x", Command::Cut),
+    ("Shift+Del", Command::Cut),
+    ("Ctrl+v", Command::Paste),
+    ("Shift+Ins", Command::Paste),
+    ("Ctrl+d", Command::DuplicateLine),
+    ("Ctrl+y", Command::DeleteLine),
+    ("Alt+Up", Command::MoveLineUp),
+    ("Alt+Down", Command::MoveLineDown),
+];
+
+const POPUP_KEYS: &[(&str, Command)] = &[
+    ("Up", Command::CursorUp),
+    ("Down", Command::CursorDown),
+    ("PgUp", Command::PageUp),
+    ("PgDn", Command::PageDown),
+    ("Home", Command::Home),
+    ("End", Command::End),
+    ("Left", Command::FocusPrev),
+    ("Right", Command::FocusNext),
+    ("Enter", Command::Confirm),
+    ("Esc", Command::Cancel),
+    ("F2", Command::Save),
+    ("Ctrl+s", Command::Save),
+    ("Ins", Command::AddItem),
+    ("F4", Command::Edit),
+    ("Del", Command::Delete),
+    ("F8", Command::Delete),
+];
+
+const VIEWER_KEYS: &[(&str, Command)] = &[
+    ("Up", Command::CursorUp),
+    ("Down", Command::CursorDown),
+    ("PgUp", Command::PageUp),
+    ("PgDn", Command::PageDown),
+    ("Home", Command::Home),
+    ("End", Command::End),
+    ("Ctrl+o", Command::ToggleOutput),
+    ("Esc", Command::Close),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_key_test() {
+        let key: KeyChord = "Ctrl+Shift+Z".parse().unwrap();
+        assert_eq!(
+            key,
+            KeyChord::new(KeyCode::Char('z'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+        );
+        assert_eq!(key.to_string(), "Ctrl+Shift+z");
+        assert_eq!("alt+f4".parse::<KeyChord>().unwrap().short_label(), "M-4");
+        assert_eq!("Ctrl++".parse::<KeyChord>().unwrap().to_string(), "Ctrl++");
+        let key: KeyChord = "Ctrl+Shift+1".parse().unwrap();
+        assert_ne!(key, "Ctrl+1".parse().unwrap());
+        assert_eq!(key.to_string(), "Ctrl+Shift+1");
+        assert!("Hyper+a".parse::<KeyChord>().is_err());
+        assert!("Foo".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn sequence_test() {
+        let mut settings = KeySettings::default();
+        settings
+            .editor
+            .insert(String::from("Ctrl+k Ctrl+d"), Command::DuplicateLine);
+        settings.editor.insert(String::from("Ctrl+d"), Command::Unbound);
+        let keymap = Keymap::new(&settings).unwrap();
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let mut pending = PendingKeys::default();
+        assert_eq!(pending.resolve(&keymap, KeyContext::Editor, ctrl('d')), Resolved::Unbound);
+        assert_eq!(pending.resolve(&keymap, KeyContext::Editor, ctrl('k')), Resolved::Pending);
+        assert_eq!(
+            pending.resolve(&keymap, KeyContext::Editor, ctrl('d')),
+            Resolved::Command(Command::DuplicateLine)
+        );
+        assert_eq!(pending.resolve(&keymap, KeyContext::Editor, ctrl('k')), Resolved::Pending);
+        assert_eq!(pending.resolve(&keymap, KeyContext::Editor, ctrl('q')), Resolved::Ignored);
+        assert!(pending.is_empty());
+        assert_eq!(keymap.label(KeyContext::Editor, Command::DuplicateLine).unwrap(), "^k ^d");
+        assert_eq!(
+            pending.resolve(&keymap, KeyContext::Editor, ctrl('r')),
+            Resolved::Command(Command::Redo)
+        );
+
+        settings.editor.insert(String::from("Ctrl+k"), Command::Undo);
+        assert!(Keymap::new(&settings).is_err());
+        let mut settings = KeySettings::default();
+        settings.panel.insert(String::from("q"), Command::Quit);
+        assert!(Keymap::new(&settings).is_err());
+        settings.panel.insert(String::from("q"), Command::Unbound);
+        settings.panel.insert(String::from("Alt+q"), Command::Quit);
+        assert!(Keymap::new(&settings).is_ok());
+    }
+}
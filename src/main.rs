@@ -1,16 +1,104 @@
+use std::{env, process};
+
+use args::{read_list, Args, USAGE};
+use config::{config_path, Settings};
+use connections::{connections_path, secrets_path, Connections};
+use hotlist::{hotlist_path, Hotlist};
+use session::{session_path, Session};
 use termination::{create_termination, Interrupted};
-use state_management::StateStore;
+use state_management::{action::Action, StartOptions, StateStore};
 use ui_management::UiManager;
 
+mod args;
+mod config;
+mod connection;
+mod connections;
+mod highlight;
+mod hotlist;
+mod keymap;
+mod search;
+mod session;
 mod state_management;
 mod ui_management;
 mod termination;
+#[cfg(test)]
+mod test_dir;
+mod theme;
+mod vfs;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) if args.help => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Ok(args) if args.version => {
+            println!("fir {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        result => match result.and_then(Args::checked) {
+            Ok(args) => args,
+            Err(err) => {
+                eprintln!("fir: {:#}\n\n{}", err, USAGE);
+                process::exit(2);
+            }
+        },
+    };
+
     let (terminator, mut interrupt_rx) = create_termination();
-    let (state_store, state_rx) = StateStore::new();
-    let (ui_manager, action_rx) = UiManager::new();
+    let config_path = args.config.clone().or_else(config_path);
+    let (settings, mut startup_error) = Settings::load(config_path.as_deref());
+    let session_path = match args.no_session {
+        true => None,
+        false => args.session_file.clone().or_else(session_path),
+    };
+    let session = match &session_path {
+        Some(_) if args.fresh => None,
+        Some(path) => match Session::load(path) {
+            Ok(session) => Some(session),
+            Err(err) => {
+                startup_error.get_or_insert(format!("Error in {}: {:#}", path.display(), err));
+                None
+            }
+        },
+        None => None,
+    };
+    let hotlist = Hotlist::load(hotlist_path()).unwrap_or_else(|err| {
+        startup_error.get_or_insert(format!("Error in hotlist: {:#}", err));
+        // without path broken file is not overwritten
+        Hotlist::default()
+    });
+    let connections = Connections::load(connections_path(), secrets_path()).unwrap_or_else(|err| {
+        startup_error.get_or_insert(format!("Error in saved connections: {:#}", err));
+        Connections::default()
+    });
+    let list = args.list.as_deref().and_then(|file| match read_list(file) {
+        Ok(files) => Some(files),
+        Err(err) => {
+            startup_error.get_or_insert(format!("{:#}", err));
+            None
+        }
+    });
+    let start = StartOptions {
+        error: startup_error,
+        config_path,
+        session,
+        session_path,
+        left_dir: args.left.clone(),
+        right_dir: args.right.clone(),
+        list,
+        hotlist,
+        connections,
+    };
+    let (state_store, state_rx) = StateStore::new(settings.clone(), start);
+    let (ui_manager, action_rx) = UiManager::new(&settings, args.mouse);
+    if let Some(file) = &args.edit {
+        ui_manager.dispatch(Action::Edit(file.display().to_string()));
+    }
+    if let Some(file) = &args.view {
+        ui_manager.dispatch(Action::View(file.display().to_string()));
+    }
 
     tokio::try_join!(
         state_store.main_loop(terminator, action_rx, interrupt_rx.resubscribe()),
@@ -0,0 +1,471 @@
+use std::{
+    collections::HashSet,
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use encoding_rs::Encoding;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    state_management::mask,
+    vfs::{DirEntry, VfsHandle},
+};
+
+/// Fields of find dialog switched on and off instead of typed
+pub const SWITCHES: &[&str] = &["regex", "case sensitive", "whole words", "follow symlinks"];
+/// File content is read and searched in chunks of this size
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Text without line breaks is searched once it grows this long
+const MAX_LINE: usize = 1024 * 1024;
+
+/// Settings of find file dialog, kept between searches
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOptions {
+    /// File name masks, e.g. `*.rs,*.toml`
+    pub mask: String,
+    /// Text files must contain, any file matches if empty
+    pub text: String,
+    /// Text is regular expression
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_words: bool,
+    /// Label of encoding files are decoded with, e.g. `utf-16le` or `windows-1251`.
+    /// Byte order mark takes precedence.
+    pub encoding: String,
+    /// Size limits in bytes with optional `K`, `M` or `G` suffix, no limit if empty
+    pub min_size: String,
+    pub max_size: String,
+    /// Modification dates as `YYYY-MM-DD` in local time, no limit if empty.
+    /// Files modified on `after` date match, on `before` date they don't.
+    pub after: String,
+    pub before: String,
+    /// Levels of subdirectories searched, unlimited if None
+    pub depth: Option<usize>,
+    /// Directories behind symbolic links are searched too
+    pub follow_symlinks: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            mask: String::from("*"),
+            text: String::new(),
+            regex: false,
+            case_sensitive: false,
+            whole_words: false,
+            encoding: String::from("utf-8"),
+            min_size: String::new(),
+            max_size: String::new(),
+            after: String::new(),
+            before: String::new(),
+            depth: None,
+            follow_symlinks: false,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Names and values of dialog fields
+    pub fn fields(&self) -> Vec<(String, String)> {
+        let switch = |on: bool| String::from(if on { "yes" } else { "no" });
+        [
+            ("file mask", self.mask.clone()),
+            ("containing text", self.text.clone()),
+            ("regex", switch(self.regex)),
+            ("case sensitive", switch(self.case_sensitive)),
+            ("whole words", switch(self.whole_words)),
+            ("encoding", self.encoding.clone()),
+            ("min size", self.min_size.clone()),
+            ("max size", self.max_size.clone()),
+            ("modified after", self.after.clone()),
+            ("modified before", self.before.clone()),
+            (
+                "depth",
+                self.depth
+                    .map(|depth| depth.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("follow symlinks", switch(self.follow_symlinks)),
+        ]
+        .into_iter()
+        .map(|(name, value)| (String::from(name), value))
+        .collect()
+    }
+
+    pub fn set_field(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        let switch = || match value {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            _ => anyhow::bail!("Use yes or no for {}", name),
+        };
+        let value = String::from(value);
+        match name {
+            "file mask" if value.is_empty() => self.mask = String::from("*"),
+            "file mask" => self.mask = value,
+            "containing text" => self.text = value,
+            "regex" => self.regex = switch()?,
+            "case sensitive" => self.case_sensitive = switch()?,
+            "whole words" => self.whole_words = switch()?,
+            "encoding" => {
+                Encoding::for_label(value.as_bytes())
+                    .with_context(|| format!("Unknown encoding {}", value))?;
+                self.encoding = value;
+            }
+            "min size" => {
+                parse_size(&value)?;
+                self.min_size = value;
+            }
+            "max size" => {
+                parse_size(&value)?;
+                self.max_size = value;
+            }
+            "modified after" => {
+                parse_date(&value)?;
+                self.after = value;
+            }
+            "modified before" => {
+                parse_date(&value)?;
+                self.before = value;
+            }
+            "depth" if value.is_empty() => self.depth = None,
+            "depth" => self.depth = Some(value.parse().context("Depth must be a number")?),
+            "follow symlinks" => self.follow_symlinks = switch()?,
+            _ => anyhow::bail!("Unknown field {}", name),
+        }
+        Ok(())
+    }
+
+    /// Checks options and prepares them for search
+    pub fn matcher(&self) -> anyhow::Result<Matcher> {
+        let content = match self.text.as_str() {
+            "" => None,
+            text => {
+                let pattern = match self.regex {
+                    true => String::from(text),
+                    false => regex::escape(text),
+                };
+                let pattern = match self.whole_words {
+                    true => format!(r"\b(?:{})\b", pattern),
+                    false => pattern,
+                };
+                let regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(!self.case_sensitive)
+                    .multi_line(true)
+                    .build()
+                    .context("Invalid regular expression")?;
+                Some(regex)
+            }
+        };
+        let encoding = Encoding::for_label(self.encoding.as_bytes())
+            .with_context(|| format!("Unknown encoding {}", self.encoding))?;
+        Ok(Matcher {
+            mask: self.mask.clone(),
+            content,
+            encoding,
+            min_size: parse_size(&self.min_size)?,
+            max_size: parse_size(&self.max_size)?,
+            after: parse_date(&self.after)?,
+            before: parse_date(&self.before)?,
+            depth: self.depth,
+            follow_symlinks: self.follow_symlinks,
+        })
+    }
+}
+
+/// What search is doing, reported while it walks directories
+pub enum Progress<'a> {
+    /// Directory is being searched
+    Directory(&'a Path),
+    Found(&'a Path),
+}
+
+/// Checked search options
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    mask: String,
+    content: Option<Regex>,
+    encoding: &'static Encoding,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    after: Option<SystemTime>,
+    before: Option<SystemTime>,
+    depth: Option<usize>,
+    follow_symlinks: bool,
+}
+
+impl Matcher {
+    /// Walks directory tree below `root`, reporting matching files and directories in
+    /// name order. Directories match only when content and size don't matter.
+    /// Unreadable directories and files are skipped, setting `cancel` stops the search.
+    pub fn search(
+        &self,
+        vfs: &VfsHandle,
+        root: &Path,
+        cancel: &AtomicBool,
+        progress: &mut dyn FnMut(Progress),
+    ) {
+        let mut visited = HashSet::new();
+        self.walk(vfs, root, 0, &mut visited, cancel, progress);
+    }
+
+    fn walk(
+        &self,
+        vfs: &VfsHandle,
+        dir: &Path,
+        depth: usize,
+        visited: &mut HashSet<PathBuf>,
+        cancel: &AtomicBool,
+        progress: &mut dyn FnMut(Progress),
+    ) {
+        // symbolic links may lead back to directory being searched
+        if self.follow_symlinks && !visited.insert(vfs.normalize(dir).unwrap_or_default()) {
+            return;
+        }
+        progress(Progress::Directory(dir));
+        let mut entries = vfs.list(dir).unwrap_or_default();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        for entry in entries {
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let path = dir.join(&entry.name);
+            if self.matches(vfs, &path, &entry, cancel) {
+                progress(Progress::Found(&path));
+            }
+            let info = &entry.info;
+            let deeper = self.depth.is_none_or(|limit| depth < limit);
+            if info.is_dir && deeper && (self.follow_symlinks || !info.is_symlink) {
+                self.walk(vfs, &path, depth + 1, visited, cancel, progress);
+            }
+        }
+    }
+
+    fn matches(&self, vfs: &VfsHandle, path: &Path, entry: &DirEntry, cancel: &AtomicBool) -> bool {
+        let info = &entry.info;
+        if !mask::matches(&self.mask, &entry.name) {
+            return false;
+        }
+        let in_range = |time: Option<SystemTime>| {
+            self.after
+                .is_none_or(|after| time.is_some_and(|time| time >= after))
+                && self
+                    .before
+                    .is_none_or(|before| time.is_some_and(|time| time < before))
+        };
+        if !in_range(info.modified) {
+            return false;
+        }
+        if info.is_dir {
+            return self.content.is_none() && self.min_size.is_none() && self.max_size.is_none();
+        }
+        let size = info.size;
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        match &self.content {
+            Some(regex) => self.contains(vfs, path, regex, cancel),
+            None => true,
+        }
+    }
+
+    /// File content decoded with chosen encoding matches, lines are searched
+    /// a chunk at a time
+    fn contains(&self, vfs: &VfsHandle, path: &Path, regex: &Regex, cancel: &AtomicBool) -> bool {
+        let Ok(mut reader) = vfs.open_read(path) else {
+            return false;
+        };
+        let mut decoder = self.encoding.new_decoder();
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let mut text = String::new();
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return false;
+            }
+            let read = match reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            };
+            let last = read == 0;
+            let needed = decoder
+                .max_utf8_buffer_length(read)
+                .unwrap_or(read * 3 + 16);
+            text.reserve(needed);
+            // output always fits in space reserved above
+            let _ = decoder.decode_to_string(&chunk[..read], &mut text, last);
+            // incomplete last line waits for the rest of it
+            let end = match text.rfind('\n') {
+                _ if last || text.len() > MAX_LINE => text.len(),
+                Some(newline) => newline + 1,
+                None => 0,
+            };
+            if regex.is_match(&text[..end]) {
+                return true;
+            }
+            if last {
+                return false;
+            }
+            text.drain(..end);
+        }
+    }
+}
+
+/// Bytes of size like `512`, `10K` or `1.5M`, None if empty
+fn parse_size(text: &str) -> anyhow::Result<Option<u64>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let upper = text.to_uppercase();
+    let number = upper.trim_end_matches('B');
+    let (number, unit) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 1 << 10),
+        Some('M') => (&number[..number.len() - 1], 1 << 20),
+        Some('G') => (&number[..number.len() - 1], 1 << 30),
+        _ => (number, 1),
+    };
+    let value: f64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid size {}, use e.g. 100, 10K or 2M", text))?;
+    if value < 0.0 {
+        anyhow::bail!("Size can't be negative");
+    }
+    Ok(Some((value * unit as f64) as u64))
+}
+
+/// Local midnight of `YYYY-MM-DD` date, None if empty
+fn parse_date(text: &str) -> anyhow::Result<Option<SystemTime>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let parts: Vec<u32> = text
+        .split('-')
+        .map(|part| part.parse())
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("Invalid date {}, use YYYY-MM-DD", text))?;
+    match parts.as_slice() {
+        [year, month @ 1..=12, day @ 1..=31] => {
+            midnight(*year, *month, *day).with_context(|| format!("Invalid date {}", text))
+        }
+        _ => anyhow::bail!("Invalid date {}, use YYYY-MM-DD", text),
+    }
+    .map(Some)
+}
+
+#[cfg(unix)]
+fn midnight(year: u32, month: u32, day: u32) -> Option<SystemTime> {
+    // SAFETY: all zero bytes are a valid `tm`, mktime only reads and normalizes it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year as i32 - 1900;
+    tm.tm_mon = month as i32 - 1;
+    tm.tm_mday = day as i32;
+    tm.tm_isdst = -1;
+    let secs = unsafe { libc::mktime(&mut tm) };
+    let secs = u64::try_from(secs).ok()?;
+    Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+}
+
+#[cfg(not(unix))]
+fn midnight(year: u32, month: u32, day: u32) -> Option<SystemTime> {
+    crate::vfs::time_of(year as u64, month as u64, day as u64, 0)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn options_test() {
+        let mut options = SearchOptions::default();
+        assert!(options.set_field("encoding", "klingon").is_err());
+        options.set_field("encoding", "windows-1251").unwrap();
+        assert!(options.set_field("min size", "big").is_err());
+        assert_eq!(parse_size("10K").unwrap(), Some(10240));
+        assert_eq!(parse_size("1.5mb").unwrap(), Some(1572864));
+        assert!(options.set_field("modified after", "2024-13-01").is_err());
+        assert!(parse_date("2024-05-01").unwrap().is_some());
+        options.set_field("regex", "yes").unwrap();
+        assert!(options.set_field("regex", "maybe").is_err());
+        options.set_field("file mask", "").unwrap();
+        assert_eq!(options.mask, "*");
+        for (name, value) in options.fields() {
+            options.set_field(&name, &value).unwrap();
+        }
+        options.set_field("containing text", "(unclosed").unwrap();
+        assert!(options.matcher().is_err());
+    }
+
+    #[test]
+    fn search_test() {
+        let dir = TestDir::new("search");
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/deep")).unwrap();
+        fs::write(root.join("notes.txt"), "Hello World\n").unwrap();
+        fs::write(
+            root.join("src/main.rs"),
+            "fn main() {\n    println!(\"hello worldwide\");\n}\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/deep/lib.rs"), "// nothing here\n").unwrap();
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(
+                "a hello in utf-16"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            )
+            .collect();
+        fs::write(root.join("wide.txt"), utf16).unwrap();
+        let vfs = VfsHandle::default();
+        let find = |options: &SearchOptions| {
+            let mut found = vec![];
+            options.matcher().unwrap().search(
+                &vfs,
+                root,
+                &AtomicBool::new(false),
+                &mut |progress| {
+                    if let Progress::Found(path) = progress {
+                        found.push(path.strip_prefix(root).unwrap().display().to_string());
+                    }
+                },
+            );
+            found
+        };
+
+        let mut options = SearchOptions {
+            mask: String::from("*.rs"),
+            ..Default::default()
+        };
+        assert_eq!(find(&options), vec!["src/deep/lib.rs", "src/main.rs"]);
+        options.depth = Some(1);
+        assert_eq!(find(&options), vec!["src/main.rs"]);
+        options.mask = String::from("*");
+        options.depth = None;
+        options.text = String::from("hello");
+        assert_eq!(find(&options), vec!["notes.txt", "src/main.rs", "wide.txt"]);
+        options.case_sensitive = true;
+        assert_eq!(find(&options), vec!["src/main.rs", "wide.txt"]);
+        options.case_sensitive = false;
+        options.text = String::from("world");
+        options.whole_words = true;
+        assert_eq!(find(&options), vec!["notes.txt"]);
+        options.text = String::from(r"^\s+print");
+        options.regex = true;
+        options.whole_words = false;
+        assert_eq!(find(&options), vec!["src/main.rs"]);
+        options.text = String::new();
+        options.min_size = String::from("20");
+        assert_eq!(find(&options), vec!["src/main.rs", "wide.txt"]);
+    }
+}
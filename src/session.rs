@@ -0,0 +1,138 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{config_dir, SortOrder, ViewMode},
+    state_management::{PanelData, State},
+};
+
+const SESSION_FILE: &str = "session.toml";
+
+/// Panels, editor and command history saved on exit and restored on next start
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Right panel had focus
+    pub right_active: bool,
+    /// File open in internal editor, remote one after prefix of its filesystem
+    pub editor_file: Option<String>,
+    /// Text of editor which wasn't saved, the file is reopened modified with it
+    pub editor_text: Option<String>,
+    pub command_history: Vec<String>,
+    /// Directories visited in both panels, oldest first
+    pub folder_history: Vec<PathBuf>,
+    pub left: PanelSession,
+    pub right: PanelSession,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanelSession {
+    pub path: PathBuf,
+    pub sort: SortOrder,
+    pub sort_reverse: bool,
+    pub view_mode: ViewMode,
+    /// File under cursor
+    pub current_file: String,
+}
+
+impl Session {
+    /// Missing file gives empty session
+    pub fn load(path: &Path) -> anyhow::Result<Session> {
+        if !path.exists() {
+            return Ok(Session::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl From<&PanelData> for PanelSession {
+    fn from(panel: &PanelData) -> Self {
+        // panel showing archive is restored in directory of the archive
+        let current_file = match panel.vfs.is_local() {
+            true => panel.current_file_name(),
+            false => String::new(),
+        };
+        PanelSession {
+            path: panel.local_dir(),
+            sort: panel.sort,
+            sort_reverse: panel.sort_reverse,
+            view_mode: panel.view_mode,
+            current_file,
+        }
+    }
+}
+
+impl From<&State> for Session {
+    fn from(state: &State) -> Self {
+        Session {
+            right_active: state.r_panel.active,
+            editor_file: editor_origin(state),
+            editor_text: state.editor_text.as_deref().cloned(),
+            command_history: state.command_history.clone(),
+            folder_history: state.folder_history.clone(),
+            left: PanelSession::from(&state.l_panel),
+            right: PanelSession::from(&state.r_panel),
+        }
+    }
+}
+
+/// Remote file edited through local copy is saved instead of the copy
+fn editor_origin(state: &State) -> Option<String> {
+    let file = state.editor_file.as_ref()?;
+    match &state.remote_edit {
+        Some(edit) if edit.local == Path::new(file) => {
+            Some(format!("{}{}", edit.vfs.prefix(), edit.path.display()))
+        }
+        _ => Some(file.clone()),
+    }
+}
+
+pub fn session_path() -> Option<PathBuf> {
+    Some(config_dir()?.join(SESSION_FILE))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn save_load_test() {
+        let dir = TestDir::new("session");
+        let path = dir.path().join(SESSION_FILE);
+        assert_eq!(Session::load(&path).unwrap(), Session::default());
+
+        let session = Session {
+            right_active: true,
+            editor_file: Some(String::from("sftp://me@example.com/tmp/notes.txt")),
+            editor_text: Some(String::from("first\nsecond\n")),
+            command_history: vec![String::from("ls -l"), String::from("make")],
+            folder_history: vec![PathBuf::from("/usr/lib"), PathBuf::from("/usr")],
+            left: PanelSession {
+                path: PathBuf::from("/usr"),
+                sort: SortOrder::Size,
+                sort_reverse: true,
+                view_mode: ViewMode::Full,
+                current_file: String::from("lib"),
+            },
+            right: PanelSession::default(),
+        };
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), session);
+    }
+}
@@ -1,3 +1,7 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::config::SortOrder;
+
 use super::state_store::PanelPosition;
 
 #[derive(Debug, Clone)]
@@ -6,21 +10,114 @@ pub enum Action {
     Edit(String),
     EditorResedModified,
     EditorModified,
+    /// Text of modified editor, kept in session if app is killed before saving
+    EditorText(Arc<String>),
     EditorExit,
+    /// External programs
+    ExternalEdit(String),
+    ExternalView(String),
+    /// File associations
+    View(String),
+    /// Edit with associated command, falls back to internal editor
+    EditAssociated(String),
+    OpenWithMenu(String),
+    /// Runs command with placeholders for given file
+    RunAssociated(String, String),
+    OpenDefault(String),
+    /// Command line
+    RunCommand(String),
+    /// Output of command run from command line
+    CommandOutput(String),
+    ToggleOutput,
+    /// Keys typed while subshell runs a command
+    SubshellInput(String),
+    Resize(u16, u16),
     /// File panel actions
     FileItemUp(usize),
     FileItemDown(usize),
     Cd(String),
     Open(String),
+    /// Shows content of archive file in panel
+    EnterArchive(String),
+    /// Packing marked files, or given file if nothing is marked, asks whether to add
+    /// them to archive open in the other panel or to create new one
+    PackInput(String),
+    PackNameInput(String),
+    /// Asks for compression level. With true archive name is taken from popup input,
+    /// otherwise files are added to archive of the other panel.
+    PackLevels(String, bool),
+    /// Starts packing with archive name, None for archive of the other panel, and level
+    Pack(String, Option<String>, u32),
     Copy(String, String),
-    Move(String, String),
+    /// Asks where to move marked files, or given file if nothing is marked, starting
+    /// with given directory
+    MoveInput(String, String),
+    /// Asks for new name of given file
+    RenameInput(String),
+    /// Moves marked files when true, otherwise given file, to path typed in prompt
+    Move(String, bool),
     SetInput(String),
     RmYesNo(String),
     MkDirInput,
     Rm(String),
     MkDir,
     SwitchTabs,
+    /// Marks file under cursor, or all files of active panel
+    Mark,
+    MarkAll,
     Reload(PanelPosition),
+    SortBy(SortOrder),
+    ToggleViewMode,
+    /// Directory history of active panel
+    HistoryBack,
+    HistoryForward,
+    FolderHistory,
+    /// Changes active panel to given directory
+    GoTo(PathBuf),
+    /// Directory hotlist
+    OpenHotlist,
+    /// Changes active panel to directory of bookmark with given index
+    JumpBookmark(usize),
+    JumpQuickBookmark(u8),
+    SetQuickBookmark(u8),
+    AddBookmarkInput,
+    AddBookmark,
+    RenameBookmarkInput(usize),
+    RenameBookmark(usize),
+    DeleteBookmark(usize),
+    /// Settings dialog
+    OpenSettings,
+    CloseSettings,
+    SetSetting(String, String),
+    SaveSettings,
+    /// Connection dialog
+    OpenConnect,
+    CloseConnect,
+    SetConnectField(String, String),
+    /// Connects active panel to server of the dialog, trusting unknown host with given fingerprint
+    Connect(Option<String>),
+    /// Saved connections listed in active panel, indexes are of the list
+    OpenNetwork,
+    ConnectProfile(usize),
+    /// Connection dialog for new saved connection, or for editing one
+    AddProfile,
+    EditProfile(usize),
+    /// Saves connection of the dialog under name entered there
+    SaveProfile,
+    DuplicateProfile(usize),
+    DeleteProfileYesNo(usize),
+    DeleteProfile(usize),
+    /// Find file dialog, searching in directory of active panel
+    OpenFind,
+    CloseFind,
+    SetFindField(String, String),
+    StartSearch,
+    StopSearch,
+    /// Shows file found with given index in active panel
+    GoToFound(usize),
+    /// Lists all found files in active panel
+    SearchPanel,
+    Error(String),
     Cancel,
     Exit,
 }
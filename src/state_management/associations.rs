@@ -0,0 +1,213 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::vfs;
+
+use super::mask;
+
+/// Commands bound to files matching mask. Mask is either file name mask (`*.png,*.jpg`)
+/// or MIME type pattern (`image/*`).
+///
+/// Commands may contain placeholders:
+/// * `!.!` - file name with extension
+/// * `!@!` - name of temporary file with list of selected files, one per line
+/// * `!!` - `!` character
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Association {
+    pub mask: String,
+    #[serde(default)]
+    pub description: String,
+    pub execute: Option<String>,
+    pub view: Option<String>,
+    pub edit: Option<String>,
+}
+
+impl Association {
+    pub fn new(mask: &str, description: &str) -> Self {
+        Association {
+            mask: String::from(mask),
+            description: String::from(description),
+            ..Default::default()
+        }
+    }
+
+    pub fn execute(mut self, command: &str) -> Self {
+        self.execute = Some(String::from(command));
+        self
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.mask.contains('/') {
+            mime_type(path).is_some_and(|mime| mask::matches(&self.mask, mime))
+        } else {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            mask::matches(&self.mask, &name)
+        }
+    }
+}
+
+pub fn default_associations() -> Vec<Association> {
+    vec![Association::new("*.sh", "Shell script").execute("sh !.!")]
+}
+
+/// Finds first association matching path, which has command selected by `command`
+pub fn find(
+    associations: &[Association],
+    path: &Path,
+    command: impl Fn(&Association) -> &Option<String>,
+) -> Option<String> {
+    associations
+        .iter()
+        .filter(|association| association.matches(path))
+        .find_map(|association| command(association).clone())
+}
+
+/// Guesses MIME type by file extension
+pub fn mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let mime = match extension.as_str() {
+        "txt" | "log" | "md" | "rs" | "toml" | "c" | "h" | "py" | "js" | "ts" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "mkv" => "video/x-matroska",
+        "webm" => "video/webm",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Command line with placeholders replaced
+#[derive(Debug)]
+pub struct Substituted {
+    pub command_line: String,
+    /// Directory with list of files, to be removed once command exits
+    pub temp_dir: Option<PathBuf>,
+}
+
+/// Replaces placeholders in command. `files` are selected files, first one is the current file.
+pub fn substitute(command: &str, files: &[PathBuf]) -> anyhow::Result<Substituted> {
+    let mut result = String::new();
+    let mut list: Option<PathBuf> = None;
+    let mut rest = command;
+    while let Some(pos) = rest.find('!') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if let Some(tail) = rest.strip_prefix("!.!") {
+            let name = files
+                .first()
+                .and_then(|file| file.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            result.push_str(&quote(&name));
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("!@!") {
+            let list = match &list {
+                Some(list) => list.clone(),
+                None => list.insert(write_list_file(files)?).clone(),
+            };
+            result.push_str(&quote(&list.display().to_string()));
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("!!") {
+            result.push('!');
+            rest = tail;
+        } else {
+            result.push('!');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(Substituted {
+        command_line: result,
+        temp_dir: list.and_then(|list| list.parent().map(Path::to_path_buf)),
+    })
+}
+
+/// Writes file list into new directory of its own in private temporary directory
+fn write_list_file(files: &[PathBuf]) -> anyhow::Result<PathBuf> {
+    let path = vfs::new_temp_dir()?.join("list.txt");
+    let mut f = BufWriter::new(File::options().write(true).create_new(true).open(&path)?);
+    for file in files {
+        writeln!(f, "{}", file.display())?;
+    }
+    f.flush()?;
+    Ok(path)
+}
+
+/// Quotes argument for shell if needed
+fn quote(arg: &str) -> String {
+    let safe = arg
+        .chars()
+        .all(|c| c.is_alphanumeric() || "-_./+,:@%".contains(c));
+    if safe && !arg.is_empty() {
+        return String::from(arg);
+    }
+    if cfg!(target_os = "windows") {
+        format!("\"{}\"", arg)
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn substitute_test() {
+        let files = vec![PathBuf::from("/tmp/my file.txt")];
+        let substituted = substitute("cat !.! !! done", &files).unwrap();
+        assert_eq!(substituted.command_line, "cat 'my file.txt' ! done");
+        assert!(substituted.temp_dir.is_none());
+        let list = substitute("xargs < !@! && wc -l !@!", &files).unwrap();
+        assert!(list.command_line.starts_with("xargs < "));
+        assert!(!list.command_line.contains("!@!"));
+        let dir = list.temp_dir.unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn find_test() {
+        let associations = vec![
+            Association {
+                view: Some(String::from("glow !.!")),
+                ..Association::new("*.md", "Markdown")
+            },
+            Association {
+                view: Some(String::from("less !.!")),
+                edit: Some(String::from("nano !.!")),
+                ..Association::new("text/*", "Text")
+            },
+        ];
+        let path = Path::new("README.md");
+        assert_eq!(find(&associations, path, |a| &a.view).unwrap(), "glow !.!");
+        assert_eq!(find(&associations, path, |a| &a.edit).unwrap(), "nano !.!");
+        assert!(find(&associations, path, |a| &a.execute).is_none());
+    }
+}
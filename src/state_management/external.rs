@@ -0,0 +1,82 @@
+use std::{env, path::PathBuf};
+
+#[cfg(target_os = "windows")]
+const FALLBACK_EDITOR: &str = "notepad";
+#[cfg(not(target_os = "windows"))]
+const FALLBACK_EDITOR: &str = "vi";
+#[cfg(target_os = "windows")]
+const FALLBACK_VIEWER: &str = "more";
+#[cfg(not(target_os = "windows"))]
+const FALLBACK_VIEWER: &str = "less";
+
+/// Program run in foreground while UI is suspended
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    /// Command as user typed it
+    pub title: String,
+    /// Output is shown in terminal and also sent back to be kept in scrollback
+    pub capture: bool,
+    /// Made for the command, e.g. with list of files, removed once it exits
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl ExternalCommand {
+    /// Builds command from configured command line (program with optional arguments) and file to open
+    pub fn new(command_line: &str, file: String, cwd: PathBuf) -> Option<Self> {
+        let mut parts = command_line.split_whitespace().map(String::from);
+        let program = parts.next()?;
+        let mut args: Vec<String> = parts.collect();
+        let title = format!("{} {}", command_line, file);
+        args.push(file);
+        Some(ExternalCommand {
+            program,
+            args,
+            cwd,
+            title,
+            capture: false,
+            temp_dir: None,
+        })
+    }
+
+    /// Runs command line through user's shell
+    pub fn shell(command_line: String, cwd: PathBuf) -> Self {
+        #[cfg(target_os = "windows")]
+        let (program, flag) = (String::from("cmd"), String::from("/C"));
+        #[cfg(not(target_os = "windows"))]
+        let (program, flag) = (
+            env::var("SHELL").unwrap_or_else(|_| String::from("sh")),
+            String::from("-c"),
+        );
+        ExternalCommand {
+            program,
+            title: command_line.clone(),
+            args: vec![flag, command_line],
+            cwd,
+            capture: false,
+            temp_dir: None,
+        }
+    }
+
+    pub fn capture(mut self) -> Self {
+        self.capture = true;
+        self
+    }
+
+    pub fn with_temp_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.temp_dir = dir;
+        self
+    }
+}
+
+pub fn default_editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from(FALLBACK_EDITOR))
+}
+
+pub fn default_viewer() -> String {
+    env::var("PAGER").unwrap_or_else(|_| String::from(FALLBACK_VIEWER))
+}
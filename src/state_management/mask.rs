@@ -0,0 +1,51 @@
+/// Checks file name against FAR style mask list, e.g. `*.rs,*.toml` or `Cargo.*`.
+/// Masks are separated by `,` or `;`, `*` matches any number of characters and `?` exactly one.
+/// Matching is case insensitive.
+pub fn matches(mask: &str, name: &str) -> bool {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    mask.split([',', ';'])
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .any(|m| {
+            let pattern: Vec<char> = m.to_lowercase().chars().collect();
+            wildcard(&pattern, &name)
+        })
+}
+
+fn wildcard(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_test() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(matches("*.txt,*.RS", "Main.rs"));
+        assert!(matches("Cargo.*", "Cargo.toml"));
+        assert!(matches("a?c*", "abcdef"));
+        assert!(matches("*", ".hidden"));
+        assert!(!matches("*.rs", "main.rs.bak"));
+        assert!(!matches("a?c", "ac"));
+        assert!(matches("text/*", "text/plain"));
+    }
+}
@@ -1,8 +1,16 @@
+pub use self::associations::{default_associations, Association};
+pub use self::external::ExternalCommand;
 pub use self::panel_item::PanelItem;
 pub use self::state::*;
-pub use self::state_store::{PanelPosition, StateStore, PopupType};
+pub use self::state_store::{MenuItem, PanelPosition, PopupType, StartOptions, StateStore};
+pub use self::terminal_output::TerminalOutput;
 
 pub mod action;
+mod associations;
+mod external;
+pub mod mask;
 mod panel_item;
 mod state;
 mod state_store;
+mod subshell;
+mod terminal_output;
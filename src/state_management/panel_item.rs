@@ -1,22 +1,55 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use ratatui::{
-    style::{Color, Style},
     text::{Line, Span},
     widgets::ListItem,
 };
 
+use crate::{
+    highlight::{Attribute, Highlighting},
+    theme::Theme,
+    vfs::FileInfo,
+};
+
 #[derive(Debug, Clone)]
 pub struct PanelItem {
     path: PathBuf,
     marked: bool,
+    info: FileInfo,
+    /// Shown instead of size and time by items which aren't files
+    description: Option<String>,
+    /// Shown instead of file name, e.g. path relative to searched directory
+    label: Option<String>,
 }
 
 impl PanelItem {
-    pub fn new(path: PathBuf) -> Self {
+    /// File with properties already read from its filesystem
+    pub fn with_info(path: PathBuf, info: FileInfo) -> Self {
         PanelItem {
             path,
             marked: false,
+            info,
+            description: None,
+            label: None,
+        }
+    }
+
+    /// File listed outside of its directory under another name
+    pub fn with_label(path: PathBuf, info: FileInfo, label: String) -> Self {
+        PanelItem {
+            label: Some(label),
+            ..PanelItem::with_info(path, info)
+        }
+    }
+
+    /// Item which isn't a file, e.g. saved connection, with its own details columns
+    pub fn with_details(path: PathBuf, description: String) -> Self {
+        PanelItem {
+            description: Some(description),
+            ..PanelItem::with_info(path, FileInfo::default())
         }
     }
 
@@ -32,7 +65,7 @@ impl PanelItem {
     }
 
     pub fn is_dir(&self) -> bool {
-        self.path.is_dir()
+        self.info.is_dir
     }
 
     pub fn display_string(&self) -> String {
@@ -43,30 +76,188 @@ impl PanelItem {
         self.path.display().to_string()
     }
 
+    /// Dot files on Unix
+    pub fn is_hidden(&self) -> bool {
+        self.current_file_name().starts_with('.') && self.current_file_name() != ".."
+    }
+
+    pub fn extension(&self) -> String {
+        self.path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.info.size
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.info.modified
+    }
+
+    pub fn has_attribute(&self, attribute: Attribute) -> bool {
+        let info = &self.info;
+        match attribute {
+            Attribute::Directory => info.is_dir,
+            Attribute::Executable => info.executable,
+            Attribute::Symlink => info.is_symlink,
+            Attribute::BrokenLink => info.broken_link,
+            Attribute::Hidden => self.is_hidden(),
+            Attribute::Setuid => info.setuid,
+            Attribute::Setgid => info.setgid,
+            Attribute::Pipe => info.pipe,
+            Attribute::Socket => info.socket,
+            Attribute::Device => info.device,
+        }
+    }
+
+    pub fn is_marked(&self) -> bool {
+        self.marked
+    }
+
+    pub fn set_marked(&mut self, marked: bool) {
+        self.marked = marked;
+    }
+
     pub fn current_file_name(&self) -> String {
         self.file_name(&self.path)
     }
 
-    fn path(&self) -> &PathBuf {
-        &self.path
+    /// Name shown in panel, relative to panel directory
+    pub fn name(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => self.current_file_name(),
+        }
     }
 
-    fn marked(&self) -> &bool {
-        &self.marked
+    /// Size and modification time columns of full view
+    pub fn details(&self) -> String {
+        if let Some(description) = &self.description {
+            return description.clone();
+        }
+        let size = if self.current_file_name() == ".." {
+            String::from("<UP>")
+        } else if self.is_dir() {
+            String::from("<DIR>")
+        } else {
+            self.size().to_string()
+        };
+        let modified = match self.modified() {
+            Some(time) if self.current_file_name() != ".." => format_time(time),
+            _ => String::new(),
+        };
+        format!("{:>10} {:<16}", size, modified)
     }
-}
 
-impl From<&PanelItem> for ListItem<'_> {
-    fn from(val: &PanelItem) -> Self {
-        let style = if *val.marked() {
-            Style::new().fg(Color::Yellow)
-        } else if val.path().is_dir() {
-            Style::new().fg(Color::White)
+    /// Line in panel styled by theme and first matching highlighting group.
+    /// With width given, line is filled up to it with details columns.
+    pub fn list_item(
+        &self,
+        theme: &Theme,
+        highlighting: &Highlighting,
+        width: Option<usize>,
+    ) -> ListItem<'static> {
+        let base = if self.is_dir() {
+            theme.directory.style()
         } else {
-            Style::new().fg(Color::Cyan)
+            theme.file.style()
         };
-        let file_name = val.file_name(val.path());
-        let file_line = Line::from(vec![Span::styled(file_name.to_string(), style)]);
-        ListItem::new(vec![file_line])
+        let group = highlighting.find(self);
+        let mut style = match group {
+            Some(group) => base.patch(group.style.style()),
+            None => base,
+        };
+        // colors meant for terminal background may be invisible on panel background
+        if style.fg.is_some() && style.fg == theme.panel.bg {
+            style.fg = base.fg;
+        }
+        if self.marked {
+            style = style.patch(theme.marked.style());
+        }
+        let mut spans = vec![];
+        if highlighting.has_markers() {
+            let marker = group
+                .and_then(|group| group.marker)
+                .unwrap_or(' ');
+            spans.push(Span::styled(String::from(marker), style));
+        }
+        let name = self.name();
+        match width {
+            Some(width) => {
+                let details = self.details();
+                let used: usize = spans.iter().map(|span| span.content.chars().count()).sum();
+                let mut name_width = width.saturating_sub(used + details.len() + 1);
+                // long descriptions are cut at panel border rather than hiding the name
+                if self.description.is_some() {
+                    name_width = name_width.max(width / 3);
+                }
+                spans.push(Span::styled(format!("{:<w$.w$} ", name, w = name_width), style));
+                spans.push(Span::styled(details, style));
+            }
+            None => spans.push(Span::styled(name, style)),
+        }
+        ListItem::new(vec![Line::from(spans)])
+    }
+}
+
+/// Time as `2024-05-01 12:30` in local time zone
+fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0);
+    let (year, month, day, hour, minute) = local_time(secs);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute)
+}
+
+#[cfg(unix)]
+fn local_time(secs: i64) -> (i64, u32, u32, u32, u32) {
+    let time = secs as libc::time_t;
+    // SAFETY: localtime_r only writes to given struct, all zero bytes are a valid `tm`
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return utc_time(secs);
+    }
+    (
+        tm.tm_year as i64 + 1900,
+        tm.tm_mon as u32 + 1,
+        tm.tm_mday as u32,
+        tm.tm_hour as u32,
+        tm.tm_min as u32,
+    )
+}
+
+#[cfg(not(unix))]
+fn local_time(secs: i64) -> (i64, u32, u32, u32, u32) {
+    utc_time(secs)
+}
+
+/// Calendar date from days since epoch, see <http://howardhinnant.github.io/date_algorithms.html>
+fn utc_time(secs: i64) -> (i64, u32, u32, u32, u32) {
+    let days = secs.div_euclid(86400);
+    let rest = secs.rem_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, (rest / 3600) as u32, (rest % 3600 / 60) as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn utc_time_test() {
+        assert_eq!(utc_time(0), (1970, 1, 1, 0, 0));
+        assert_eq!(utc_time(951_827_696), (2000, 2, 29, 12, 34));
+        assert_eq!(utc_time(-60), (1969, 12, 31, 23, 59));
     }
 }
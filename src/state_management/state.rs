@@ -1,19 +1,265 @@
 use std::{
-    env,
-    fs::read_dir,
+    collections::HashSet,
+    env, fs,
     path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc},
+    time::SystemTime,
 };
 
-use anyhow::Ok;
+use anyhow::{Context, Ok};
 
-use super::{action::Action, panel_item::PanelItem, state_store::PopupType, PanelPosition};
+use crate::{
+    config::{config_dir, PanelSettings, Settings, SortOrder, ViewMode},
+    connection::Connection,
+    connections::Connections,
+    highlight::Highlighting,
+    hotlist::Hotlist,
+    keymap::{Command, KeyContext, Keymap},
+    search::SearchOptions,
+    session::Session,
+    theme::Theme,
+    vfs::{self, FileInfo, VfsHandle},
+};
+
+use super::{
+    action::Action,
+    external::{default_editor, default_viewer, ExternalCommand},
+    panel_item::PanelItem,
+    state_store::{MenuItem, PopupType},
+    PanelPosition, TerminalOutput,
+};
+
+const FOLDER_HISTORY_SIZE: usize = 50;
+
+/// Progress of job running in background, e.g. packing files
+#[derive(Debug, Clone, Default)]
+pub struct JobStatus {
+    pub title: String,
+    /// File being processed
+    pub file: String,
+    pub done: u64,
+    pub total: u64,
+}
+
+impl JobStatus {
+    /// Part done, from 0 to 1
+    pub fn ratio(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => (self.done as f64 / total as f64).min(1.0),
+        }
+    }
+}
+
+/// Local copy of remote file being edited, uploaded when it changes
+#[derive(Debug, Clone)]
+pub struct RemoteEdit {
+    pub vfs: VfsHandle,
+    pub path: PathBuf,
+    pub local: PathBuf,
+    /// Modification time of local copy when it was last uploaded
+    modified: Option<SystemTime>,
+}
+
+impl RemoteEdit {
+    pub fn new(vfs: VfsHandle, path: PathBuf) -> anyhow::Result<RemoteEdit> {
+        let local = vfs::local_copy(&vfs, &path)?;
+        let modified = fs::metadata(&local)?.modified().ok();
+        Ok(RemoteEdit {
+            vfs,
+            path,
+            local,
+            modified,
+        })
+    }
+
+    /// Whether local copy was saved since last upload
+    pub fn changed(&self) -> bool {
+        self.local_modified() != self.modified
+    }
+
+    /// Local copy counts as uploaded from now on, upload itself can run on another thread
+    pub fn set_uploaded(&mut self) {
+        self.modified = self.local_modified();
+    }
+
+    pub fn upload(&self) -> anyhow::Result<()> {
+        vfs::copy(&VfsHandle::default(), &self.local, &self.vfs, &self.path, &mut |_, _| {})
+            .with_context(|| format!("Failed to upload {}{}", self.vfs.prefix(), self.path.display()))
+    }
+
+    fn local_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.local).ok()?.modified().ok()
+    }
+}
+
+/// Saved connection being edited in connection dialog
+#[derive(Debug, Clone, Default)]
+pub struct ProfileEdit {
+    /// Profile replaced on save, new one is added if None
+    pub index: Option<usize>,
+    pub name: String,
+}
+
+/// Find file dialog with results of the last search
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    pub options: SearchOptions,
+    /// Filesystem and directory searched, results are paths in it
+    pub vfs: VfsHandle,
+    pub root: PathBuf,
+    pub results: Vec<PathBuf>,
+    pub running: bool,
+    /// Progress or problem shown below results
+    pub message: String,
+    /// Number of the search, reports of earlier ones are ignored
+    pub id: usize,
+    /// Set to stop the search
+    pub cancel: Arc<AtomicBool>,
+}
+
+/// Directory on some filesystem, kept in panel history
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub vfs: VfsHandle,
+    pub path: PathBuf,
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct PanelData {
     pub active: bool,
+    /// Filesystem shown in panel and directory within it
+    pub vfs: VfsHandle,
     pub path: PathBuf,
     pub index: usize,
     pub items: Vec<PanelItem>,
+    /// Sorting and view of this panel, start from settings
+    pub sort: SortOrder,
+    pub sort_reverse: bool,
+    pub view_mode: ViewMode,
+    /// Directories visited before current one and ones left with going back, latest last
+    pub back: Vec<Location>,
+    pub forward: Vec<Location>,
+    /// Saved connections are listed instead of directory, `..` leaves them
+    pub network: bool,
+    /// Files listed instead of directory content, e.g. search results. They are
+    /// named relative to `path`, `..` leaves them.
+    pub listed: Option<Vec<PathBuf>>,
+    /// Remote filesystem is being read, panel shows what it did before until it's done
+    pub loading: bool,
+}
+
+impl PanelData {
+    fn new(path: PathBuf, settings: &PanelSettings) -> Self {
+        let mut panel = PanelData {
+            path,
+            sort: settings.sort,
+            sort_reverse: settings.sort_reverse,
+            view_mode: settings.view_mode,
+            items: sorted_items(vec![], settings),
+            ..Default::default()
+        };
+        // unreadable directory shows only `..`
+        let _ = panel.read_items(settings);
+        panel
+    }
+
+    /// Settings with panel's own sort order
+    fn sorting(&self, settings: &PanelSettings) -> PanelSettings {
+        PanelSettings {
+            sort: self.sort,
+            sort_reverse: self.sort_reverse,
+            ..settings.clone()
+        }
+    }
+
+    /// Lists directory again with panel's own sort order, cursor is kept inside the list.
+    /// Files which are still listed stay marked. Unreadable directory keeps its old listing.
+    fn read_items(&mut self, settings: &PanelSettings) -> anyhow::Result<()> {
+        // saved connections are listed by `State::refresh_network`
+        if self.network {
+            return Ok(());
+        }
+        let settings = self.sorting(settings);
+        let marked: HashSet<String> = self
+            .items
+            .iter()
+            .filter(|item| item.is_marked())
+            .map(PanelItem::file_full_path)
+            .collect();
+        self.items = match &self.listed {
+            Some(files) => read_listed(&self.vfs, &self.path, files, &settings),
+            None => read_items(&self.vfs, &self.path, &settings)?,
+        };
+        for item in self.items.iter_mut() {
+            if marked.contains(&item.file_full_path()) {
+                item.set_marked(true);
+            }
+        }
+        self.index = self.index.min(self.items.len() - 1);
+        Ok(())
+    }
+
+    /// Shows directory of the same or another filesystem, panel stays where it was
+    /// when the directory can't be listed. When moving up cursor is put on directory
+    /// we came from.
+    fn change_dir(
+        &mut self,
+        vfs: VfsHandle,
+        path: PathBuf,
+        settings: &PanelSettings,
+    ) -> anyhow::Result<()> {
+        self.items = read_items(&vfs, &path, &self.sorting(settings))?;
+        // path of old filesystem says nothing about where cursor goes
+        let previous = match vfs.same(&self.vfs) {
+            true => std::mem::replace(&mut self.path, path),
+            false => {
+                self.path = path;
+                self.path.clone()
+            }
+        };
+        self.vfs = vfs;
+        self.index = 0;
+        self.network = false;
+        self.listed = None;
+        if let Some(child) = previous
+            .strip_prefix(&self.path)
+            .ok()
+            .and_then(|rest| rest.components().next())
+        {
+            self.select(&child.as_os_str().to_string_lossy());
+        }
+        Ok(())
+    }
+
+    /// Directory on local disk programs run from this panel start in. Inside archive
+    /// it is the directory of the archive, on remote filesystems the current directory.
+    pub fn local_dir(&self) -> PathBuf {
+        let mut vfs = self.vfs.clone();
+        let mut path = self.path.clone();
+        while !vfs.is_local() {
+            let Some((parent, file)) = vfs.parent() else {
+                return env::current_dir().unwrap_or_default();
+            };
+            path = file.parent().map(Path::to_path_buf).unwrap_or_default();
+            vfs = parent;
+        }
+        path
+    }
+
+    pub fn current_file_name(&self) -> String {
+        self.items
+            .get(self.index)
+            .map(PanelItem::name)
+            .unwrap_or_default()
+    }
+
+    /// Moves cursor to file with given name, if it is listed
+    fn select(&mut self, name: &str) {
+        if let Some(index) = self.items.iter().position(|item| item.name() == name) {
+            self.index = index;
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -24,43 +270,321 @@ pub struct State {
     /// If None, editors page is not rendered. If there is value, it points to file name to be edited.
     pub editor_file: Option<String>,
     pub editor_modified: bool,
+    /// Unsaved text of editor, which it starts with instead of file's content
+    pub editor_text: Option<Arc<String>>,
+    /// Program UI should run in foreground. Set only for one state update.
+    pub external_command: Option<ExternalCommand>,
+    /// Commands entered in command line, oldest first
+    pub command_history: Vec<String>,
+    /// Output of commands run from command line, shown instead of panels with Ctrl+O
+    pub terminal_output: TerminalOutput,
+    pub show_output: bool,
+    /// Subshell is running command, keys on output page are sent to it
+    pub subshell_busy: bool,
+    /// Background job, only one runs at a time
+    pub job: Option<JobStatus>,
     /// App state
+    pub settings: Settings,
+    /// File settings are saved to
+    pub config_path: Option<PathBuf>,
+    /// Directory bookmarks
+    pub hotlist: Hotlist,
+    /// Directories visited in both panels, oldest first, kept across sessions
+    pub folder_history: Vec<PathBuf>,
+    /// Key bindings built from settings
+    pub keymap: Arc<Keymap>,
+    /// Styles built from settings
+    pub theme: Arc<Theme>,
+    pub highlighting: Arc<Highlighting>,
+    /// Settings dialog is shown instead of panels
+    pub settings_open: bool,
+    pub settings_message: String,
+    /// Connection dialog is shown instead of panels
+    pub connect_open: bool,
+    pub connect_message: String,
+    /// Connection is being made in background
+    pub connecting: bool,
+    /// Server of connection dialog, last one connected to by default
+    pub connection: Connection,
+    /// Saved connections listed in network panel
+    pub connections: Connections,
+    /// Connection dialog edits saved connection instead of connecting
+    pub profile_edit: Option<ProfileEdit>,
+    /// Find file dialog is shown instead of panels
+    pub find_open: bool,
+    pub search: Search,
+    /// Remote file opened in editor
+    pub remote_edit: Option<RemoteEdit>,
     /// Popup
     pub popup_msg: String,
     pub popup_type: PopupType,
     pub popup_next_action: Option<Action>,
     pub popup_input: String,
+    /// Items of menu popup
+    pub popup_menu: Vec<MenuItem>,
 }
 
 impl State {
-    pub fn new() -> anyhow::Result<Self> {
+    /// Both panels start in current directory unless previous session is given
+    pub fn new(settings: Settings, session: Option<Session>) -> anyhow::Result<Self> {
         let path = env::current_dir()?;
-        let items = read_items(&path);
-        let state = State {
+        let keymap = Arc::new(Keymap::new(&settings.keys).unwrap_or_default());
+        let theme = Arc::new(settings.theme().unwrap_or_default());
+        let highlighting = Arc::new(Highlighting::new(
+            &settings.highlight,
+            settings.ui.color_depth,
+        ));
+        let mut state = State {
             l_panel: PanelData {
                 active: true,
-                path: path.clone(),
-                index: 0,
-                items: items.clone(),
-            },
-            r_panel: PanelData {
-                active: false,
-                path,
-                index: 0,
-                items,
+                ..PanelData::new(path.clone(), &settings.panels)
             },
+            r_panel: PanelData::new(path, &settings.panels),
+            settings,
+            keymap,
+            theme,
+            highlighting,
             ..Default::default()
         };
+        if let Some(session) = session {
+            state.restore(session);
+        }
         Ok(state)
     }
 
-    fn active_panel(&self) -> &PanelData {
+    /// Applies panels, editor and history of previous session. Directories and files
+    /// which no longer exist are skipped.
+    fn restore(&mut self, session: Session) {
+        for (panel, saved) in [
+            (&mut self.l_panel, session.left),
+            (&mut self.r_panel, session.right),
+        ] {
+            panel.sort = saved.sort;
+            panel.sort_reverse = saved.sort_reverse;
+            panel.view_mode = saved.view_mode;
+            let vfs = panel.vfs.clone();
+            let moved = saved.path.is_dir()
+                && panel.change_dir(vfs, saved.path, &self.settings.panels).is_ok();
+            if !moved {
+                let _ = panel.read_items(&self.settings.panels);
+            }
+            panel.select(&saved.current_file);
+        }
+        self.l_panel.active = !session.right_active;
+        self.r_panel.active = session.right_active;
+        self.restore_editor(session.editor_file, session.editor_text);
+        self.command_history = session.command_history;
+        self.folder_history = session.folder_history;
+    }
+
+    /// Reopens file of editor with its unsaved text. Remote file can't be reopened
+    /// before connecting, so its unsaved text is put in local file.
+    fn restore_editor(&mut self, file: Option<String>, text: Option<String>) {
+        let Some(file) = file else {
+            return;
+        };
+        let file = match (file.contains("://"), &text) {
+            (false, Some(_)) => file,
+            (false, None) if Path::new(&file).is_file() => file,
+            (true, Some(_)) => match recovery_path(&file) {
+                core::result::Result::Ok(path) => {
+                    self.popup_msg = format!(
+                        "{} can't be reopened, its unsaved text is in {}",
+                        file,
+                        path.display()
+                    );
+                    self.popup_type = PopupType::Error;
+                    path.display().to_string()
+                }
+                Err(err) => {
+                    self.popup_msg = format!("Unsaved text of {} is lost: {:#}", file, err);
+                    self.popup_type = PopupType::Error;
+                    return;
+                }
+            },
+            _ => return,
+        };
+        self.editor_file = Some(file);
+        self.editor_modified = text.is_some();
+        self.editor_text = text.map(Arc::new);
+    }
+
+    /// External editor command, may contain arguments
+    pub fn external_editor(&self) -> String {
+        match self.settings.editor.external_editor.trim() {
+            "" => default_editor(),
+            editor => String::from(editor),
+        }
+    }
+
+    pub fn external_viewer(&self) -> String {
+        match self.settings.editor.external_viewer.trim() {
+            "" => default_viewer(),
+            viewer => String::from(viewer),
+        }
+    }
+
+    pub fn opposite_panel(&self) -> &PanelData {
+        if self.l_panel.active {
+            return &self.r_panel;
+        }
+        &self.l_panel
+    }
+
+    pub fn active_panel(&self) -> &PanelData {
         if self.l_panel.active {
             return &self.l_panel;
         }
         &self.r_panel
     }
 
+    pub fn panel(&self, position: &PanelPosition) -> &PanelData {
+        match position {
+            PanelPosition::L => &self.l_panel,
+            PanelPosition::R => &self.r_panel,
+        }
+    }
+
+    pub fn active_position(&self) -> PanelPosition {
+        match self.l_panel.active {
+            true => PanelPosition::L,
+            false => PanelPosition::R,
+        }
+    }
+
+    /// Takes panel from copy of state it was changed in on another thread, with
+    /// folder history and error it got
+    pub fn take_panel(&mut self, position: &PanelPosition, copy: State) {
+        let (panel, changed) = match position {
+            PanelPosition::L => (&mut self.l_panel, copy.l_panel),
+            PanelPosition::R => (&mut self.r_panel, copy.r_panel),
+        };
+        *panel = PanelData {
+            active: panel.active,
+            loading: false,
+            ..changed
+        };
+        self.folder_history = copy.folder_history;
+        if !copy.popup_msg.is_empty() {
+            self.popup_msg = copy.popup_msg;
+            self.popup_type = copy.popup_type;
+        }
+    }
+
+    fn active_panel_mut(&mut self) -> &mut PanelData {
+        if self.l_panel.active {
+            return &mut self.l_panel;
+        }
+        &mut self.r_panel
+    }
+
+    /// Marked files of active panel, or given file if nothing is marked
+    pub fn selected_files(&self, file: &str) -> Vec<PathBuf> {
+        let marked: Vec<PathBuf> = self
+            .active_panel()
+            .items
+            .iter()
+            .filter(|item| item.is_marked())
+            .map(|item| PathBuf::from(item.file_full_path()))
+            .collect();
+        if marked.is_empty() {
+            vec![PathBuf::from(file)]
+        } else {
+            marked
+        }
+    }
+
+    /// Marks or unmarks file under cursor of active panel and moves to the next one
+    pub fn toggle_mark(&mut self) {
+        let panel = self.active_panel_mut();
+        if let Some(item) = panel.items.get_mut(panel.index) {
+            if item.current_file_name() != ".." {
+                item.set_marked(!item.is_marked());
+            }
+        }
+        self.add_index(1);
+    }
+
+    /// Marks all files of active panel, or unmarks them when all are marked
+    pub fn mark_all(&mut self) {
+        let files: Vec<&mut PanelItem> = self
+            .active_panel_mut()
+            .items
+            .iter_mut()
+            .filter(|item| item.current_file_name() != "..")
+            .collect();
+        let marked = !files.iter().all(|item| item.is_marked());
+        for item in files {
+            item.set_marked(marked);
+        }
+    }
+
+    pub fn clear_marks(&mut self) {
+        for item in self.active_panel_mut().items.iter_mut() {
+            item.set_marked(false);
+        }
+    }
+
+    /// Opens or refreshes hotlist popup
+    pub fn show_hotlist(&mut self) {
+        let key = |command| {
+            self.keymap
+                .keys_for(KeyContext::Popup, command)
+                .and_then(|keys| keys.first())
+                .map(|key| key.to_string())
+                .unwrap_or_default()
+        };
+        self.popup_msg = format!(
+            "Hotlist  {} add  {} rename  {} delete",
+            key(Command::AddItem),
+            key(Command::Edit),
+            key(Command::Delete)
+        );
+        self.popup_type = PopupType::Hotlist;
+        self.popup_next_action = None;
+        self.popup_menu = self
+            .hotlist
+            .bookmarks
+            .iter()
+            .enumerate()
+            .map(|(index, bookmark)| MenuItem::new(bookmark.label(), Action::JumpBookmark(index)))
+            .collect();
+    }
+
+    /// Lists saved connections in active panel
+    pub fn show_network(&mut self) {
+        let panel = self.active_panel_mut();
+        panel.network = true;
+        panel.index = 0;
+        self.refresh_network();
+    }
+
+    /// Lists saved connections again in panels showing them, e.g. after one was edited
+    pub fn refresh_network(&mut self) {
+        let up = FileInfo {
+            is_dir: true,
+            ..Default::default()
+        };
+        let items: Vec<PanelItem> = [PanelItem::with_info(PathBuf::from(".."), up)]
+            .into_iter()
+            .chain(self.connections.profiles.iter().map(|profile| {
+                PanelItem::with_details(PathBuf::from(&profile.name), profile.details())
+            }))
+            .collect();
+        for panel in [&mut self.l_panel, &mut self.r_panel] {
+            if panel.network {
+                panel.items = items.clone();
+                panel.index = panel.index.min(items.len() - 1);
+            }
+        }
+    }
+
+    pub fn close_popup(&mut self) {
+        self.popup_msg = String::from("");
+        self.popup_next_action = None;
+        self.popup_menu = vec![];
+    }
+
     fn set_active_panel_index(&mut self, index: usize) {
         if self.l_panel.active {
             self.l_panel.index = index;
@@ -70,27 +594,260 @@ impl State {
     }
 
     pub fn reload(&mut self, position: PanelPosition) {
-        match position {
-            PanelPosition::L => {
-                self.l_panel.items = read_items(&self.l_panel.path);
-            },
-            PanelPosition::R => {
-                self.r_panel.items = read_items(&self.r_panel.path);
-            },
+        let result = match position {
+            PanelPosition::L => self.l_panel.read_items(&self.settings.panels),
+            PanelPosition::R => self.r_panel.read_items(&self.settings.panels),
+        };
+        if let Err(err) = result {
+            self.show_error(err);
         }
     }
 
+    fn show_error(&mut self, err: anyhow::Error) {
+        self.popup_msg = format!("{:#}", err);
+        self.popup_type = PopupType::Error;
+    }
+
     pub fn cd(&mut self, dir: String) {
+        let panel = if self.l_panel.active {
+            &mut self.l_panel
+        } else {
+            &mut self.r_panel
+        };
+        if dir == ".." && (panel.network || panel.listed.is_some()) {
+            let (vfs, path) = (panel.vfs.clone(), panel.path.clone());
+            if let Err(err) = panel.change_dir(vfs, path, &self.settings.panels) {
+                self.show_error(err);
+            }
+            return;
+        }
+        // going up from archive root returns to the directory of the archive
+        if dir == ".." && panel.path.parent().is_none() {
+            if let Some((parent, file)) = panel.vfs.parent() {
+                let dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+                if self.visit(parent, dir) {
+                    let name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+                    self.active_panel_mut().select(&name);
+                }
+                return;
+            }
+        }
+        let path = match panel.vfs.normalize(&panel.path.join(&dir)) {
+            core::result::Result::Ok(path) => path,
+            Err(err) => {
+                self.popup_msg = format!("cd: {}: {}", dir, err);
+                self.popup_type = PopupType::Error;
+                return;
+            }
+        };
+        let vfs = panel.vfs.clone();
+        self.visit(vfs, path);
+    }
+
+    /// Shows directory of local disk in active panel, leaving archive it may show
+    pub fn cd_local(&mut self, path: PathBuf) {
+        match self.active_panel().vfs.is_local() {
+            true => self.cd(path.display().to_string()),
+            false => {
+                self.visit(VfsHandle::default(), path);
+            }
+        }
+    }
+
+    /// Shows content of archive in active panel, `..` at its root comes back
+    pub fn enter_archive(&mut self, file: &Path) {
+        let panel = self.active_panel();
+        match vfs::open_archive(&panel.vfs, file) {
+            core::result::Result::Ok(archive) => {
+                self.visit(archive, PathBuf::from("/"));
+            }
+            Err(err) => {
+                self.popup_msg = format!("Can't open {}: {:#}", file.display(), err);
+                self.popup_type = PopupType::Error;
+            }
+        }
+    }
+
+    /// Indexes archive shown in panel again, e.g. after files were added to it
+    pub fn reopen_archive(&mut self, position: PanelPosition) {
+        let panel = match position {
+            PanelPosition::L => &mut self.l_panel,
+            PanelPosition::R => &mut self.r_panel,
+        };
+        let Some((parent, file)) = panel.vfs.parent() else {
+            return;
+        };
+        match vfs::open_archive(&parent, &file) {
+            core::result::Result::Ok(archive) => {
+                panel.vfs = archive;
+                if let Err(err) = panel.read_items(&self.settings.panels) {
+                    self.show_error(err);
+                }
+            }
+            Err(err) => {
+                self.popup_msg = format!("Can't open {}: {:#}", file.display(), err);
+                self.popup_type = PopupType::Error;
+            }
+        }
+    }
+
+    /// Shows directory of remote filesystem in active panel
+    pub fn open_remote(&mut self, vfs: VfsHandle, path: PathBuf) {
+        self.visit(vfs, path);
+    }
+
+    /// Shows directory of file in active panel with cursor on the file
+    pub fn show_file(&mut self, vfs: VfsHandle, path: &Path) {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return;
+        };
+        if self.visit(vfs, dir.to_path_buf()) {
+            self.active_panel_mut().select(&name.to_string_lossy());
+        }
+    }
+
+    /// Lists given files of filesystem in active panel, named relative to `root`.
+    /// Files which no longer exist are left out.
+    pub fn show_list(&mut self, vfs: VfsHandle, root: PathBuf, files: Vec<PathBuf>) {
+        if !self.visit(vfs, root) {
+            return;
+        }
+        let panel = if self.l_panel.active {
+            &mut self.l_panel
+        } else {
+            &mut self.r_panel
+        };
+        panel.listed = Some(files);
+        panel.index = 0;
+        if let Err(err) = panel.read_items(&self.settings.panels) {
+            self.show_error(err);
+        }
+    }
+
+    /// Moves active panel to directory, remembering current one in its history.
+    /// Returns false when the directory can't be listed, which popup tells.
+    fn visit(&mut self, vfs: VfsHandle, path: PathBuf) -> bool {
+        let panel = if self.l_panel.active {
+            &mut self.l_panel
+        } else {
+            &mut self.r_panel
+        };
+        let current = Location {
+            vfs: panel.vfs.clone(),
+            path: panel.path.clone(),
+        };
+        if let Err(err) = panel.change_dir(vfs, path.clone(), &self.settings.panels) {
+            self.show_error(err);
+            return false;
+        }
+        if path != current.path || !panel.vfs.same(&current.vfs) {
+            panel.back.push(current);
+            panel.forward.clear();
+        }
+        if panel.vfs.is_local() {
+            self.remember_folder(path);
+        }
+        true
+    }
+
+    /// Goes to previous directory of active panel, or to next one after going back.
+    /// Directories removed meanwhile are skipped.
+    pub fn go_history(&mut self, back: bool) {
+        let panel = if self.l_panel.active {
+            &mut self.l_panel
+        } else {
+            &mut self.r_panel
+        };
+        let location = loop {
+            let next = match back {
+                true => panel.back.pop(),
+                false => panel.forward.pop(),
+            };
+            match next {
+                Some(location) if location.vfs.stat(&location.path).is_ok_and(|info| info.is_dir) => {
+                    break location
+                }
+                Some(_) => continue,
+                None => return,
+            }
+        };
+        let current = Location {
+            vfs: panel.vfs.clone(),
+            path: panel.path.clone(),
+        };
+        let result = panel.change_dir(location.vfs, location.path.clone(), &self.settings.panels);
+        if let Err(err) = result {
+            self.show_error(err);
+            return;
+        }
+        match back {
+            true => panel.forward.push(current),
+            false => panel.back.push(current),
+        }
+        if panel.vfs.is_local() {
+            self.remember_folder(location.path);
+        }
+    }
+
+    fn remember_folder(&mut self, path: PathBuf) {
+        self.folder_history.retain(|visited| *visited != path);
+        self.folder_history.push(path);
+        if self.folder_history.len() > FOLDER_HISTORY_SIZE {
+            self.folder_history.remove(0);
+        }
+    }
+
+    /// Opens popup with recently visited directories, latest first
+    pub fn show_folder_history(&mut self) {
+        self.popup_msg = String::from("Folder history");
+        self.popup_type = PopupType::Menu;
+        self.popup_next_action = None;
+        self.popup_menu = self
+            .folder_history
+            .iter()
+            .rev()
+            .map(|path| MenuItem::new(path.display().to_string(), Action::GoTo(path.clone())))
+            .collect();
+    }
+
+    /// Shows given directory in panel, e.g. one passed on command line
+    pub fn set_panel_path(&mut self, position: PanelPosition, path: PathBuf) {
+        let panel = match position {
+            PanelPosition::L => &mut self.l_panel,
+            PanelPosition::R => &mut self.r_panel,
+        };
+        let vfs = panel.vfs.clone();
+        if let Err(err) = panel.change_dir(vfs, path, &self.settings.panels) {
+            self.show_error(err);
+        }
+    }
+
+    /// Sorts active panel, choosing current order again reverses it
+    pub fn sort_by(&mut self, order: SortOrder) {
+        let panel = if self.l_panel.active {
+            &mut self.l_panel
+        } else {
+            &mut self.r_panel
+        };
+        if panel.sort == order {
+            panel.sort_reverse = !panel.sort_reverse;
+        } else {
+            panel.sort = order;
+            panel.sort_reverse = false;
+        }
+        let current = panel.current_file_name();
+        let result = panel.read_items(&self.settings.panels);
+        panel.select(&current);
+        if let Err(err) = result {
+            self.show_error(err);
+        }
+    }
+
+    pub fn toggle_view_mode(&mut self) {
         if self.l_panel.active {
-            self.l_panel.path.push(dir);
-            self.l_panel.path = self.l_panel.path.canonicalize().expect("Error while cannonize path");
-            self.l_panel.index = 0;
-            self.l_panel.items = read_items(&self.l_panel.path);
+            self.l_panel.view_mode = self.l_panel.view_mode.next();
         } else {
-            self.r_panel.path.push(dir);
-            self.r_panel.path = self.r_panel.path.canonicalize().expect("Error while cannonize path");
-            self.r_panel.index = 0;
-            self.r_panel.items = read_items(&self.r_panel.path);
+            self.r_panel.view_mode = self.r_panel.view_mode.next();
         }
     }
 
@@ -111,15 +868,211 @@ impl State {
     }
 }
 
-fn read_items(path: &Path) -> Vec<PanelItem> {
-    let paths = read_dir(path).unwrap();
-    let mut items: Vec<PanelItem> = paths
-        .filter_map(|path| Some(PanelItem::new(path.ok()?.path())))
+/// Sorted directory listing starting with `..`
+fn read_items(
+    vfs: &VfsHandle,
+    path: &Path,
+    settings: &PanelSettings,
+) -> anyhow::Result<Vec<PanelItem>> {
+    let entries = vfs
+        .list(path)
+        .with_context(|| format!("Can't read {}{}", vfs.prefix(), path.display()))?;
+    let items: Vec<PanelItem> = entries
+        .into_iter()
+        .map(|entry| PanelItem::with_info(path.join(entry.name), entry.info))
+        .filter(|item| settings.show_hidden || !item.is_hidden())
+        .collect();
+    Ok(sorted_items(items, settings))
+}
+
+/// Files which still exist, sorted and starting with `..`. Hidden files are kept,
+/// they were listed on purpose.
+fn read_listed(
+    vfs: &VfsHandle,
+    root: &Path,
+    files: &[PathBuf],
+    settings: &PanelSettings,
+) -> Vec<PanelItem> {
+    let items: Vec<PanelItem> = files
+        .iter()
+        .filter_map(|file| {
+            let info = vfs.stat(file).ok()?;
+            let label = file.strip_prefix(root).unwrap_or(file).display().to_string();
+            Some(PanelItem::with_label(file.clone(), info, label))
+        })
         .collect();
-    let mut back_path = PathBuf::new();
-    back_path.push("..");
-    items.push(PanelItem::new(back_path));
-    items.sort_by(|pb_a, pb_b| pb_a.display_string().cmp(&pb_b.display_string()));
-    items.sort_by_key(|pb| !pb.is_dir());
+    sorted_items(items, settings)
+}
+
+fn sorted_items(mut items: Vec<PanelItem>, settings: &PanelSettings) -> Vec<PanelItem> {
+    match settings.sort {
+        SortOrder::Name => items.sort_by_key(|pb| pb.display_string()),
+        SortOrder::Extension => items.sort_by_key(|pb| (pb.extension(), pb.display_string())),
+        SortOrder::Size => items.sort_by_key(|pb| (pb.size(), pb.display_string())),
+        SortOrder::Modified => items.sort_by_key(|pb| (pb.modified(), pb.display_string())),
+    }
+    if settings.sort_reverse {
+        items.reverse();
+    }
+    if settings.directories_first {
+        items.sort_by_key(|pb| !pb.is_dir());
+    }
+    let up = FileInfo {
+        is_dir: true,
+        ..Default::default()
+    };
+    items.insert(0, PanelItem::with_info(PathBuf::from(".."), up));
     items
 }
+
+/// Local file for unsaved text of remote `file`, in config directory so it isn't
+/// removed with temporary files. Existing files are never reused.
+fn recovery_path(file: &str) -> anyhow::Result<PathBuf> {
+    let dir = config_dir()
+        .context("No config directory to keep it in")?
+        .join("recovered");
+    fs::create_dir_all(&dir)?;
+    let name = file.rsplit('/').next().unwrap_or_default();
+    let mut path = dir.join(name);
+    let mut number = 1;
+    while path.exists() {
+        path = dir.join(format!("{}.{}", name, number));
+        number += 1;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::{connections::Profile, test_dir::TestDir};
+
+    use super::*;
+
+    #[test]
+    fn history_test() {
+        let dir = TestDir::new("history");
+        let root = dir.path().to_path_buf();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b/c")).unwrap();
+        let mut state = State::new(Settings::defaults(), None).unwrap();
+        state.set_panel_path(PanelPosition::L, root.clone());
+
+        state.cd(String::from("b"));
+        state.cd(String::from("c"));
+        state.cd(String::from("../.."));
+        assert_eq!(state.l_panel.path, root);
+        assert_eq!(state.l_panel.current_file_name(), "b");
+
+        state.go_history(true);
+        assert_eq!(state.l_panel.path, root.join("b/c"));
+        state.go_history(true);
+        assert_eq!(state.l_panel.path, root.join("b"));
+        state.go_history(false);
+        assert_eq!(state.l_panel.path, root.join("b/c"));
+        assert_eq!(state.folder_history.last(), Some(&root.join("b/c")));
+        assert_eq!(state.folder_history.len(), 3);
+
+        // directory that can't be listed leaves panel and its history as they were
+        state.show_file(VfsHandle::default(), &root.join("gone/x.txt"));
+        assert_eq!(state.l_panel.path, root.join("b/c"));
+        assert_eq!(state.l_panel.back.len(), 2);
+        assert!(matches!(state.popup_type, PopupType::Error));
+    }
+
+    #[test]
+    fn archive_test() {
+        let dir = TestDir::new("archive");
+        let root = dir.path().to_path_buf();
+        let file = root.join("docs.zip");
+        let mut zip = ::zip::ZipWriter::new(fs::File::create(&file).unwrap());
+        zip.start_file("guide/intro.txt", Default::default()).unwrap();
+        zip.finish().unwrap();
+        let mut state = State::new(Settings::defaults(), None).unwrap();
+        state.set_panel_path(PanelPosition::L, root.clone());
+
+        state.enter_archive(&file);
+        assert_eq!(state.l_panel.path, PathBuf::from("/"));
+        assert_eq!(state.l_panel.local_dir(), root);
+        state.cd(String::from("guide"));
+        assert_eq!(state.l_panel.current_file_name(), "..");
+        assert_eq!(state.l_panel.items[1].current_file_name(), "intro.txt");
+        state.cd(String::from(".."));
+        state.cd(String::from(".."));
+        assert!(state.l_panel.vfs.is_local());
+        assert_eq!(state.l_panel.path, root);
+        assert_eq!(state.l_panel.current_file_name(), "docs.zip");
+        assert_eq!(state.folder_history, vec![root.clone()]);
+
+        state.go_history(true);
+        assert_eq!(state.l_panel.path, PathBuf::from("/"));
+        assert!(!state.l_panel.vfs.is_local());
+    }
+
+    #[test]
+    fn network_test() {
+        let mut state = State::new(Settings::defaults(), None).unwrap();
+        let path = state.l_panel.path.clone();
+        let profile = Profile {
+            name: String::from("Backup"),
+            ..Default::default()
+        };
+        state.connections.set(None, profile).unwrap();
+
+        state.show_network();
+        assert!(state.l_panel.network);
+        assert_eq!(state.l_panel.items.len(), 2);
+        assert_eq!(state.l_panel.items[1].current_file_name(), "Backup");
+        state.sort_by(SortOrder::Size);
+        assert_eq!(state.l_panel.items.len(), 2);
+        state.cd(String::from(".."));
+        assert!(!state.l_panel.network);
+        assert_eq!(state.l_panel.path, path);
+    }
+
+    #[test]
+    fn restore_editor_test() {
+        let mut state = State::new(Settings::defaults(), None).unwrap();
+        state.restore_editor(Some(String::from("/gone/notes.txt")), None);
+        assert_eq!(state.editor_file, None);
+        state.restore_editor(Some(String::from("sftp://me@example.com/notes.txt")), None);
+        assert_eq!(state.editor_file, None);
+
+        // unsaved text is kept even if the file is gone, saving creates it again
+        state.restore_editor(Some(String::from("/gone/notes.txt")), Some(String::from("draft")));
+        assert_eq!(state.editor_file.as_deref(), Some("/gone/notes.txt"));
+        assert!(state.editor_modified);
+        assert_eq!(state.editor_text.as_deref().map(String::as_str), Some("draft"));
+    }
+
+    #[test]
+    fn listed_test() {
+        let dir = TestDir::new("listed");
+        let root = dir.path().to_path_buf();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/b/x.txt"), "x").unwrap();
+        fs::write(root.join("y.txt"), "y").unwrap();
+        let mut state = State::new(Settings::defaults(), None).unwrap();
+        let files = vec![root.join("y.txt"), root.join("a/b/x.txt"), root.join("gone.txt")];
+
+        state.show_list(VfsHandle::default(), root.clone(), files);
+        let names: Vec<String> = state.l_panel.items.iter().map(PanelItem::name).collect();
+        assert_eq!(names, ["..", "a/b/x.txt", "y.txt"]);
+        state.mark_all();
+        assert_eq!(state.selected_files("").len(), 2);
+        state.mark_all();
+        state.add_index(1);
+        state.toggle_mark();
+        assert_eq!(state.selected_files(""), vec![root.join("a/b/x.txt")]);
+        fs::remove_file(root.join("y.txt")).unwrap();
+        state.reload(PanelPosition::L);
+        assert_eq!(state.l_panel.items.len(), 2);
+        assert!(state.l_panel.items[1].is_marked());
+
+        state.cd(String::from(".."));
+        assert!(state.l_panel.listed.is_none());
+        assert_eq!(state.l_panel.path, root);
+        assert_eq!(state.l_panel.current_file_name(), "..");
+    }
+}
@@ -1,4 +1,15 @@
-use std::{path::MAIN_SEPARATOR, process::{Command, Output}};
+use std::{
+    collections::{HashMap, HashSet},
+    env, io,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 use anyhow::Ok;
 use tokio::sync::{
@@ -6,9 +17,30 @@ use tokio::sync::{
     mpsc::{self, UnboundedReceiver, UnboundedSender},
 };
 
-use crate::termination::{Interrupted, Terminator};
+use crate::{
+    config::Settings,
+    connection::{Connection, Protocol},
+    connections::{Connections, Profile, NAME_FIELD},
+    highlight::Highlighting,
+    hotlist::Hotlist,
+    keymap::Keymap,
+    search::Progress,
+    session::Session,
+    termination::{Interrupted, Terminator},
+    vfs::{self, PackOptions, UnknownHost, VfsHandle},
+};
+
+use super::{
+    action::Action, associations, subshell::Subshell, ExternalCommand, JobStatus, ProfileEdit,
+    RemoteEdit, State,
+};
+
+const COMMAND_HISTORY_SIZE: usize = 100;
+/// Background jobs report progress at most this often
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
 
-use super::{action::Action, State};
+/// `vfs::copy` or `vfs::move_to`
+type TransferFn = fn(&VfsHandle, &Path, &VfsHandle, &Path, &mut dyn FnMut(&Path, u64)) -> anyhow::Result<()>;
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum PanelPosition {
@@ -20,19 +52,108 @@ pub enum PanelPosition {
 pub enum PopupType {
     Input,
     YesNo,
+    Menu,
+    /// Menu of directory bookmarks, which can be edited in place
+    Hotlist,
     #[default]
     Error,
 }
 
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    pub label: String,
+    pub action: Action,
+}
+
+impl MenuItem {
+    pub fn new(label: String, action: Action) -> Self {
+        MenuItem { label, action }
+    }
+}
+
+/// Messages of background job thread
+enum JobEvent {
+    Progress(JobStatus),
+    /// Job ended, with archive it wrote and error if it failed
+    Finished {
+        target: VfsHandle,
+        archive: PathBuf,
+        result: Result<(), String>,
+    },
+    /// Copying, moving or removing files ended, with error if it failed
+    FilesDone(Result<(), String>),
+    /// Connection made from dialog, with directory to show
+    Connected(anyhow::Result<(VfsHandle, PathBuf)>),
+    /// Files found by search with given number since last report, and directory being searched
+    Found {
+        id: usize,
+        files: Vec<PathBuf>,
+        dir: PathBuf,
+    },
+    /// Search ended, with files found since last report
+    SearchFinished { id: usize, files: Vec<PathBuf> },
+    /// Panel at given position was changed on copy of state
+    Navigated {
+        position: PanelPosition,
+        copy: Box<State>,
+    },
+    /// Remote file was copied to local disk, along with what uploads it back when
+    /// it's edited
+    Downloaded {
+        result: Result<(PathBuf, Option<RemoteEdit>), String>,
+        opener: Opener,
+    },
+}
+
+/// Program file is opened with once it's on local disk
+enum Opener {
+    Viewer,
+    Editor,
+    ExternalEditor,
+    /// Associated command
+    Command(String),
+    /// Program registered in the system
+    Default,
+}
+
+/// Where app state comes from and goes to, set up from command line
+#[derive(Debug, Clone, Default)]
+pub struct StartOptions {
+    /// Shown in popup once UI starts, e.g. invalid config file
+    pub error: Option<String>,
+    /// File settings are saved to
+    pub config_path: Option<PathBuf>,
+    /// Restored on start
+    pub session: Option<Session>,
+    /// Where session is saved on exit, not saved if None
+    pub session_path: Option<PathBuf>,
+    /// Panel directories, take precedence over session
+    pub left_dir: Option<PathBuf>,
+    pub right_dir: Option<PathBuf>,
+    /// Files listed in active panel, named relative to current directory
+    pub list: Option<Vec<PathBuf>>,
+    pub hotlist: Hotlist,
+    pub connections: Connections,
+}
+
 pub struct StateStore {
     state_tx: UnboundedSender<State>,
+    settings: Settings,
+    start: StartOptions,
 }
 
-impl<'a> StateStore {
-    pub fn new() -> (Self, UnboundedReceiver<State>) {
+impl StateStore {
+    pub fn new(settings: Settings, start: StartOptions) -> (Self, UnboundedReceiver<State>) {
         let (state_tx, state_rx) = mpsc::unbounded_channel::<State>();
 
-        (StateStore { state_tx }, state_rx)
+        (
+            StateStore {
+                state_tx,
+                settings,
+                start,
+            },
+            state_rx,
+        )
     }
 
     pub async fn main_loop(
@@ -41,7 +162,32 @@ impl<'a> StateStore {
         mut action_rx: UnboundedReceiver<Action>,
         mut interrupt_rx: broadcast::Receiver<Interrupted>,
     ) -> anyhow::Result<Interrupted> {
-        let mut state: State = State::new()?;
+        let start = &self.start;
+        let mut state: State = State::new(self.settings.clone(), start.session.clone())?;
+        state.config_path = start.config_path.clone();
+        state.hotlist = start.hotlist.clone();
+        state.connections = start.connections.clone();
+        if let Some(dir) = start.left_dir.clone() {
+            state.set_panel_path(PanelPosition::L, dir);
+        }
+        if let Some(dir) = start.right_dir.clone() {
+            state.set_panel_path(PanelPosition::R, dir);
+        }
+        if let Some(files) = start.list.clone() {
+            state.show_list(VfsHandle::default(), env::current_dir()?, files);
+        }
+        if let Some(msg) = start.error.clone() {
+            state.popup_msg = msg;
+            state.popup_type = PopupType::Error;
+        }
+
+        let (job_tx, mut job_rx) = mpsc::unbounded_channel::<JobEvent>();
+        let (subshell_tx, mut subshell_rx) = mpsc::unbounded_channel::<String>();
+        let mut subshell = if state.settings.shell.subshell {
+            Subshell::spawn(&state.active_panel().local_dir(), subshell_tx).ok()
+        } else {
+            None
+        };
 
         // the initial state once
         self.state_tx.send(state.clone())?;
@@ -53,8 +199,24 @@ impl<'a> StateStore {
                         state.l_panel.active = !state.l_panel.active;
                         state.r_panel.active = !state.r_panel.active;
                     },
+                    Action::Mark => {
+                        state.toggle_mark();
+                    },
+                    Action::MarkAll => {
+                        state.mark_all();
+                    },
                     Action::Reload(panel_position) => {
-                        state.reload(panel_position);
+                        // external editor has exited
+                        if state.editor_file.is_none() {
+                            self.upload_edited(&mut state, true, &job_tx);
+                        }
+                        self.reload(&mut state, panel_position, &job_tx);
+                    },
+                    Action::SortBy(order) => {
+                        self.navigate_active(&mut state, &job_tx, move |state| state.sort_by(order));
+                    },
+                    Action::ToggleViewMode => {
+                        state.toggle_view_mode();
                     },
                     Action::FileItemDown(times) => {
                         state.add_index(times);
@@ -63,50 +225,131 @@ impl<'a> StateStore {
                         state.sub_index(times);
                     },
                     Action::Cd(dir) => {
-                        state.cd(dir);
-                    },
-                    Action::Move(from, to) => {
-                        #[cfg(target_os = "windows")]
-                        let output = Command::new("move")
-                            .arg(from)
-                            .arg(to)
-                            .output()
-                            .expect("Filed to copy file");
-                        #[cfg(not(target_os = "windows"))]
-                        let output = Command::new("mv")
-                            .arg(from)
-                            .arg(to)
-                            .output()
-                            .expect("Filed to copy file");
-                        if !output.status.success() {
-                            state.popup_msg = self.get_error_msg(&output)?;
-                            state.popup_type = PopupType::Error;
-                        }
-                        state.reload(PanelPosition::L);
-                        state.reload(PanelPosition::R);
+                        self.navigate_active(&mut state, &job_tx, move |state| state.cd(dir));
+                    },
+                    Action::MoveInput(from, to) => {
+                        let files = state.selected_files(&from);
+                        let (input, msg) = match files.as_slice() {
+                            [single] => {
+                                let name = single.file_name().unwrap_or_default().to_string_lossy();
+                                (Path::new(&to).join(name.as_ref()), format!("Move {} to:", name))
+                            },
+                            files => (PathBuf::from(&to), format!("Move {} files to:", files.len())),
+                        };
+                        state.popup_input = input.display().to_string();
+                        state.popup_msg = msg;
+                        state.popup_type = PopupType::Input;
+                        state.popup_next_action = Some(Action::Move(from, true));
+                    },
+                    Action::RenameInput(file) => {
+                        let name = Path::new(&file).file_name().unwrap_or_default().to_string_lossy().to_string();
+                        state.popup_msg = format!("Rename {} to:", name);
+                        state.popup_input = name;
+                        state.popup_type = PopupType::Input;
+                        state.popup_next_action = Some(Action::Move(file, false));
+                    },
+                    Action::Move(from, marked) => {
+                        let input = PathBuf::from(state.popup_input.trim());
+                        state.close_popup();
+                        let files = match marked {
+                            true => state.selected_files(&from),
+                            false => vec![PathBuf::from(&from)],
+                        };
+                        // relative names stay next to the file, e.g. renaming in place
+                        let (target, to) = match input.is_absolute() {
+                            true => (state.opposite_panel().vfs.clone(), input),
+                            false => {
+                                let dir = match files.as_slice() {
+                                    [single] => single.parent().map(Path::to_path_buf),
+                                    _ => None,
+                                };
+                                let dir = dir.unwrap_or_else(|| state.active_panel().path.clone());
+                                (state.active_panel().vfs.clone(), dir.join(input))
+                            },
+                        };
+                        self.transfer(&mut state, files, target, to, "Moving", vfs::move_to, job_tx.clone());
                     },
                     Action::Copy(from, to) => {
-                        #[cfg(target_os = "windows")]
-                        let output = Command::new("copy")
-                            .arg(from)
-                            .arg(to)
-                            .output()
-                            .expect("Filed to copy file");
-                        #[cfg(not(target_os = "windows"))]
-                        let output = Command::new("cp")
-                            .arg("-r")
-                            .arg(from)
-                            .arg(to)
-                            .output()
-                            .expect("Filed to copy file");
-                        if !output.status.success() {
-                            state.popup_msg = self.get_error_msg(&output)?;
-                            state.popup_type = PopupType::Error;
+                        let files = state.selected_files(&from);
+                        let target = state.opposite_panel().vfs.clone();
+                        self.transfer(&mut state, files, target, PathBuf::from(to), "Copying", vfs::copy, job_tx.clone());
+                    },
+                    Action::HistoryBack => {
+                        self.go_history(&mut state, true, &job_tx);
+                    },
+                    Action::HistoryForward => {
+                        self.go_history(&mut state, false, &job_tx);
+                    },
+                    Action::FolderHistory => {
+                        state.show_folder_history();
+                    },
+                    Action::GoTo(path) => {
+                        state.close_popup();
+                        self.go_to(&mut state, &path);
+                    },
+                    Action::OpenHotlist => {
+                        state.show_hotlist();
+                    },
+                    Action::JumpBookmark(index) => {
+                        if let Some(bookmark) = state.hotlist.bookmarks.get(index).cloned() {
+                            state.close_popup();
+                            self.go_to(&mut state, &bookmark.path);
                         }
-                        state.reload(PanelPosition::L);
-                        state.reload(PanelPosition::R);
+                    },
+                    Action::JumpQuickBookmark(key) => {
+                        match state.hotlist.quick(key).cloned() {
+                            Some(bookmark) => {
+                                state.close_popup();
+                                self.go_to(&mut state, &bookmark.path);
+                            },
+                            None => {
+                                state.popup_msg = format!("Quick bookmark {} is not set", key);
+                                state.popup_type = PopupType::Error;
+                            },
+                        }
+                    },
+                    Action::SetQuickBookmark(key) => {
+                        let path = state.active_panel().local_dir();
+                        state.hotlist.set_quick(key, path);
+                        self.save_hotlist(&mut state);
+                    },
+                    Action::AddBookmarkInput => {
+                        let path = state.active_panel().local_dir();
+                        state.popup_msg = format!("Name for {}:", path.display());
+                        state.popup_type = PopupType::Input;
+                        state.popup_input = path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        state.popup_next_action = Some(Action::AddBookmark);
+                    },
+                    Action::AddBookmark => {
+                        let path = state.active_panel().local_dir();
+                        state.hotlist.add(&state.popup_input, path);
+                        state.show_hotlist();
+                        self.save_hotlist(&mut state);
+                    },
+                    Action::RenameBookmarkInput(index) => {
+                        if let Some(bookmark) = state.hotlist.bookmarks.get(index) {
+                            state.popup_msg = format!("Name for {}:", bookmark.path.display());
+                            state.popup_type = PopupType::Input;
+                            state.popup_input = bookmark.name.clone();
+                            state.popup_next_action = Some(Action::RenameBookmark(index));
+                        }
+                    },
+                    Action::RenameBookmark(index) => {
+                        let name = state.popup_input.clone();
+                        state.hotlist.rename(index, &name);
+                        state.show_hotlist();
+                        self.save_hotlist(&mut state);
+                    },
+                    Action::DeleteBookmark(index) => {
+                        state.hotlist.remove(index);
+                        state.show_hotlist();
+                        self.save_hotlist(&mut state);
                     },
                     Action::MkDirInput => {
+                        state.popup_input = String::new();
                         state.popup_msg = String::from("Create directory:");
                         state.popup_type = PopupType::Input;
                         state.popup_next_action = Some(Action::MkDir)
@@ -116,90 +359,398 @@ impl<'a> StateStore {
                     },
                     Action::MkDir => {
                         state.popup_next_action = None;
-                        let mut full_path = if state.l_panel.active {
-                            state.l_panel.path.display().to_string().clone()
-                        } else {
-                            state.r_panel.path.display().to_string().clone()
-                        };
-                        full_path.push(MAIN_SEPARATOR);
-                        full_path.push_str(&state.popup_input);
-                        #[cfg(target_os = "windows")]
-                        let output = Command::new("md")
-                            .arg(full_path)
-                            .output()
-                            .expect("Filed to create directory");
-                        #[cfg(not(target_os = "windows"))]
-                        let output = 
-                        Command::new("mkdir")
-                            .arg(full_path)
-                            .output()
-                            .expect("Filed to create directory");
-                        if !output.status.success() {
-                            state.popup_msg = self.get_error_msg(&output)?;
-                            state.popup_type = PopupType::Error;
-                        } else {
-                            state.popup_msg = String::from("");
+                        let panel = state.active_panel();
+                        let path = panel.path.join(&state.popup_input);
+                        match panel.vfs.mkdir(&path) {
+                            core::result::Result::Ok(()) => state.close_popup(),
+                            Err(err) => {
+                                state.popup_msg = format!("Can't create {}: {:#}", path.display(), err);
+                                state.popup_type = PopupType::Error;
+                            },
                         }
+                        self.reload(&mut state, PanelPosition::L, &job_tx);
+                        self.reload(&mut state, PanelPosition::R, &job_tx);
                     },
                     Action::RmYesNo(file) => {
-                        let mut msg = String::from("Do you want to remove file? ");
-                        msg.push_str(&file);
-                        state.popup_msg = msg;
+                        state.popup_msg = format!("Do you want to remove file? {}", file);
                         state.popup_type = PopupType::YesNo;
                         state.popup_next_action = Some(Action::Rm(file))
                     }
                     Action::Rm(file) => {
-                        state.popup_next_action = None;
-                        #[cfg(target_os = "windows")]
-                        let output = Command::new("del")
-                            .arg(file)
-                            .output()
-                            .expect("Filed to remove file");
-                        #[cfg(not(target_os = "windows"))]
-                        let output = Command::new("rm")
-                            .arg("-rf")
-                            .arg(file)
-                            .output()
-                            .expect("Filed to remove file");
-                        if !output.status.success() {
-                            state.popup_msg = self.get_error_msg(&output)?;
+                        state.close_popup();
+                        let vfs = state.active_panel().vfs.clone();
+                        let files = vec![PathBuf::from(file)];
+                        self.start_files_job(&mut state, "Removing", files, job_tx.clone(), move |path, report| {
+                            match path.file_name() {
+                                Some(_) => vfs::remove_all(&vfs, path, report),
+                                None => Err(anyhow::anyhow!("Can't remove {}", path.display())),
+                            }
+                        });
+                    },
+                    Action::Open(file) => {
+                        if vfs::is_archive(Path::new(&file)) {
+                            self.navigate_active(&mut state, &job_tx, move |state| state.enter_archive(Path::new(&file)));
+                        } else {
+                            match associations::find(&state.settings.associations, Path::new(&file), |a| &a.execute) {
+                                Some(command) => self.run_associated(&mut state, command, file, &job_tx)?,
+                                None => self.open_local(&mut state, file, Opener::Default, false, &job_tx)?,
+                            }
+                        }
+                    },
+                    Action::EnterArchive(file) => {
+                        if vfs::is_archive(Path::new(&file)) {
+                            self.navigate_active(&mut state, &job_tx, move |state| state.enter_archive(Path::new(&file)));
+                        } else {
+                            state.popup_msg = format!("{} is not a known archive", file);
+                            state.popup_type = PopupType::Error;
+                        }
+                    },
+                    Action::PackInput(file) => {
+                        if state.job.is_some() {
+                            state.popup_msg = String::from("Wait for the running job to finish");
                             state.popup_type = PopupType::Error;
+                        } else if let Some((_, archive)) = state.opposite_panel().vfs.parent() {
+                            let path = state.opposite_panel().path.clone();
+                            state.popup_msg = self.pack_title(&state, &file);
+                            state.popup_type = PopupType::Menu;
+                            state.popup_next_action = None;
+                            state.popup_menu = vec![
+                                MenuItem::new(
+                                    format!("Add to {}:{}", archive.display(), path.display()),
+                                    Action::PackLevels(file.clone(), false),
+                                ),
+                                MenuItem::new(String::from("New archive"), Action::PackNameInput(file)),
+                            ];
                         } else {
-                            state.popup_msg = String::from("");
+                            self.pack_name_input(&mut state, file);
                         }
                     },
-                    Action::Open(file) => {
-                        #[cfg(target_os = "windows")]
-                        let output = Command::new("cmd")
-                            .args(["/C", "start ", file])
-                            .output()
-                            .expect("Failed to open file");
-                        #[cfg(not(target_os = "windows"))]
-                        let output = Command::new("open")
-                            .arg(file)
-                            .output()
-                            .expect("Failed to open file");
-                        if !output.status.success() {
-                            state.popup_msg = self.get_error_msg(&output)?;
+                    Action::PackNameInput(file) => {
+                        self.pack_name_input(&mut state, file);
+                    },
+                    Action::PackLevels(file, new) => {
+                        let name = String::from(state.popup_input.trim());
+                        if new && !vfs::is_archive(Path::new(&name)) {
+                            state.popup_msg = format!("{} is not a zip or tar archive name", name);
                             state.popup_type = PopupType::Error;
+                        } else {
+                            let archive = new.then_some(name);
+                            state.popup_msg = String::from("Compression level");
+                            state.popup_type = PopupType::Menu;
+                            state.popup_next_action = None;
+                            state.popup_menu = (1..=9)
+                                .map(|level| {
+                                    let label = match level {
+                                        1 => String::from("1 fastest"),
+                                        6 => String::from("6 default"),
+                                        9 => String::from("9 smallest"),
+                                        level => level.to_string(),
+                                    };
+                                    MenuItem::new(label, Action::Pack(file.clone(), archive.clone(), level))
+                                })
+                                .collect();
+                        }
+                    },
+                    Action::Pack(file, archive, level) => {
+                        state.close_popup();
+                        self.start_pack(&mut state, &file, archive, level, job_tx.clone());
+                    },
+                    Action::View(file) => {
+                        match associations::find(&state.settings.associations, Path::new(&file), |a| &a.view) {
+                            Some(command) => self.run_associated(&mut state, command, file, &job_tx)?,
+                            None => self.open_local(&mut state, file, Opener::Viewer, false, &job_tx)?,
+                        }
+                    },
+                    Action::OpenWithMenu(file) => {
+                        let path = PathBuf::from(&file);
+                        let mut menu = vec![];
+                        for association in state.settings.associations.iter().filter(|a| a.matches(&path)) {
+                            for command in [&association.execute, &association.view, &association.edit].into_iter().flatten() {
+                                menu.push(MenuItem::new(
+                                    format!("{}: {}", association.description, command),
+                                    Action::RunAssociated(command.clone(), file.clone()),
+                                ));
+                            }
+                        }
+                        menu.push(MenuItem::new(String::from("Internal editor"), Action::Edit(file.clone())));
+                        menu.push(MenuItem::new(format!("External editor: {}", state.external_editor()), Action::ExternalEdit(file.clone())));
+                        menu.push(MenuItem::new(format!("External viewer: {}", state.external_viewer()), Action::ExternalView(file.clone())));
+                        menu.push(MenuItem::new(String::from("System default"), Action::OpenDefault(file)));
+                        state.popup_msg = String::from("Open with");
+                        state.popup_type = PopupType::Menu;
+                        state.popup_menu = menu;
+                    },
+                    Action::RunAssociated(command, file) => {
+                        state.close_popup();
+                        self.run_associated(&mut state, command, file, &job_tx)?;
+                    },
+                    Action::OpenDefault(file) => {
+                        state.close_popup();
+                        self.open_local(&mut state, file, Opener::Default, false, &job_tx)?;
+                    },
+                    Action::EditAssociated(file) => {
+                        let opener = match associations::find(&state.settings.associations, Path::new(&file), |a| &a.edit) {
+                            Some(command) => Opener::Command(command),
+                            None => Opener::Editor,
+                        };
+                        self.open_local(&mut state, file, opener, true, &job_tx)?;
+                    },
+                    Action::RunCommand(command) => {
+                        if state.command_history.last() != Some(&command) {
+                            state.command_history.push(command.clone());
+                        }
+                        if state.command_history.len() > COMMAND_HISTORY_SIZE {
+                            state.command_history.remove(0);
+                        }
+                        if subshell.as_mut().is_some_and(|shell| !shell.alive()) {
+                            subshell = None;
+                        }
+                        let builtin_cd = command.strip_prefix("cd").filter(|dir| dir.is_empty() || dir.starts_with(' '));
+                        if let Some(shell) = subshell.as_mut() {
+                            if shell.busy() {
+                                state.popup_msg = String::from("Previous command is still running");
+                                state.popup_type = PopupType::Error;
+                            } else if let Err(err) = shell.run(&command, &state.active_panel().local_dir()) {
+                                state.popup_msg = format!("Failed to run command in subshell: {}", err);
+                                state.popup_type = PopupType::Error;
+                                subshell = None;
+                            } else {
+                                state.subshell_busy = true;
+                                state.show_output = true;
+                            }
+                        } else if let Some(dir) = builtin_cd {
+                            self.change_dir(&mut state, dir.trim());
+                        } else {
+                            let cwd = state.active_panel().local_dir();
+                            state.terminal_output.end_line();
+                            state.terminal_output.push(&format!("{}> {}\n", cwd.display(), command));
+                            state.external_command = Some(ExternalCommand::shell(command, cwd).capture());
+                        }
+                    },
+                    Action::CommandOutput(output) => {
+                        state.terminal_output.push(&output);
+                    },
+                    Action::SubshellInput(input) => {
+                        if let Some(shell) = subshell.as_mut() {
+                            let _ = shell.input(&input);
                         }
                     },
+                    Action::Resize(cols, rows) => {
+                        if let Some(shell) = subshell.as_ref() {
+                            shell.resize(cols, rows);
+                        }
+                    },
+                    Action::ToggleOutput => {
+                        state.show_output = !state.show_output;
+                    },
                     Action::Edit(file) => {
-                        state.editor_file = Some(file);
-                        state.editor_modified = false;
+                        state.close_popup();
+                        self.open_local(&mut state, file, Opener::Editor, true, &job_tx)?;
                     },
                     Action::EditorResedModified => {
                         state.editor_modified = false;
+                        state.editor_text = None;
+                        self.upload_edited(&mut state, false, &job_tx);
                     },
                     Action::EditorModified => {
                         state.editor_modified = true;
                     },
+                    Action::EditorText(text) => {
+                        if state.editor_modified {
+                            state.editor_text = Some(text);
+                        }
+                    },
                     Action::EditorExit => {
                         state.editor_file = None;
+                        state.editor_text = None;
+                        self.upload_edited(&mut state, true, &job_tx);
                     },
-                    Action::Cancel => {
-                        state.popup_msg = String::from("");
+                    Action::ExternalEdit(file) => {
+                        state.close_popup();
+                        self.open_local(&mut state, file, Opener::ExternalEditor, true, &job_tx)?;
+                    },
+                    Action::ExternalView(file) => {
+                        state.close_popup();
+                        self.open_local(&mut state, file, Opener::Viewer, false, &job_tx)?;
+                    },
+                    Action::Error(msg) => {
+                        state.popup_msg = msg;
+                        state.popup_type = PopupType::Error;
+                    },
+                    Action::OpenSettings => {
+                        state.settings_open = true;
+                        state.settings_message = String::from("");
+                    },
+                    Action::CloseSettings => {
+                        state.settings_open = false;
+                    },
+                    Action::SetSetting(name, value) => {
+                        match state.settings.set_field(&name, &value) {
+                            core::result::Result::Ok(settings) => {
+                                state.keymap = Arc::new(Keymap::new(&settings.keys).unwrap_or_default());
+                                state.theme = Arc::new(settings.theme().unwrap_or_default());
+                                state.highlighting = Arc::new(Highlighting::new(
+                                    &settings.highlight,
+                                    settings.ui.color_depth,
+                                ));
+                                // changed defaults apply to both panels
+                                let old = &state.settings.panels;
+                                for panel in [&mut state.l_panel, &mut state.r_panel] {
+                                    if settings.panels.sort != old.sort || settings.panels.sort_reverse != old.sort_reverse {
+                                        panel.sort = settings.panels.sort;
+                                        panel.sort_reverse = settings.panels.sort_reverse;
+                                    }
+                                    if settings.panels.view_mode != old.view_mode {
+                                        panel.view_mode = settings.panels.view_mode;
+                                    }
+                                }
+                                state.settings = settings;
+                                state.settings_message = String::from("");
+                                self.reload(&mut state, PanelPosition::L, &job_tx);
+                                self.reload(&mut state, PanelPosition::R, &job_tx);
+                            },
+                            Err(err) => {
+                                state.settings_message = format!("{:#}", err);
+                            },
+                        }
+                    },
+                    Action::SaveSettings => {
+                        state.settings_message = match &state.config_path {
+                            Some(path) => match state.settings.save(path) {
+                                core::result::Result::Ok(()) => format!("Saved to {}", path.display()),
+                                Err(err) => format!("Failed to save settings: {:#}", err),
+                            },
+                            None => String::from("Can't find config directory"),
+                        };
+                    },
+                    Action::OpenConnect => {
+                        state.connect_open = true;
+                        state.connect_message = String::from("");
+                    },
+                    Action::CloseConnect => {
+                        state.connect_open = false;
+                        state.profile_edit = None;
+                    },
+                    Action::SetConnectField(name, value) if name == NAME_FIELD && state.profile_edit.is_some() => {
+                        if let Some(edit) = state.profile_edit.as_mut() {
+                            edit.name = value;
+                        }
+                        state.connect_message = String::from("");
+                    },
+                    Action::SetConnectField(name, value) => {
+                        state.connect_message = match state.connection.set_field(&name, &value) {
+                            core::result::Result::Ok(()) => String::from(""),
+                            Err(err) => format!("{:#}", err),
+                        };
+                    },
+                    Action::Connect(trusted) => {
+                        self.start_connect(&mut state, trusted, job_tx.clone());
+                    },
+                    Action::OpenNetwork => {
+                        state.show_network();
+                    },
+                    Action::ConnectProfile(index) => {
+                        if let Some(profile) = state.connections.profiles.get(index) {
+                            state.connection = profile.connection.clone();
+                            self.start_connect(&mut state, None, job_tx.clone());
+                        }
+                    },
+                    Action::AddProfile => {
+                        state.connection = Connection::default();
+                        state.profile_edit = Some(ProfileEdit::default());
+                        state.connect_open = true;
+                        state.connect_message = String::from("");
+                    },
+                    Action::EditProfile(index) => {
+                        if let Some(profile) = state.connections.profiles.get(index) {
+                            state.connection = profile.connection.clone();
+                            state.profile_edit = Some(ProfileEdit {
+                                index: Some(index),
+                                name: profile.name.clone(),
+                            });
+                            state.connect_open = true;
+                            state.connect_message = String::from("");
+                        }
+                    },
+                    Action::SaveProfile => {
+                        if let Some(edit) = state.profile_edit.clone() {
+                            let profile = Profile {
+                                name: edit.name,
+                                connection: state.connection.clone(),
+                            };
+                            match state.connections.set(edit.index, profile) {
+                                core::result::Result::Ok(()) => {
+                                    state.connect_open = false;
+                                    state.profile_edit = None;
+                                    state.refresh_network();
+                                    self.save_connections(&mut state);
+                                },
+                                Err(err) => state.connect_message = format!("{:#}", err),
+                            }
+                        }
+                    },
+                    Action::DuplicateProfile(index) => {
+                        state.connections.duplicate(index);
+                        state.refresh_network();
+                        // cursor goes to the copy right after the original
+                        state.add_index(1);
+                        self.save_connections(&mut state);
+                    },
+                    Action::DeleteProfileYesNo(index) => {
+                        if let Some(profile) = state.connections.profiles.get(index) {
+                            state.popup_msg = format!("Do you want to delete connection {}?", profile.name);
+                            state.popup_type = PopupType::YesNo;
+                            state.popup_next_action = Some(Action::DeleteProfile(index));
+                        }
+                    },
+                    Action::DeleteProfile(index) => {
                         state.popup_next_action = None;
+                        state.connections.remove(index);
+                        state.refresh_network();
+                        self.save_connections(&mut state);
+                    },
+                    Action::OpenFind => {
+                        state.find_open = true;
+                    },
+                    Action::CloseFind => {
+                        state.search.cancel.store(true, Ordering::Relaxed);
+                        state.find_open = false;
+                    },
+                    Action::SetFindField(name, value) => {
+                        state.search.message = match state.search.options.set_field(&name, &value) {
+                            core::result::Result::Ok(()) => String::from(""),
+                            Err(err) => format!("{:#}", err),
+                        };
+                    },
+                    Action::StartSearch => {
+                        self.start_search(&mut state, job_tx.clone());
+                    },
+                    Action::StopSearch => {
+                        state.search.cancel.store(true, Ordering::Relaxed);
+                    },
+                    Action::GoToFound(index) => {
+                        if let Some(path) = state.search.results.get(index).cloned() {
+                            state.find_open = false;
+                            let vfs = state.search.vfs.clone();
+                            let position = state.active_position();
+                            self.navigate(&mut state, position, &vfs.clone(), &job_tx, move |state| state.show_file(vfs, &path));
+                        }
+                    },
+                    Action::SearchPanel => {
+                        if state.search.results.is_empty() {
+                            state.search.message = String::from("Nothing found to list");
+                        } else {
+                            state.search.cancel.store(true, Ordering::Relaxed);
+                            state.find_open = false;
+                            let search = state.search.clone();
+                            let position = state.active_position();
+                            self.navigate(&mut state, position, &search.vfs.clone(), &job_tx, move |state| {
+                                state.show_list(search.vfs, search.root, search.results)
+                            });
+                        }
+                    },
+                    Action::Cancel => {
+                        state.close_popup();
                     },
                     Action::Exit => {
                         let _ = terminator.terminate(Interrupted::UserInt);
@@ -207,6 +758,119 @@ impl<'a> StateStore {
                         break Interrupted::UserInt;
                     }
                 },
+                Some(output) = subshell_rx.recv() => {
+                    for payload in state.terminal_output.push(&output) {
+                        let Some(dir) = subshell.as_mut().and_then(|shell| shell.handle_osc(&payload)) else {
+                            continue;
+                        };
+                        // prompt is back, command has finished
+                        if state.subshell_busy {
+                            state.subshell_busy = false;
+                            state.show_output = false;
+                        }
+                        // panel showing archive stays there, shell is in directory of the archive
+                        let panel = state.active_panel();
+                        if panel.vfs.is_local() && dir != panel.path && dir.is_dir() {
+                            state.cd(dir.display().to_string());
+                        }
+                    }
+                },
+                Some(event) = job_rx.recv() => match event {
+                    JobEvent::Progress(status) => {
+                        state.job = Some(status);
+                    },
+                    JobEvent::Finished { target, archive, result } => {
+                        state.job = None;
+                        for position in [PanelPosition::L, PanelPosition::R] {
+                            let vfs = state.panel(&position).vfs.clone();
+                            let changed = vfs
+                                .parent()
+                                .is_some_and(|(parent, file)| parent.same(&target) && file == archive);
+                            match changed {
+                                true => self.navigate(&mut state, position.clone(), &vfs, &job_tx, move |state| {
+                                    state.reopen_archive(position)
+                                }),
+                                false => self.reload(&mut state, position, &job_tx),
+                            }
+                        }
+                        if let Err(err) = result {
+                            state.popup_msg = format!("Packing failed: {}", err);
+                            state.popup_type = PopupType::Error;
+                        }
+                        self.upload_edited(&mut state, false, &job_tx);
+                    },
+                    JobEvent::FilesDone(result) => {
+                        state.job = None;
+                        self.reload(&mut state, PanelPosition::L, &job_tx);
+                        self.reload(&mut state, PanelPosition::R, &job_tx);
+                        if let Err(err) = result {
+                            state.popup_msg = err;
+                            state.popup_type = PopupType::Error;
+                        }
+                        self.upload_edited(&mut state, false, &job_tx);
+                    },
+                    JobEvent::Connected(result) => {
+                        state.connecting = false;
+                        match result {
+                            core::result::Result::Ok((vfs, path)) => {
+                                state.connect_open = false;
+                                let position = state.active_position();
+                                self.navigate(&mut state, position, &vfs.clone(), &job_tx, move |state| state.open_remote(vfs, path));
+                            },
+                            Err(err) => match err.downcast_ref::<UnknownHost>() {
+                                Some(unknown) => {
+                                    state.connect_open = false;
+                                    state.popup_msg = format!("{}. Do you trust it and want to connect?", unknown);
+                                    state.popup_type = PopupType::YesNo;
+                                    state.popup_next_action = Some(Action::Connect(Some(unknown.fingerprint.clone())));
+                                },
+                                None => {
+                                    state.connect_message = format!("{:#}", err);
+                                },
+                            },
+                        }
+                    },
+                    JobEvent::Found { id, files, dir } if id == state.search.id => {
+                        state.search.results.extend(files);
+                        state.search.message = format!(
+                            "{} found, searching {}{}",
+                            state.search.results.len(),
+                            state.search.vfs.prefix(),
+                            dir.display()
+                        );
+                    },
+                    JobEvent::SearchFinished { id, files } if id == state.search.id => {
+                        let search = &mut state.search;
+                        search.results.extend(files);
+                        search.running = false;
+                        search.message = match search.cancel.load(Ordering::Relaxed) {
+                            true => format!("Stopped, {} found", search.results.len()),
+                            false => format!("{} found", search.results.len()),
+                        };
+                    },
+                    // reports of earlier search
+                    JobEvent::Found { .. } | JobEvent::SearchFinished { .. } => {},
+                    JobEvent::Navigated { position, copy } => {
+                        state.take_panel(&position, *copy);
+                    },
+                    JobEvent::Downloaded { result, opener } => {
+                        state.job = None;
+                        // edit saved meanwhile is uploaded before the new one replaces it
+                        self.upload_edited(&mut state, false, &job_tx);
+                        match result {
+                            core::result::Result::Ok((local, edit)) => {
+                                if edit.is_some() {
+                                    state.remote_edit = edit;
+                                }
+                                self.open_with(&mut state, local.display().to_string(), opener)?;
+                            },
+                            Err(err) => {
+                                state.popup_msg = err;
+                                state.popup_type = PopupType::Error;
+                            },
+                        }
+                    },
+                },
                 // Catch and handle interrupt signal to gracefully shutdown
                 core::result::Result::Ok(interrupted) = interrupt_rx.recv() => {
                     break interrupted;
@@ -214,11 +878,586 @@ impl<'a> StateStore {
             }
 
             self.state_tx.send(state.clone())?;
+            // external command is handed to UI only once
+            state.external_command = None;
         };
 
+        if let Some(path) = &self.start.session_path {
+            if let Err(err) = Session::from(&state).save(path) {
+                eprintln!("Failed to save session to {}: {:#}", path.display(), err);
+            }
+        }
+
+        vfs::remove_temp_dir();
+
         Ok(result)
     }
 
+    /// Built-in `cd`, as child shell can't change directory of the panel
+    fn change_dir(&self, state: &mut State, dir: &str) {
+        let home = env::var("HOME").unwrap_or_default();
+        let target = if dir.is_empty() || dir == "~" {
+            PathBuf::from(home)
+        } else if let Some(rest) = dir.strip_prefix("~/") {
+            PathBuf::from(home).join(rest)
+        } else {
+            state.active_panel().local_dir().join(dir)
+        };
+        if target.is_dir() {
+            state.cd_local(target);
+        } else {
+            state.popup_msg = format!("cd: {}: No such directory", dir);
+            state.popup_type = PopupType::Error;
+        }
+    }
+
+    /// Copies or moves files of active panel into directory `to` of `target`. Single
+    /// file is given the name `to` unless it's an existing directory.
+    #[allow(clippy::too_many_arguments)]
+    fn transfer(
+        &self,
+        state: &mut State,
+        files: Vec<PathBuf>,
+        target: VfsHandle,
+        to: PathBuf,
+        verb: &str,
+        operation: TransferFn,
+        job_tx: UnboundedSender<JobEvent>,
+    ) {
+        let source = state.active_panel().vfs.clone();
+        // listed files come from different directories, so they keep their path
+        // below the listed one and files of the same name don't overwrite each other
+        let panel = state.active_panel();
+        let root = panel.listed.is_some().then(|| panel.path.clone());
+        let mut targets = HashMap::new();
+        let mut written = HashSet::new();
+        for file in &files {
+            let relative = match root.as_ref().and_then(|root| file.strip_prefix(root).ok()) {
+                Some(relative) => relative.to_path_buf(),
+                None => match file.file_name() {
+                    Some(name) => PathBuf::from(name),
+                    None => {
+                        state.popup_msg = format!("Can't copy {}", file.display());
+                        state.popup_type = PopupType::Error;
+                        return;
+                    },
+                },
+            };
+            let file_to = to.join(relative);
+            if !written.insert(file_to.clone()) {
+                state.popup_msg = format!("{} would be written twice, copy the files apart", file_to.display());
+                state.popup_type = PopupType::Error;
+                return;
+            }
+            targets.insert(file.clone(), file_to);
+        }
+        let single = files.len() == 1;
+        let started = self.start_files_job(state, verb, files, job_tx, move |from, report| {
+            let into_dir = !single || target.stat(&to).is_ok_and(|info| info.is_dir);
+            let to = if into_dir { &targets[from] } else { &to };
+            if let Some(dir) = to.parent() {
+                vfs::create_dir_all(&target, dir)?;
+            }
+            operation(&source, from, &target, to, report)
+        });
+        // files are taken, so marks don't linger after the job
+        if started {
+            state.clear_marks();
+        }
+    }
+
+    /// Runs `work` for each file of active panel on a thread of its own, which
+    /// reports progress to the main loop. Panels are reloaded when it ends.
+    /// Returns false when another job is still running.
+    fn start_files_job(
+        &self,
+        state: &mut State,
+        verb: &str,
+        files: Vec<PathBuf>,
+        job_tx: UnboundedSender<JobEvent>,
+        work: impl Fn(&Path, &mut dyn FnMut(&Path, u64)) -> anyhow::Result<()> + Send + 'static,
+    ) -> bool {
+        if state.job.is_some() {
+            state.popup_msg = String::from("Wait for the running job to finish");
+            state.popup_type = PopupType::Error;
+            return false;
+        }
+        let title = match files.as_slice() {
+            [single] => format!("{} {}", verb, single.file_name().unwrap_or_default().to_string_lossy()),
+            files => format!("{} {} files", verb, files.len()),
+        };
+        state.job = Some(JobStatus {
+            title: title.clone(),
+            ..Default::default()
+        });
+        let vfs = state.active_panel().vfs.clone();
+        thread::spawn(move || {
+            // sizes only drive the gauge, files that can't be read fail below
+            let sizes: Vec<u64> = files
+                .iter()
+                .map(|file| vfs::tree_size(&vfs, file).unwrap_or_default())
+                .collect();
+            let total = sizes.iter().sum();
+            let mut done = 0;
+            let mut reported: Option<Instant> = None;
+            let result = files.iter().zip(sizes).try_for_each(|(file, size)| {
+                let start = done;
+                work(file, &mut |path, len| {
+                    done += len;
+                    if reported.is_some_and(|time| time.elapsed() < PROGRESS_INTERVAL) {
+                        return;
+                    }
+                    reported = Some(Instant::now());
+                    let _ = job_tx.send(JobEvent::Progress(JobStatus {
+                        title: title.clone(),
+                        file: path.display().to_string(),
+                        done,
+                        total,
+                    }));
+                })?;
+                // renamed files report nothing, links and changed files differ from size
+                done = start + size;
+                Ok(())
+            });
+            let _ = job_tx.send(JobEvent::FilesDone(result.map_err(|err| format!("{:#}", err))));
+        });
+        true
+    }
+
+    /// Names what is about to be packed, marked files by their count
+    fn pack_title(&self, state: &State, file: &str) -> String {
+        match state.selected_files(file).as_slice() {
+            [single] => format!("Pack {}", single.file_name().unwrap_or_default().to_string_lossy()),
+            files => format!("Pack {} marked files", files.len()),
+        }
+    }
+
+    fn pack_name_input(&self, state: &mut State, file: String) {
+        let files = state.selected_files(&file);
+        let name = match files.as_slice() {
+            [single] => single.file_name(),
+            _ => state.active_panel().path.file_name(),
+        };
+        let name = name.map(|name| name.to_string_lossy().to_string());
+        state.popup_input = format!("{}.tar.gz", name.as_deref().unwrap_or("archive"));
+        state.popup_msg = format!(
+            "{} as (.zip, .tar.gz, .tar.xz, .tar.zst, .tar.bz2):",
+            self.pack_title(state, &file)
+        );
+        state.popup_type = PopupType::Input;
+        state.popup_next_action = Some(Action::PackLevels(file, true));
+    }
+
+    /// Packs marked files, or given one, into new archive in directory of active panel,
+    /// or adds them to archive of the other panel when no name is given. Files are
+    /// packed on a thread of their own, which reports to the main loop.
+    fn start_pack(
+        &self,
+        state: &mut State,
+        file: &str,
+        archive: Option<String>,
+        level: u32,
+        job_tx: UnboundedSender<JobEvent>,
+    ) {
+        let files: Vec<PathBuf> = state
+            .selected_files(file)
+            .into_iter()
+            .filter(|file| file.file_name().is_some())
+            .collect();
+        if files.is_empty() {
+            state.popup_msg = String::from("Nothing to pack");
+            state.popup_type = PopupType::Error;
+            return;
+        }
+        let active = state.active_panel();
+        let options = match archive {
+            Some(name) => PackOptions {
+                source: active.vfs.clone(),
+                files,
+                target: active.vfs.clone(),
+                archive: active.path.join(name),
+                base: PathBuf::from("/"),
+                level,
+                append: false,
+            },
+            None => {
+                let other = state.opposite_panel();
+                let Some((target, archive)) = other.vfs.parent() else {
+                    return;
+                };
+                PackOptions {
+                    source: active.vfs.clone(),
+                    files,
+                    target,
+                    archive,
+                    base: other.path.clone(),
+                    level,
+                    append: true,
+                }
+            }
+        };
+        let title = format!(
+            "Packing {}",
+            options.archive.file_name().unwrap_or_default().to_string_lossy()
+        );
+        state.job = Some(JobStatus {
+            title: title.clone(),
+            ..Default::default()
+        });
+        // files to pack are taken, so marks don't linger after the job
+        state.clear_marks();
+        thread::spawn(move || {
+            let mut reported: Option<Instant> = None;
+            let result = vfs::pack(&options, &mut |file, done, total| {
+                if reported.is_some_and(|time| time.elapsed() < PROGRESS_INTERVAL) {
+                    return;
+                }
+                reported = Some(Instant::now());
+                let _ = job_tx.send(JobEvent::Progress(JobStatus {
+                    title: title.clone(),
+                    file: file.display().to_string(),
+                    done,
+                    total,
+                }));
+            });
+            let _ = job_tx.send(JobEvent::Finished {
+                target: options.target,
+                archive: options.archive,
+                result: result.map_err(|err| format!("{:#}", err)),
+            });
+        });
+    }
+
+    fn go_to(&self, state: &mut State, path: &Path) {
+        if path.is_dir() {
+            state.cd_local(path.to_path_buf());
+        } else {
+            state.popup_msg = format!("{}: No such directory", path.display());
+            state.popup_type = PopupType::Error;
+        }
+    }
+
+    fn save_hotlist(&self, state: &mut State) {
+        if let Err(err) = state.hotlist.save() {
+            state.popup_msg = format!("Failed to save hotlist: {:#}", err);
+            state.popup_type = PopupType::Error;
+        }
+    }
+
+    /// Searches directory of active panel with options of find dialog on a thread of
+    /// its own, which streams found files to the main loop. Search running before is stopped.
+    fn start_search(&self, state: &mut State, job_tx: UnboundedSender<JobEvent>) {
+        let search = &mut state.search;
+        search.cancel.store(true, Ordering::Relaxed);
+        let matcher = match search.options.matcher() {
+            core::result::Result::Ok(matcher) => matcher,
+            Err(err) => {
+                search.message = format!("{:#}", err);
+                return;
+            }
+        };
+        let panel = if state.l_panel.active {
+            &state.l_panel
+        } else {
+            &state.r_panel
+        };
+        search.id += 1;
+        search.vfs = panel.vfs.clone();
+        search.root = panel.path.clone();
+        search.results.clear();
+        search.running = true;
+        search.message = String::from("Searching...");
+        search.cancel = Arc::new(AtomicBool::new(false));
+        let (id, vfs, root, cancel) = (
+            search.id,
+            search.vfs.clone(),
+            search.root.clone(),
+            search.cancel.clone(),
+        );
+        thread::spawn(move || {
+            let mut files = vec![];
+            let mut reported = Instant::now();
+            matcher.search(&vfs, &root, &cancel, &mut |progress| {
+                let dir = match progress {
+                    Progress::Found(path) => {
+                        files.push(path.to_path_buf());
+                        path.parent().unwrap_or(path)
+                    }
+                    Progress::Directory(dir) => dir,
+                };
+                if reported.elapsed() < PROGRESS_INTERVAL {
+                    return;
+                }
+                reported = Instant::now();
+                let _ = job_tx.send(JobEvent::Found {
+                    id,
+                    files: std::mem::take(&mut files),
+                    dir: dir.to_path_buf(),
+                });
+            });
+            let _ = job_tx.send(JobEvent::SearchFinished { id, files });
+        });
+    }
+
+    fn save_connections(&self, state: &mut State) {
+        if let Err(err) = state.connections.save() {
+            state.popup_msg = format!("Failed to save connections: {:#}", err);
+            state.popup_type = PopupType::Error;
+        }
+    }
+
+    /// Pins FTPS certificate user trusted to connection of the dialog, and to saved
+    /// connections to the same server, so they aren't asked about again
+    fn trust_certificate(&self, state: &mut State, fingerprint: &str) {
+        let mut saved = false;
+        for profile in state.connections.profiles.iter_mut() {
+            if profile.connection == state.connection {
+                profile.connection.certificate = String::from(fingerprint);
+                saved = true;
+            }
+        }
+        state.connection.certificate = String::from(fingerprint);
+        if saved {
+            self.save_connections(state);
+        }
+    }
+
+    /// Connects active panel to server of connection dialog on a thread of its own,
+    /// dialog shows progress and errors
+    fn start_connect(&self, state: &mut State, trusted: Option<String>, job_tx: UnboundedSender<JobEvent>) {
+        state.close_popup();
+        if state.connecting {
+            return;
+        }
+        if let Some(fingerprint) = trusted.as_ref().filter(|_| state.connection.protocol == Protocol::Ftps) {
+            self.trust_certificate(state, fingerprint);
+        }
+        state.connecting = true;
+        state.connect_open = true;
+        state.connect_message = format!("Connecting to {}...", state.connection.host);
+        let connection = state.connection.clone();
+        thread::spawn(move || {
+            let _ = job_tx.send(JobEvent::Connected(connection.open(trusted.as_deref())));
+        });
+    }
+
+    /// Changes panel at `position` with `navigate`. When the panel shows remote
+    /// filesystem or goes to `vfs` which is remote, so it can take long, it's done
+    /// on a copy of state on a thread of its own. Panel shows it's loading until
+    /// then and isn't changed otherwise.
+    fn navigate(
+        &self,
+        state: &mut State,
+        position: PanelPosition,
+        vfs: &VfsHandle,
+        job_tx: &UnboundedSender<JobEvent>,
+        navigate: impl FnOnce(&mut State) + Send + 'static,
+    ) {
+        let panel = state.panel(&position);
+        if panel.loading {
+            return;
+        }
+        if panel.vfs.is_local() && vfs.is_local() {
+            return navigate(state);
+        }
+        // only errors of this navigation are taken from the copy
+        let mut copy = state.clone();
+        copy.close_popup();
+        match position {
+            PanelPosition::L => state.l_panel.loading = true,
+            PanelPosition::R => state.r_panel.loading = true,
+        }
+        let job_tx = job_tx.clone();
+        thread::spawn(move || {
+            navigate(&mut copy);
+            let _ = job_tx.send(JobEvent::Navigated {
+                position,
+                copy: Box::new(copy),
+            });
+        });
+    }
+
+    fn navigate_active(
+        &self,
+        state: &mut State,
+        job_tx: &UnboundedSender<JobEvent>,
+        navigate: impl FnOnce(&mut State) + Send + 'static,
+    ) {
+        let position = state.active_position();
+        let vfs = state.active_panel().vfs.clone();
+        self.navigate(state, position, &vfs, job_tx, navigate);
+    }
+
+    fn reload(&self, state: &mut State, position: PanelPosition, job_tx: &UnboundedSender<JobEvent>) {
+        let vfs = state.panel(&position).vfs.clone();
+        self.navigate(state, position.clone(), &vfs, job_tx, move |state| state.reload(position));
+    }
+
+    /// History may lead to another filesystem, the one of the next directory decides
+    /// whether it's read in background
+    fn go_history(&self, state: &mut State, back: bool, job_tx: &UnboundedSender<JobEvent>) {
+        let panel = state.active_panel();
+        let next = match back {
+            true => panel.back.last(),
+            false => panel.forward.last(),
+        };
+        let Some(vfs) = next.map(|location| location.vfs.clone()) else {
+            return;
+        };
+        let position = state.active_position();
+        self.navigate(state, position, &vfs, job_tx, move |state| state.go_history(back));
+    }
+
+    /// Opens file of active panel with `opener`. Files on local disk are opened in
+    /// place, remote files through local copy downloaded on a thread of its own.
+    /// Copy of file being `edit`ed is uploaded after saving, archives can't be edited.
+    fn open_local(
+        &self,
+        state: &mut State,
+        file: String,
+        opener: Opener,
+        edit: bool,
+        job_tx: &UnboundedSender<JobEvent>,
+    ) -> anyhow::Result<()> {
+        let vfs = state.active_panel().vfs.clone();
+        if vfs.is_local() {
+            return self.open_with(state, file, opener);
+        }
+        if edit && vfs.parent().is_some() {
+            state.popup_msg = format!("Can't edit files in {}, copy them to local disk first", vfs.prefix());
+            state.popup_type = PopupType::Error;
+            return Ok(());
+        }
+        if state.job.is_some() {
+            state.popup_msg = String::from("Wait for the running job to finish");
+            state.popup_type = PopupType::Error;
+            return Ok(());
+        }
+        let path = PathBuf::from(file);
+        state.job = Some(JobStatus {
+            title: format!("Downloading {}", path.file_name().unwrap_or_default().to_string_lossy()),
+            ..Default::default()
+        });
+        let job_tx = job_tx.clone();
+        thread::spawn(move || {
+            let result = match edit {
+                true => RemoteEdit::new(vfs, path).map(|edit| (edit.local.clone(), Some(edit))),
+                false => vfs::local_copy(&vfs, &path).map(|local| (local, None)),
+            };
+            let _ = job_tx.send(JobEvent::Downloaded {
+                result: result.map_err(|err| format!("{:#}", err)),
+                opener,
+            });
+        });
+        Ok(())
+    }
+
+    /// Opens file on local disk
+    fn open_with(&self, state: &mut State, file: String, opener: Opener) -> anyhow::Result<()> {
+        let cwd = state.active_panel().local_dir();
+        match opener {
+            Opener::Viewer => state.external_command = ExternalCommand::new(&state.external_viewer(), file, cwd),
+            Opener::ExternalEditor => state.external_command = ExternalCommand::new(&state.external_editor(), file, cwd),
+            Opener::Editor => {
+                state.editor_file = Some(file);
+                state.editor_modified = false;
+                state.editor_text = None;
+            },
+            Opener::Command(command) => self.run_with_files(state, command, vec![PathBuf::from(file)]),
+            Opener::Default => self.open_default(state, file)?,
+        }
+        Ok(())
+    }
+
+    /// Uploads remote file edited through local copy on a thread of its own when it
+    /// was saved, forgetting it when editing is `done`. Upload waits for running job,
+    /// which calls this again when it ends.
+    fn upload_edited(&self, state: &mut State, done: bool, job_tx: &UnboundedSender<JobEvent>) {
+        let Some(edit) = state.remote_edit.as_mut() else {
+            return;
+        };
+        if !edit.changed() {
+            if done {
+                state.remote_edit = None;
+            }
+            return;
+        }
+        if state.job.is_some() {
+            return;
+        }
+        let upload = edit.clone();
+        edit.set_uploaded();
+        if done {
+            state.remote_edit = None;
+        }
+        state.job = Some(JobStatus {
+            title: format!("Uploading {}", upload.path.file_name().unwrap_or_default().to_string_lossy()),
+            ..Default::default()
+        });
+        let job_tx = job_tx.clone();
+        thread::spawn(move || {
+            let result = upload.upload().map_err(|err| format!("{:#}", err));
+            let _ = job_tx.send(JobEvent::FilesDone(result));
+        });
+    }
+
+    fn run_associated(
+        &self,
+        state: &mut State,
+        command: String,
+        file: String,
+        job_tx: &UnboundedSender<JobEvent>,
+    ) -> anyhow::Result<()> {
+        // marked files are passed only from local disk, other files are copied one at a time
+        match state.active_panel().vfs.is_local() {
+            true => {
+                let files = state.selected_files(&file);
+                self.run_with_files(state, command, files);
+                Ok(())
+            },
+            false => self.open_local(state, file, Opener::Command(command), false, job_tx),
+        }
+    }
+
+    fn run_with_files(&self, state: &mut State, command: String, files: Vec<PathBuf>) {
+        match associations::substitute(&command, &files) {
+            core::result::Result::Ok(substituted) => {
+                let cwd = files[0]
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|| state.active_panel().local_dir());
+                let command = ExternalCommand::shell(substituted.command_line, cwd);
+                state.external_command = Some(command.with_temp_dir(substituted.temp_dir));
+            }
+            Err(err) => {
+                state.popup_msg = err.to_string();
+                state.popup_type = PopupType::Error;
+            }
+        }
+    }
+
+    /// Opens file on local disk with program registered in the system
+    fn open_default(&self, state: &mut State, file: String) -> anyhow::Result<()> {
+        #[cfg(target_os = "windows")]
+        let output = Command::new("cmd").args(["/C", "start ", &file]).output();
+        #[cfg(target_os = "macos")]
+        let output = Command::new("open").arg(&file).output();
+        #[cfg(all(not(target_os = "windows"), not(target_os = "macos")))]
+        let output = Command::new("xdg-open").arg(&file).output();
+        match output {
+            io::Result::Ok(output) if !output.status.success() => {
+                state.popup_msg = self.get_error_msg(&output)?;
+                state.popup_type = PopupType::Error;
+            }
+            io::Result::Err(err) => {
+                state.popup_msg = format!("Failed to open file: {}", err);
+                state.popup_type = PopupType::Error;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn get_error_msg(&self, output: &Output) -> anyhow::Result<String> {
         let msg = format!("{}", String::from_utf8_lossy(&output.stderr));
         Ok(msg)
@@ -231,7 +1470,7 @@ mod test {
 
     #[test]
     fn get_error_msg_test() {
-        let (state_store, _state_rx) = StateStore::new();
+        let (state_store, _state_rx) = StateStore::new(Settings::defaults(), StartOptions::default());
         let output = Command::new("/bin/cat")
             .arg("file.txt")
             .output()
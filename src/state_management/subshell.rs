@@ -0,0 +1,188 @@
+use std::{
+    env, fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::vfs;
+
+/// Prints working directory as OSC 7 sequence, it is parsed back to keep panel in sync with shell
+const PROMPT_HOOK: &str = r#"__fir_prompt() { printf '\033]7;file://%s%s\007' "${HOSTNAME:-localhost}" "$PWD"; }"#;
+
+/// Long living user's shell running in pseudo terminal. Commands from command line are
+/// written to it, so `cd` and environment changes persist between commands.
+pub struct Subshell {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    /// Directory shell reported with last prompt
+    cwd: PathBuf,
+    /// Set when command is sent, cleared when prompt is printed again
+    busy: bool,
+    /// Private directory with startup file of the shell
+    rc_dir: Option<PathBuf>,
+}
+
+impl Subshell {
+    /// Starts shell in `cwd`. Everything shell prints is sent to `output_tx`.
+    pub fn spawn(cwd: &Path, output_tx: UnboundedSender<String>) -> anyhow::Result<Self> {
+        let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+        let pair = native_pty_system().openpty(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+
+        let shell = env::var("SHELL").unwrap_or_else(|_| String::from("sh"));
+        let mut command = CommandBuilder::new(&shell);
+        command.cwd(cwd);
+        command.env("FIR_SUBSHELL", "1");
+        let rc_dir = install_prompt_hook(&shell, &mut command)?;
+
+        let child = match pair.slave.spawn_command(command) {
+            Ok(child) => child,
+            Err(err) => {
+                if let Some(dir) = rc_dir {
+                    let _ = fs::remove_dir_all(dir);
+                }
+                return Err(err);
+            }
+        };
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            // bytes of utf-8 character split between reads
+            let mut pending: Vec<u8> = vec![];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                pending.extend_from_slice(&buf[..n]);
+                let valid = match std::str::from_utf8(&pending) {
+                    Ok(text) => text.len(),
+                    Err(err) if err.error_len().is_none() => err.valid_up_to(),
+                    Err(_) => pending.len(),
+                };
+                let text = String::from_utf8_lossy(&pending[..valid]).to_string();
+                pending.drain(..valid);
+                if output_tx.send(text).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Subshell {
+            master: pair.master,
+            writer,
+            child,
+            cwd: cwd.to_path_buf(),
+            // shell is busy until it prints first prompt
+            busy: true,
+            rc_dir,
+        })
+    }
+
+    pub fn alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    pub fn busy(&self) -> bool {
+        self.busy
+    }
+
+    /// Runs command in shell, changing shell directory to `cwd` first if needed
+    pub fn run(&mut self, command: &str, cwd: &Path) -> anyhow::Result<()> {
+        if self.cwd != cwd {
+            let dir = cwd.display().to_string().replace('\'', "'\\''");
+            // leading space keeps it out of shell history
+            write!(self.writer, " cd -- '{}' && ", dir)?;
+        }
+        write!(self.writer, "{}\r", command)?;
+        self.writer.flush()?;
+        self.busy = true;
+        Ok(())
+    }
+
+    /// Sends raw input to the shell, e.g. keys typed while command is running
+    pub fn input(&mut self, input: &str) -> anyhow::Result<()> {
+        self.writer.write_all(input.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Handles OSC sequence printed by the shell. Returns new working directory
+    /// when prompt was printed.
+    pub fn handle_osc(&mut self, payload: &str) -> Option<PathBuf> {
+        let url = payload.strip_prefix("7;file://")?;
+        let path = PathBuf::from(&url[url.find('/')?..]);
+        self.busy = false;
+        self.cwd = path.clone();
+        Some(path)
+    }
+
+    pub fn resize(&self, cols: u16, rows: u16) {
+        let _ = self.master.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        });
+    }
+}
+
+impl Drop for Subshell {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        if let Some(dir) = self.rc_dir.take() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Makes shell report its directory after every command. Returns directory made
+/// for startup file of the shell, which only the user can access.
+fn install_prompt_hook(shell: &str, command: &mut CommandBuilder) -> anyhow::Result<Option<PathBuf>> {
+    let shell_name = Path::new(shell)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if shell_name != "bash" && shell_name != "zsh" {
+        // POSIX shells expand parameters in prompt
+        command.env("PS1", "\x1b]7;file://localhost$PWD\x07$ ");
+        return Ok(None);
+    }
+    let parent = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(env::temp_dir);
+    let dir = vfs::private_dir(&parent, "fir-subshell")?;
+    if shell_name == "bash" {
+        let rc_file = dir.join("bashrc");
+        fs::write(
+            &rc_file,
+            format!(
+                "[ -f ~/.bashrc ] && . ~/.bashrc\n{}\nPROMPT_COMMAND=\"__fir_prompt${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}\"\n",
+                PROMPT_HOOK
+            ),
+        )?;
+        command.arg("--rcfile");
+        command.arg(rc_file);
+    } else {
+        fs::write(
+            dir.join(".zshrc"),
+            format!(
+                "[ -f \"$HOME/.zshrc\" ] && . \"$HOME/.zshrc\"\n{}\nprecmd_functions+=(__fir_prompt)\n",
+                PROMPT_HOOK.replace("HOSTNAME", "HOST")
+            ),
+        )?;
+        command.env("ZDOTDIR", &dir);
+    }
+    Ok(Some(dir))
+}
@@ -0,0 +1,114 @@
+use std::{collections::VecDeque, sync::Arc};
+
+const MAX_LINES: usize = 10000;
+const TAB_WIDTH: usize = 8;
+
+/// Scrollback of commands run from fir. Output is fed in chunks as it arrives,
+/// escape sequences are dropped, carriage return rewrites current line.
+#[derive(Debug, Clone, Default)]
+pub struct TerminalOutput {
+    /// Finished lines, shared with state copies and UI instead of cloned
+    lines: Arc<VecDeque<String>>,
+    current: String,
+    /// Escape sequence which is not finished yet
+    escape: Option<String>,
+    carriage_return: bool,
+}
+
+impl TerminalOutput {
+    /// Appends output chunk. Returns payloads of OSC sequences found in chunk.
+    pub fn push(&mut self, text: &str) -> Vec<String> {
+        let mut osc = vec![];
+        for c in text.chars() {
+            if let Some(mut escape) = self.escape.take() {
+                escape.push(c);
+                if escape_finished(&escape) {
+                    if let Some(payload) = osc_payload(&escape) {
+                        osc.push(payload);
+                    }
+                } else {
+                    self.escape = Some(escape);
+                }
+                continue;
+            }
+            if self.carriage_return && c != '\n' {
+                self.current.clear();
+            }
+            self.carriage_return = false;
+            match c {
+                '\x1b' => self.escape = Some(String::from(c)),
+                '\n' => self.new_line(),
+                '\r' => self.carriage_return = true,
+                '\x08' => {
+                    self.current.pop();
+                }
+                '\t' => {
+                    let spaces = TAB_WIDTH - self.current.chars().count() % TAB_WIDTH;
+                    self.current.push_str(&" ".repeat(spaces));
+                }
+                c if c.is_control() => {}
+                c => self.current.push(c),
+            }
+        }
+        osc
+    }
+
+    /// Finishes current line if it has any text
+    pub fn end_line(&mut self) {
+        if !self.current.is_empty() {
+            self.new_line();
+        }
+    }
+
+    pub fn lines(&self) -> Arc<VecDeque<String>> {
+        self.lines.clone()
+    }
+
+    /// Line being written, not finished with line break yet
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    fn new_line(&mut self) {
+        let lines = Arc::make_mut(&mut self.lines);
+        lines.push_back(std::mem::take(&mut self.current));
+        if lines.len() > MAX_LINES {
+            lines.pop_front();
+        }
+    }
+}
+
+fn escape_finished(escape: &str) -> bool {
+    let mut chars = escape.chars().skip(1);
+    match chars.next() {
+        None => false,
+        // CSI ends with byte from @ to ~
+        Some('[') => escape.len() > 2 && escape.ends_with(|c: char| ('@'..='~').contains(&c)),
+        // OSC ends with BEL or ST
+        Some(']') => escape.ends_with('\x07') || escape.ends_with("\x1b\\"),
+        Some(_) => true,
+    }
+}
+
+fn osc_payload(escape: &str) -> Option<String> {
+    let payload = escape.strip_prefix("\x1b]")?;
+    let payload = payload
+        .strip_suffix('\x07')
+        .or_else(|| payload.strip_suffix("\x1b\\"))?;
+    Some(String::from(payload))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_test() {
+        let mut output = TerminalOutput::default();
+        output.push("\x1b[1;31mred\x1b[0m text\r\nprogress 10%\rprogress 1");
+        let osc = output.push("00%\n\x1b]7;file://host/tmp\x07\tx");
+        assert_eq!(osc, vec!["7;file://host/tmp"]);
+        assert_eq!(*output.lines(), ["red text", "progress 100%"]);
+        assert_eq!(output.current(), "        x");
+    }
+}
@@ -0,0 +1,33 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Directory of a test in temp dir, removed with its content when dropped, so
+/// failed tests don't leave it behind
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    /// Empty directory named after the test and process, its path is canonical
+    pub fn new(name: &str) -> TestDir {
+        let path = env::temp_dir().join(format!("fir-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TestDir {
+            path: path.canonicalize().unwrap(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
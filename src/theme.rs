@@ -0,0 +1,343 @@
+use std::{env, fs};
+
+use anyhow::Context;
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+
+use crate::config::config_dir;
+
+pub const DEFAULT_THEME: &str = "dark";
+
+const BUNDLED_THEMES: &[(&str, &str)] = &[
+    ("dark", include_str!("../themes/dark.toml")),
+    ("far", include_str!("../themes/far.toml")),
+    ("mc", include_str!("../themes/mc.toml")),
+    ("light", include_str!("../themes/light.toml")),
+];
+
+/// Standard terminal palette, used to find nearest color on 16 color terminals
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (128, 0, 0)),
+    (Color::Green, (0, 128, 0)),
+    (Color::Yellow, (128, 128, 0)),
+    (Color::Blue, (0, 0, 128)),
+    (Color::Magenta, (128, 0, 128)),
+    (Color::Cyan, (0, 128, 128)),
+    (Color::Gray, (192, 192, 192)),
+    (Color::DarkGray, (128, 128, 128)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (0, 0, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Channel values of 6x6x6 color cube of 256 color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Colors terminal can show. Theme colors are reduced to nearest supported ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ColorDepth {
+    /// Guessed from `COLORTERM` and `TERM`
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "16")]
+    Ansi16,
+    #[serde(rename = "256")]
+    Indexed,
+    #[serde(rename = "truecolor")]
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub fn resolve(self) -> ColorDepth {
+        if self != ColorDepth::Auto {
+            return self;
+        }
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Indexed
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+/// Style of one theme role, e.g. `{ fg = "white", bg = "#1e1e1e", bold = true }`.
+/// Colors are names, palette indexes or `#rrggbb`, missing color keeps terminal default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleSpec {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reversed: bool,
+}
+
+impl StyleSpec {
+    pub fn style(&self) -> Style {
+        let mut style = Style::new();
+        if let Some(fg) = self.fg {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = self.bg {
+            style = style.bg(bg);
+        }
+        for (enabled, modifier) in [
+            (self.bold, Modifier::BOLD),
+            (self.italic, Modifier::ITALIC),
+            (self.underline, Modifier::UNDERLINED),
+            (self.reversed, Modifier::REVERSED),
+        ] {
+            if enabled {
+                style = style.add_modifier(modifier);
+            }
+        }
+        style
+    }
+
+    pub fn with_depth(self, depth: ColorDepth) -> StyleSpec {
+        StyleSpec {
+            fg: self.fg.map(|color| reduce_color(color, depth)),
+            bg: self.bg.map(|color| reduce_color(color, depth)),
+            ..self
+        }
+    }
+}
+
+/// Styles of UI parts. Bundled themes live in `themes/`, user themes are read from
+/// `themes/<name>.toml` in config directory. User theme may set `base = "<theme>"`
+/// to take roles it does not define from another theme.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    pub panel: StyleSpec,
+    pub panel_title: StyleSpec,
+    pub panel_title_active: StyleSpec,
+    pub directory: StyleSpec,
+    pub file: StyleSpec,
+    pub marked: StyleSpec,
+    pub cursor: StyleSpec,
+    pub command_line: StyleSpec,
+    pub help_key: StyleSpec,
+    pub help_label: StyleSpec,
+    pub popup: StyleSpec,
+    pub button: StyleSpec,
+    pub button_active: StyleSpec,
+    pub input: StyleSpec,
+    pub input_cursor: StyleSpec,
+    pub menu_cursor: StyleSpec,
+    pub editor: StyleSpec,
+    pub editor_selection: StyleSpec,
+    pub output: StyleSpec,
+    pub dialog: StyleSpec,
+    pub dialog_cursor: StyleSpec,
+    pub dialog_input: StyleSpec,
+    pub dialog_message: StyleSpec,
+}
+
+impl Default for Theme {
+    /// Bundled default theme. User's theme files are left out, so a broken one is
+    /// only reported by settings validation.
+    fn default() -> Self {
+        let theme: Theme = theme_table(DEFAULT_THEME, false, 0)
+            .and_then(|table| Ok(table.try_into()?))
+            .expect("Bundled theme should be valid");
+        theme.with_depth(ColorDepth::Auto.resolve())
+    }
+}
+
+impl Theme {
+    pub fn load(name: &str, depth: ColorDepth) -> anyhow::Result<Theme> {
+        let table = theme_table(name, true, 0)?;
+        let theme: Theme = table
+            .try_into()
+            .with_context(|| format!("Invalid theme {}", name))?;
+        Ok(theme.with_depth(depth.resolve()))
+    }
+
+    fn with_depth(self, depth: ColorDepth) -> Theme {
+        Theme {
+            panel: self.panel.with_depth(depth),
+            panel_title: self.panel_title.with_depth(depth),
+            panel_title_active: self.panel_title_active.with_depth(depth),
+            directory: self.directory.with_depth(depth),
+            file: self.file.with_depth(depth),
+            marked: self.marked.with_depth(depth),
+            cursor: self.cursor.with_depth(depth),
+            command_line: self.command_line.with_depth(depth),
+            help_key: self.help_key.with_depth(depth),
+            help_label: self.help_label.with_depth(depth),
+            popup: self.popup.with_depth(depth),
+            button: self.button.with_depth(depth),
+            button_active: self.button_active.with_depth(depth),
+            input: self.input.with_depth(depth),
+            input_cursor: self.input_cursor.with_depth(depth),
+            menu_cursor: self.menu_cursor.with_depth(depth),
+            editor: self.editor.with_depth(depth),
+            editor_selection: self.editor_selection.with_depth(depth),
+            output: self.output.with_depth(depth),
+            dialog: self.dialog.with_depth(depth),
+            dialog_cursor: self.dialog_cursor.with_depth(depth),
+            dialog_input: self.dialog_input.with_depth(depth),
+            dialog_message: self.dialog_message.with_depth(depth),
+        }
+    }
+}
+
+/// Names of bundled themes and themes found in config directory
+pub fn theme_names() -> Vec<String> {
+    let mut names: Vec<String> = BUNDLED_THEMES
+        .iter()
+        .map(|(name, _)| String::from(*name))
+        .collect();
+    let user_themes = config_dir()
+        .and_then(|dir| fs::read_dir(dir.join("themes")).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "toml" {
+                return None;
+            }
+            Some(path.file_stem()?.to_string_lossy().to_string())
+        });
+    for name in user_themes {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Theme roles with roles of base themes filled in. User theme can shadow bundled
+/// one with the same name and still use it as base.
+fn theme_table(name: &str, with_user: bool, level: usize) -> anyhow::Result<toml::Table> {
+    if level > 8 {
+        anyhow::bail!("Theme {} has too many base themes", name);
+    }
+    let user_path = config_dir()
+        .map(|dir| dir.join("themes").join(format!("{}.toml", name)))
+        .filter(|path| with_user && path.exists());
+    let content = match user_path {
+        Some(path) => fs::read_to_string(&path)
+            .with_context(|| format!("Can't read theme {}", path.display()))?,
+        None => BUNDLED_THEMES
+            .iter()
+            .find(|(bundled, _)| *bundled == name)
+            .map(|(_, content)| String::from(*content))
+            .with_context(|| {
+                format!("Unknown theme {}, available: {}", name, theme_names().join(", "))
+            })?,
+    };
+    let mut table: toml::Table = content
+        .parse()
+        .with_context(|| format!("Invalid theme {}", name))?;
+    match table.remove("base") {
+        None => Ok(table),
+        Some(toml::Value::String(base)) => {
+            // theme may extend bundled theme of the same name
+            let mut merged = theme_table(&base, with_user && base != name, level + 1)?;
+            merged.extend(table);
+            Ok(merged)
+        }
+        Some(_) => anyhow::bail!("base of theme {} should be theme name", name),
+    }
+}
+
+fn reduce_color(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (Color::Rgb(r, g, b), ColorDepth::Indexed) => Color::Indexed(nearest_indexed((r, g, b))),
+        (Color::Rgb(r, g, b), ColorDepth::Ansi16) => nearest_ansi((r, g, b)),
+        (Color::Indexed(index), ColorDepth::Ansi16) if index < 16 => ANSI_COLORS[index as usize].0,
+        (Color::Indexed(index), ColorDepth::Ansi16) => nearest_ansi(indexed_rgb(index)),
+        (color, _) => color,
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn nearest_ansi(rgb: (u8, u8, u8)) -> Color {
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// Nearest color of 256 color palette, from color cube or grayscale ramp
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    let level = |v: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| (**level as i32 - v as i32).abs())
+            .map(|(i, _)| i as u8)
+            .unwrap_or(0)
+    };
+    let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + ((average.saturating_sub(8) / 10).min(23)) as u8;
+    if distance(rgb, indexed_rgb(gray)) < distance(rgb, indexed_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_COLORS[index as usize].1,
+        16..=231 => {
+            let n = index - 16;
+            (
+                CUBE_LEVELS[(n / 36) as usize],
+                CUBE_LEVELS[(n / 6 % 6) as usize],
+                CUBE_LEVELS[(n % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bundled_themes_test() {
+        for (name, _) in BUNDLED_THEMES {
+            for depth in [ColorDepth::Ansi16, ColorDepth::Indexed, ColorDepth::TrueColor] {
+                assert!(Theme::load(name, depth).is_ok(), "{} should load", name);
+            }
+        }
+        assert!(Theme::load("unknown", ColorDepth::Ansi16).is_err());
+    }
+
+    #[test]
+    fn reduce_color_test() {
+        let white = Color::Rgb(0xf5, 0xf5, 0xf0);
+        assert_eq!(reduce_color(white, ColorDepth::TrueColor), white);
+        assert_eq!(reduce_color(white, ColorDepth::Ansi16), Color::White);
+        assert_eq!(reduce_color(Color::Rgb(255, 0, 0), ColorDepth::Indexed), Color::Indexed(196));
+        assert_eq!(reduce_color(Color::Rgb(128, 128, 128), ColorDepth::Indexed), Color::Indexed(244));
+        assert_eq!(reduce_color(Color::Indexed(4), ColorDepth::Ansi16), Color::Blue);
+        assert_eq!(reduce_color(Color::Indexed(21), ColorDepth::Ansi16), Color::LightBlue);
+        assert_eq!(reduce_color(Color::Cyan, ColorDepth::Ansi16), Color::Cyan);
+    }
+}
@@ -12,10 +12,14 @@ pub trait Component {
     where
         Self: Sized;
 
+    #[allow(dead_code)]
     fn name(&self) -> &str;
 
     fn handle_key_event(&mut self, key: KeyEvent);
 
+    /// Text pasted into terminal (bracketed paste)
+    fn handle_paste(&mut self, _text: String) {}
+
     fn check(&mut self);
 }
 
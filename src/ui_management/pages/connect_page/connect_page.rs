@@ -0,0 +1,230 @@
+use std::sync::Arc;
+
+use crossterm::event::KeyEventKind;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, BorderType, List, ListItem, ListState, Paragraph, StatefulWidget},
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tui_textarea::TextArea;
+
+use crate::{
+    connection::PASSWORD_FIELD,
+    connections::NAME_FIELD,
+    keymap::{Command, KeyContext, Keymap, PendingKeys, Resolved},
+    state_management::{action::Action, State},
+    theme::Theme,
+    ui_management::components::{Component, ComponentRender},
+};
+
+const MASK: char = '*';
+
+struct Props {
+    /// Field name and its current value
+    fields: Vec<(String, String)>,
+    /// Saved connection is edited, its name is the first field
+    profile: bool,
+    message: String,
+    keymap: Arc<Keymap>,
+    theme: Arc<Theme>,
+}
+
+impl From<&State> for Props {
+    fn from(value: &State) -> Self {
+        let name = value
+            .profile_edit
+            .as_ref()
+            .map(|edit| (String::from(NAME_FIELD), edit.name.clone()));
+        Props {
+            fields: name.into_iter().chain(value.connection.fields()).collect(),
+            profile: value.profile_edit.is_some(),
+            message: value.connect_message.clone(),
+            keymap: value.keymap.clone(),
+            theme: value.theme.clone(),
+        }
+    }
+}
+
+/// Dialog of remote server the active panel connects to. Fields are edited in place,
+/// the last item connects, or saves the connection when one from network panel is edited.
+pub struct ConnectPage<'a> {
+    props: Props,
+    /// Fields and connect button after them
+    index: usize,
+    /// Value being edited
+    input: Option<TextArea<'a>>,
+    pending_keys: PendingKeys,
+    pub action_tx: UnboundedSender<Action>,
+}
+
+impl<'a> ConnectPage<'a> {
+    fn start_edit(&mut self) {
+        let Some((name, value)) = self.props.fields.get(self.index) else {
+            let _ = self.action_tx.send(match self.props.profile {
+                true => Action::SaveProfile,
+                false => Action::Connect(None),
+            });
+            return;
+        };
+        let mut input = TextArea::new(vec![value.clone()]);
+        input.set_style(self.props.theme.dialog_input.style());
+        input.set_cursor_line_style(Style::default());
+        if name == PASSWORD_FIELD {
+            input.set_mask_char(MASK);
+        }
+        input.move_cursor(tui_textarea::CursorMove::End);
+        self.input = Some(input);
+    }
+
+    fn finish_edit(&mut self) {
+        let Some(input) = self.input.take() else {
+            return;
+        };
+        if let Some((name, _)) = self.props.fields.get(self.index) {
+            let value = input.lines()[0].trim().to_string();
+            let _ = self
+                .action_tx
+                .send(Action::SetConnectField(name.clone(), value));
+        }
+    }
+}
+
+impl<'a> Component for ConnectPage<'a> {
+    fn new(state: &State, action_tx: UnboundedSender<Action>) -> Self
+    where
+        Self: Sized,
+    {
+        ConnectPage {
+            props: Props::from(state),
+            index: 0,
+            input: None,
+            pending_keys: PendingKeys::default(),
+            action_tx,
+        }
+    }
+
+    fn move_with_state(self, state: &State) -> Self
+    where
+        Self: Sized,
+    {
+        let props = Props::from(state);
+        ConnectPage {
+            index: self.index.min(props.fields.len()),
+            props,
+            ..self
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Connect"
+    }
+
+    fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let keymap = self.props.keymap.clone();
+        let resolved = self.pending_keys.resolve(&keymap, KeyContext::Popup, key);
+        if let Some(input) = &mut self.input {
+            match resolved {
+                Resolved::Command(Command::Confirm) => self.finish_edit(),
+                Resolved::Command(Command::Cancel) => self.input = None,
+                Resolved::Unbound | Resolved::Command(Command::FocusPrev | Command::FocusNext) => {
+                    input.input(key);
+                }
+                _ => {}
+            }
+            return;
+        }
+        let Resolved::Command(command) = resolved else {
+            return;
+        };
+        match command {
+            Command::CursorUp => self.index = self.index.saturating_sub(1),
+            Command::CursorDown if self.index < self.props.fields.len() => self.index += 1,
+            Command::PageUp | Command::Home => self.index = 0,
+            Command::PageDown | Command::End => self.index = self.props.fields.len(),
+            Command::Confirm => self.start_edit(),
+            Command::Cancel => {
+                let _ = self.action_tx.send(Action::CloseConnect);
+            }
+            _ => {}
+        }
+    }
+
+    fn check(&mut self) {}
+}
+
+pub struct RenderProps {
+    pub area: Rect,
+}
+
+impl<'a> ComponentRender<RenderProps> for ConnectPage<'a> {
+    fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
+        let [list_rec, message_rec] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(props.area)
+        else {
+            panic!("Connect layout should have 2 chunks")
+        };
+        let name_width = self
+            .props
+            .fields
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut items: Vec<ListItem> = self
+            .props
+            .fields
+            .iter()
+            .map(|(name, value)| {
+                let value = match name.as_str() {
+                    PASSWORD_FIELD => value.chars().map(|_| MASK).collect(),
+                    _ => value.clone(),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!(" {:width$}  ", name, width = name_width)),
+                    Span::raw(value),
+                ]))
+            })
+            .collect();
+        let (button, title) = match self.props.profile {
+            true => (" [ Save ]", "Saved connection"),
+            false => (" [ Connect ]", "Connect to server"),
+        };
+        items.push(ListItem::new(Line::from(button)));
+        let block = Block::bordered()
+            .border_type(BorderType::Double)
+            .title(title)
+            .style(self.props.theme.dialog.style());
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(self.props.theme.dialog_cursor.style());
+        let mut state = ListState::default().with_selected(Some(self.index));
+        StatefulWidget::render(list, list_rec, frame.buffer_mut(), &mut state);
+
+        if let Some(input) = &self.input {
+            let row = 1 + self.index as u16 - state.offset() as u16;
+            let column = name_width as u16 + 4;
+            if row + 1 < list_rec.height && column + 1 < list_rec.width {
+                let input_rec = Rect {
+                    x: list_rec.x + column,
+                    y: list_rec.y + row,
+                    width: list_rec.width - column - 1,
+                    height: 1,
+                };
+                frame.render_widget(input.widget(), input_rec);
+            }
+        }
+
+        frame.render_widget(
+            Paragraph::new(self.props.message.clone())
+                .style(self.props.theme.dialog_message.style()),
+            message_rec,
+        );
+    }
+}
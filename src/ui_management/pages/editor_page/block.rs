@@ -0,0 +1,217 @@
+use tui_textarea::{CursorMove, TextArea};
+
+/// Rectangular (column) selection. One corner stays at the anchor, the opposite
+/// one follows the cursor.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockSelection {
+    anchor: (usize, usize),
+}
+
+impl BlockSelection {
+    pub fn new(anchor: (usize, usize)) -> Self {
+        BlockSelection { anchor }
+    }
+
+    /// Returns `(top, left, bottom, right)` where rows are inclusive. Columns are the
+    /// ones on screen, with tabs expanded, and `right` is exclusive.
+    pub fn bounds(&self, text_area: &TextArea) -> (usize, usize, usize, usize) {
+        let cursor = text_area.cursor();
+        let column = |(row, col): (usize, usize)| match text_area.lines().get(row) {
+            Some(line) => display_col(line, col, text_area.tab_length()),
+            None => col,
+        };
+        let (anchor_col, cursor_col) = (column(self.anchor), column(cursor));
+        let (top, bottom) = if self.anchor.0 <= cursor.0 {
+            (self.anchor.0, cursor.0)
+        } else {
+            (cursor.0, self.anchor.0)
+        };
+        let (left, right) = if anchor_col <= cursor_col {
+            (anchor_col, cursor_col)
+        } else {
+            (cursor_col, anchor_col)
+        };
+        (top, left, bottom, right)
+    }
+
+    pub fn copy(&self, text_area: &TextArea) -> Vec<String> {
+        let (top, left, bottom, right) = self.bounds(text_area);
+        let tab_width = text_area.tab_length();
+        text_area.lines()[top..=bottom]
+            .iter()
+            .map(|line| {
+                let (start, end) = char_range(line, left, right, tab_width);
+                line.chars().skip(start).take(end - start).collect()
+            })
+            .collect()
+    }
+
+    /// Removes selected columns from every selected line. Returns true when text was changed.
+    pub fn delete(&self, text_area: &mut TextArea) -> bool {
+        let (top, left, bottom, right) = self.bounds(text_area);
+        let tab_width = text_area.tab_length();
+        let mut modified = false;
+        for row in top..=bottom {
+            let (start, end) = char_range(&text_area.lines()[row], left, right, tab_width);
+            if start < end {
+                text_area.move_cursor(CursorMove::Jump(row as u16, start as u16));
+                modified |= text_area.delete_str(end - start);
+            }
+        }
+        let (start, _) = char_range(&text_area.lines()[top], left, right, tab_width);
+        text_area.move_cursor(CursorMove::Jump(top as u16, start as u16));
+        modified
+    }
+}
+
+/// Screen column character `col` of line starts at, tabs reach to the next tab stop
+fn display_col(line: &str, col: usize, tab_width: u8) -> usize {
+    line.chars().take(col).fold(0, |width, c| match c {
+        '\t' if tab_width > 0 => width + tab_width as usize - width % tab_width as usize,
+        '\t' => width,
+        _ => width + 1,
+    })
+}
+
+/// Characters of line starting within screen columns `left..right`, as `(start, end)`
+/// character indexes
+fn char_range(line: &str, left: usize, right: usize, tab_width: u8) -> (usize, usize) {
+    let len = line.chars().count();
+    let first = |column: usize| {
+        (0..len)
+            .find(|col| display_col(line, *col, tab_width) >= column)
+            .unwrap_or(len)
+    };
+    (first(left), first(right))
+}
+
+/// Inserts lines of a block one under another starting at cursor column,
+/// padding short lines with spaces and appending lines past the end of text.
+pub fn paste_block(text_area: &mut TextArea, block: &[String]) -> bool {
+    let (row, col) = text_area.cursor();
+    for (i, text) in block.iter().enumerate() {
+        let target = row + i;
+        if target >= text_area.lines().len() {
+            text_area.move_cursor(CursorMove::Bottom);
+            text_area.move_cursor(CursorMove::End);
+            text_area.insert_newline();
+        }
+        let len = text_area.lines()[target].chars().count();
+        text_area.move_cursor(CursorMove::Jump(target as u16, col as u16));
+        if len < col {
+            text_area.insert_str(" ".repeat(col - len));
+        }
+        text_area.insert_str(text);
+    }
+    text_area.move_cursor(CursorMove::Jump(row as u16, col as u16));
+    !block.is_empty()
+}
+
+/// Removes a whole line including its line break. Yank buffer is left untouched.
+pub fn remove_line(text_area: &mut TextArea, row: usize) -> bool {
+    let yank = text_area.yank_text();
+    let len = text_area.lines().len();
+    text_area.cancel_selection();
+    if len == 1 {
+        text_area.move_cursor(CursorMove::Head);
+        text_area.start_selection();
+        text_area.move_cursor(CursorMove::End);
+    } else if row + 1 < len {
+        text_area.move_cursor(CursorMove::Jump(row as u16, 0));
+        text_area.start_selection();
+        text_area.move_cursor(CursorMove::Jump(row as u16 + 1, 0));
+    } else {
+        text_area.move_cursor(CursorMove::Jump(row as u16 - 1, 0));
+        text_area.move_cursor(CursorMove::End);
+        text_area.start_selection();
+        text_area.move_cursor(CursorMove::Bottom);
+        text_area.move_cursor(CursorMove::End);
+    }
+    let modified = text_area.cut();
+    text_area.set_yank_text(yank);
+    modified
+}
+
+pub fn duplicate_line(text_area: &mut TextArea) -> bool {
+    let (row, col) = text_area.cursor();
+    let line = text_area.lines()[row].clone();
+    text_area.cancel_selection();
+    text_area.move_cursor(CursorMove::End);
+    text_area.insert_newline();
+    text_area.insert_str(line);
+    text_area.move_cursor(CursorMove::Jump(row as u16 + 1, col as u16));
+    true
+}
+
+pub fn move_line_up(text_area: &mut TextArea) -> bool {
+    let (row, col) = text_area.cursor();
+    if row == 0 {
+        return false;
+    }
+    let line = text_area.lines()[row].clone();
+    remove_line(text_area, row);
+    text_area.move_cursor(CursorMove::Jump(row as u16 - 1, 0));
+    text_area.insert_str(line);
+    text_area.insert_newline();
+    text_area.move_cursor(CursorMove::Jump(row as u16 - 1, col as u16));
+    true
+}
+
+pub fn move_line_down(text_area: &mut TextArea) -> bool {
+    let (row, col) = text_area.cursor();
+    if row + 1 >= text_area.lines().len() {
+        return false;
+    }
+    let line = text_area.lines()[row].clone();
+    remove_line(text_area, row);
+    text_area.move_cursor(CursorMove::Jump(row as u16, 0));
+    text_area.move_cursor(CursorMove::End);
+    text_area.insert_newline();
+    text_area.insert_str(line);
+    text_area.move_cursor(CursorMove::Jump(row as u16 + 1, col as u16));
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text_area(lines: &[&str]) -> TextArea<'static> {
+        TextArea::new(lines.iter().map(|l| l.to_string()).collect())
+    }
+
+    #[test]
+    fn block_copy_and_delete_test() {
+        let mut ta = text_area(&["abcdef", "ab", "abcdef"]);
+        let block = BlockSelection::new((0, 1));
+        ta.move_cursor(CursorMove::Jump(2, 4));
+        assert_eq!(block.copy(&ta), vec!["bcd", "b", "bcd"]);
+        assert!(block.delete(&mut ta));
+        assert_eq!(ta.lines(), ["aef", "a", "aef"]);
+    }
+
+    #[test]
+    fn block_with_tabs_test() {
+        let mut ta = text_area(&["\tab", "abcdefghij"]);
+        ta.set_tab_length(4);
+        assert_eq!(display_col("a\tb", 2, 4), 4);
+        let block = BlockSelection::new((1, 4));
+        ta.move_cursor(CursorMove::Jump(0, 2));
+        assert_eq!(block.bounds(&ta), (0, 4, 1, 5));
+        assert_eq!(block.copy(&ta), vec!["a", "e"]);
+        assert!(block.delete(&mut ta));
+        assert_eq!(ta.lines(), ["\tb", "abcdfghij"]);
+    }
+
+    #[test]
+    fn move_line_test() {
+        let mut ta = text_area(&["one", "two", "three"]);
+        assert!(move_line_down(&mut ta));
+        assert_eq!(ta.lines(), ["two", "one", "three"]);
+        ta.move_cursor(CursorMove::Jump(2, 0));
+        assert!(move_line_up(&mut ta));
+        assert_eq!(ta.lines(), ["two", "three", "one"]);
+        while ta.undo() {}
+        assert_eq!(ta.lines(), ["one", "two", "three"]);
+    }
+}
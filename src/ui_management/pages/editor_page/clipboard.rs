@@ -0,0 +1,11 @@
+use std::io::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Puts text to system clipboard using OSC 52 escape sequence.
+/// Terminal has to support it, otherwise sequence is silently ignored.
+pub fn set_system_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
@@ -1,21 +1,40 @@
 use std::{
+    cell::Cell,
     fs::File,
     io::{BufRead, BufReader, BufWriter, Write},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use crossterm::event::{KeyCode, KeyModifiers};
-use ratatui::{layout::Rect, style::{Color, Style}};
+use ratatui::layout::Rect;
 use tokio::sync::mpsc::UnboundedSender;
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 
 use crate::{
+    keymap::{Command, KeyContext, Keymap, PendingKeys, Resolved},
     state_management::{action::Action, State},
+    theme::Theme,
     ui_management::components::{Component, ComponentRender},
 };
 
+use super::{
+    block::{self, BlockSelection},
+    clipboard::set_system_clipboard,
+};
+
+/// How often unsaved text is sent to be kept in session
+const TEXT_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
 struct Props {
     modified: bool,
     editor_file: String,
+    /// Unsaved text of previous session
+    editor_text: Option<Arc<String>>,
+    tab_width: u8,
+    hard_tabs: bool,
+    keymap: Arc<Keymap>,
+    theme: Arc<Theme>,
 }
 
 impl From<&State> for Props {
@@ -28,6 +47,11 @@ impl From<&State> for Props {
         Props {
             editor_file,
             modified: value.editor_modified,
+            editor_text: value.editor_text.clone(),
+            tab_width: value.settings.editor.tab_width,
+            hard_tabs: value.settings.editor.hard_tabs,
+            keymap: value.keymap.clone(),
+            theme: value.theme.clone(),
         }
     }
 }
@@ -35,9 +59,170 @@ impl From<&State> for Props {
 pub struct EditorPage<'a> {
     props: Props,
     text_area: Option<TextArea<'a>>,
+    /// Active rectangular selection
+    block: Option<BlockSelection>,
+    /// Last copied rectangular block. Pasted as block instead of text area yank.
+    block_clipboard: Option<Vec<String>>,
+    /// Mirrors scroll position of text area, so block selection can be drawn over it
+    scroll_top: Cell<(u16, u16)>,
+    pending_keys: PendingKeys,
+    /// Text was modified since it was last sent
+    text_changed: bool,
+    text_sent: Instant,
     pub action_tx: UnboundedSender<Action>,
 }
 
+impl<'a> EditorPage<'a> {
+    fn save(&mut self) {
+        if let Some(text_area) = &self.text_area {
+            let mut f = BufWriter::new(
+                File::create(&self.props.editor_file).expect("Cannot open file"),
+            );
+            for line in text_area.lines() {
+                f.write_all(line.as_bytes())
+                    .expect("Not able to write linee");
+                f.write_all(b"\n").expect("Not able to write endline");
+            }
+            self.text_changed = false;
+            let _ = self.action_tx.send(Action::EditorResedModified);
+        }
+    }
+
+    fn copy(&mut self) {
+        let Some(text_area) = self.text_area.as_mut() else {
+            return;
+        };
+        let text = if let Some(block) = self.block {
+            let lines = block.copy(text_area);
+            let text = lines.join("\n");
+            self.block_clipboard = Some(lines);
+            text
+        } else {
+            text_area.copy();
+            self.block_clipboard = None;
+            text_area.yank_text()
+        };
+        let _ = set_system_clipboard(&text);
+    }
+
+    fn cut(&mut self) -> bool {
+        self.copy();
+        let Some(text_area) = self.text_area.as_mut() else {
+            return false;
+        };
+        match self.block.take() {
+            Some(block) => block.delete(text_area),
+            None => text_area.cut(),
+        }
+    }
+
+    fn paste(&mut self) -> bool {
+        let Some(text_area) = self.text_area.as_mut() else {
+            return false;
+        };
+        match &self.block_clipboard {
+            Some(lines) => block::paste_block(text_area, lines),
+            None => text_area.paste(),
+        }
+    }
+
+    fn extend_block(&mut self, cursor_move: CursorMove) {
+        if let Some(text_area) = self.text_area.as_mut() {
+            text_area.cancel_selection();
+            if self.block.is_none() {
+                self.block = Some(BlockSelection::new(text_area.cursor()));
+            }
+            text_area.move_cursor(cursor_move);
+        }
+    }
+
+    fn extend_selection(&mut self, cursor_move: CursorMove) {
+        self.block = None;
+        if let Some(text_area) = self.text_area.as_mut() {
+            if !text_area.is_selecting() {
+                text_area.start_selection();
+            }
+            text_area.move_cursor(cursor_move);
+        }
+    }
+
+    /// Runs bound command, returns whether text was modified
+    fn handle_command(&mut self, command: Command) -> bool {
+        match command {
+            Command::Close => {
+                self.text_area = None;
+                let _ = self.action_tx.send(Action::EditorExit);
+                return false;
+            }
+            Command::Save => {
+                if self.props.modified {
+                    self.save();
+                }
+                return false;
+            }
+            Command::Copy => {
+                self.copy();
+                return false;
+            }
+            Command::Cut => return self.cut(),
+            Command::Paste => return self.paste(),
+            _ => {}
+        }
+        let Some(text_area) = self.text_area.as_mut() else {
+            return false;
+        };
+        match command {
+            Command::Undo => text_area.undo(),
+            Command::Redo => text_area.redo(),
+            Command::DuplicateLine => block::duplicate_line(text_area),
+            Command::DeleteLine => {
+                let (row, _) = text_area.cursor();
+                block::remove_line(text_area, row)
+            }
+            Command::MoveLineUp => block::move_line_up(text_area),
+            Command::MoveLineDown => block::move_line_down(text_area),
+            _ => false,
+        }
+    }
+
+    /// Handles selection keys. Returns None if key should be passed to text area,
+    /// otherwise whether text was modified.
+    fn handle_selection(&mut self, key: crossterm::event::KeyEvent) -> Option<bool> {
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let cursor_move = match key.code {
+            KeyCode::Up => Some(CursorMove::Up),
+            KeyCode::Down => Some(CursorMove::Down),
+            KeyCode::Left => Some(CursorMove::Back),
+            KeyCode::Right => Some(CursorMove::Forward),
+            KeyCode::Home => Some(CursorMove::Head),
+            KeyCode::End => Some(CursorMove::End),
+            _ => None,
+        };
+        if let Some(cursor_move) = cursor_move {
+            if alt && shift {
+                self.extend_block(cursor_move);
+                return Some(false);
+            }
+            if shift {
+                self.extend_selection(cursor_move);
+                return Some(false);
+            }
+        }
+
+        let text_area = self.text_area.as_mut()?;
+        if key.code == KeyCode::Delete && self.block.is_some() {
+            let block = self.block.take()?;
+            return Some(block.delete(text_area));
+        }
+        if cursor_move.is_some() {
+            text_area.cancel_selection();
+        }
+        self.block = None;
+        None
+    }
+}
+
 impl<'a> Component for EditorPage<'a> {
     fn new(
         state: &crate::state_management::State,
@@ -49,6 +234,12 @@ impl<'a> Component for EditorPage<'a> {
         let props = Props::from(state);
         EditorPage {
             text_area: None,
+            block: None,
+            block_clipboard: None,
+            scroll_top: Cell::new((0, 0)),
+            pending_keys: PendingKeys::default(),
+            text_changed: false,
+            text_sent: Instant::now(),
             action_tx: action_tx.clone(),
             props,
         }
@@ -61,22 +252,36 @@ impl<'a> Component for EditorPage<'a> {
     {
         let props = Props::from(state);
         if !props.editor_file.is_empty() && self.text_area.is_none() {
-            let mut text_area: TextArea =
-                BufReader::new(File::open(props.editor_file.clone()).expect("Can't read file"))
+            let mut text_area: TextArea = match &props.editor_text {
+                Some(text) => text.lines().collect(),
+                None => BufReader::new(File::open(props.editor_file.clone()).expect("Can't read file"))
                     .lines()
                     .collect::<Result<_, _>>()
-                    .expect("Error?");
-            text_area.set_style(Style::default().bg(Color::Blue).fg(Color::White));
-            if text_area.lines().iter().any(|l| l.starts_with('\t')) {
+                    .expect("Error?"),
+            };
+            text_area.set_style(props.theme.editor.style());
+            text_area.set_tab_length(props.tab_width);
+            if props.hard_tabs || text_area.lines().iter().any(|l| l.starts_with('\t')) {
                 text_area.set_hard_tab_indent(true);
             };
             return EditorPage {
                 props,
                 text_area: Some(text_area),
+                block: None,
+                scroll_top: Cell::new((0, 0)),
+                text_changed: false,
                 ..self
             };
         };
-        EditorPage { props, ..self }
+        let mut text_area = self.text_area;
+        if let Some(text_area) = text_area.as_mut() {
+            text_area.set_style(props.theme.editor.style());
+        }
+        EditorPage {
+            props,
+            text_area,
+            ..self
+        }
     }
 
     fn name(&self) -> &str {
@@ -84,39 +289,45 @@ impl<'a> Component for EditorPage<'a> {
     }
 
     fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) {
-        match key.code {
-            KeyCode::Esc => {
-                self.text_area = None;
-                let _ = self.action_tx.send(Action::EditorExit);
-            }
-            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                if self.props.modified {
-                    if let Some(text_area) = &self.text_area {
-                        let mut f = BufWriter::new(
-                            File::create(&self.props.editor_file).expect("Cannot open file"),
-                        );
-                        for line in text_area.lines() {
-                            f.write_all(line.as_bytes())
-                                .expect("Not able to write linee");
-                            f.write_all(b"\n").expect("Not able to write endline");
-                        }
-                        let _ = self.action_tx.send(Action::EditorResedModified);
-                    }
-                }
-            }
-            _ => {
-                if let Some(text_area) = self.text_area.as_mut() {
-                    let modified = text_area.input(key);
-                    if modified {
-                        let _ = self.action_tx.send(Action::EditorModified);
-                    }
-                }
+        let keymap = self.props.keymap.clone();
+        let modified = match self.pending_keys.resolve(&keymap, KeyContext::Editor, key) {
+            Resolved::Command(command) => self.handle_command(command),
+            Resolved::Pending | Resolved::Ignored => false,
+            Resolved::Unbound => match self.handle_selection(key) {
+                Some(modified) => modified,
+                None => self
+                    .text_area
+                    .as_mut()
+                    .map(|text_area| text_area.input(key))
+                    .unwrap_or(false),
+            },
+        };
+        if modified {
+            self.text_changed = true;
+            let _ = self.action_tx.send(Action::EditorModified);
+        }
+    }
+
+    fn handle_paste(&mut self, text: String) {
+        if let Some(text_area) = self.text_area.as_mut() {
+            self.block = None;
+            if text_area.insert_str(text.replace("\r\n", "\n").replace('\r', "\n")) {
+                self.text_changed = true;
+                let _ = self.action_tx.send(Action::EditorModified);
             }
         }
     }
-    
+
     fn check(&mut self) {
-        
+        let Some(text_area) = &self.text_area else {
+            return;
+        };
+        if self.text_changed && self.text_sent.elapsed() >= TEXT_SYNC_INTERVAL {
+            let text = Arc::new(text_area.lines().join("\n"));
+            let _ = self.action_tx.send(Action::EditorText(text));
+            self.text_changed = false;
+            self.text_sent = Instant::now();
+        }
     }
 }
 
@@ -127,7 +338,44 @@ pub struct RenderProps {
 impl<'a> ComponentRender<RenderProps> for EditorPage<'a> {
     fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
         if let Some(text_area) = &self.text_area {
-            frame.render_widget(text_area.widget(), props.area)
+            frame.render_widget(text_area.widget(), props.area);
+
+            // Same scrolling rule as text area uses to keep cursor visible
+            fn next_scroll_top(prev_top: u16, cursor: u16, length: u16) -> u16 {
+                if cursor < prev_top {
+                    cursor
+                } else if prev_top + length <= cursor {
+                    cursor + 1 - length
+                } else {
+                    prev_top
+                }
+            }
+            let area = props.area;
+            let cursor = text_area.cursor();
+            let (prev_row, prev_col) = self.scroll_top.get();
+            let top_row = next_scroll_top(prev_row, cursor.0 as u16, area.height);
+            let top_col = next_scroll_top(prev_col, cursor.1 as u16, area.width);
+            self.scroll_top.set((top_row, top_col));
+
+            if let Some(block) = self.block {
+                let (top, left, bottom, right) = block.bounds(text_area);
+                let style = self.props.theme.editor_selection.style();
+                let buf = frame.buffer_mut();
+                for row in top.max(top_row as usize)..=bottom {
+                    let y = row - top_row as usize;
+                    if y >= area.height as usize {
+                        break;
+                    }
+                    for col in left.max(top_col as usize)..right {
+                        let x = col - top_col as usize;
+                        if x >= area.width as usize {
+                            break;
+                        }
+                        buf.get_mut(area.x + x as u16, area.y + y as u16)
+                            .set_style(style);
+                    }
+                }
+            }
         }
     }
 }
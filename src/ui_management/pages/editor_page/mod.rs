@@ -1,3 +1,6 @@
+#[allow(clippy::module_inception)]
 mod editor_page;
+mod block;
+mod clipboard;
 
 pub use editor_page::{EditorPage, RenderProps};
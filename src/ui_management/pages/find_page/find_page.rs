@@ -0,0 +1,277 @@
+use std::sync::Arc;
+
+use crossterm::event::KeyEventKind;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, BorderType, List, ListItem, ListState, Paragraph, StatefulWidget},
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tui_textarea::TextArea;
+
+use crate::{
+    keymap::{Command, KeyContext, Keymap, PendingKeys, Resolved},
+    search::SWITCHES,
+    state_management::{action::Action, State},
+    theme::Theme,
+    ui_management::components::{Component, ComponentRender},
+};
+
+/// Results the cursor moves by with page keys
+const PAGE: usize = 20;
+
+struct Props {
+    /// Field name and its current value
+    fields: Vec<(String, String)>,
+    /// Found files relative to searched directory
+    results: Vec<String>,
+    /// Searched directory with its filesystem
+    root: String,
+    running: bool,
+    message: String,
+    keymap: Arc<Keymap>,
+    theme: Arc<Theme>,
+}
+
+impl From<&State> for Props {
+    fn from(value: &State) -> Self {
+        let search = &value.search;
+        Props {
+            fields: search.options.fields(),
+            results: search
+                .results
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(&search.root)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string()
+                })
+                .collect(),
+            root: format!("{}{}", search.vfs.prefix(), search.root.display()),
+            running: search.running,
+            message: search.message.clone(),
+            keymap: value.keymap.clone(),
+            theme: value.theme.clone(),
+        }
+    }
+}
+
+/// Dialog searching files below directory of active panel. Options are edited in place,
+/// the buttons after them start or stop the search and list found files in panel.
+/// Found files are listed below while the search runs.
+pub struct FindPage<'a> {
+    props: Props,
+    /// Fields, buttons after them and found files
+    index: usize,
+    /// Value being edited
+    input: Option<TextArea<'a>>,
+    pending_keys: PendingKeys,
+    pub action_tx: UnboundedSender<Action>,
+}
+
+impl<'a> FindPage<'a> {
+    fn last_index(&self) -> usize {
+        self.props.fields.len() + 1 + self.props.results.len()
+    }
+
+    fn confirm(&mut self) {
+        let button = self.props.fields.len();
+        let action = match self.props.fields.get(self.index) {
+            Some((name, value)) if SWITCHES.contains(&name.as_str()) => {
+                let value = if value == "yes" { "no" } else { "yes" };
+                Action::SetFindField(name.clone(), String::from(value))
+            }
+            Some((_, value)) => {
+                let mut input = TextArea::new(vec![value.clone()]);
+                input.set_style(self.props.theme.dialog_input.style());
+                input.set_cursor_line_style(Style::default());
+                input.move_cursor(tui_textarea::CursorMove::End);
+                self.input = Some(input);
+                return;
+            }
+            None if self.index == button && self.props.running => Action::StopSearch,
+            None if self.index == button => Action::StartSearch,
+            None if self.index == button + 1 => Action::SearchPanel,
+            None => Action::GoToFound(self.index - button - 2),
+        };
+        let _ = self.action_tx.send(action);
+    }
+
+    fn finish_edit(&mut self) {
+        let Some(input) = self.input.take() else {
+            return;
+        };
+        if let Some((name, _)) = self.props.fields.get(self.index) {
+            let value = input.lines()[0].trim().to_string();
+            let _ = self
+                .action_tx
+                .send(Action::SetFindField(name.clone(), value));
+        }
+    }
+}
+
+impl<'a> Component for FindPage<'a> {
+    fn new(state: &State, action_tx: UnboundedSender<Action>) -> Self
+    where
+        Self: Sized,
+    {
+        FindPage {
+            props: Props::from(state),
+            index: 0,
+            input: None,
+            pending_keys: PendingKeys::default(),
+            action_tx,
+        }
+    }
+
+    fn move_with_state(self, state: &State) -> Self
+    where
+        Self: Sized,
+    {
+        let props = Props::from(state);
+        FindPage {
+            index: self.index.min(props.fields.len() + 1 + props.results.len()),
+            props,
+            ..self
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Find file"
+    }
+
+    fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let keymap = self.props.keymap.clone();
+        let resolved = self.pending_keys.resolve(&keymap, KeyContext::Popup, key);
+        if let Some(input) = &mut self.input {
+            match resolved {
+                Resolved::Command(Command::Confirm) => self.finish_edit(),
+                Resolved::Command(Command::Cancel) => self.input = None,
+                Resolved::Unbound | Resolved::Command(Command::FocusPrev | Command::FocusNext) => {
+                    input.input(key);
+                }
+                _ => {}
+            }
+            return;
+        }
+        let Resolved::Command(command) = resolved else {
+            return;
+        };
+        match command {
+            Command::CursorUp => self.index = self.index.saturating_sub(1),
+            Command::CursorDown => self.index = (self.index + 1).min(self.last_index()),
+            Command::PageUp => self.index = self.index.saturating_sub(PAGE),
+            Command::PageDown => self.index = (self.index + PAGE).min(self.last_index()),
+            Command::Home => self.index = 0,
+            Command::End => self.index = self.last_index(),
+            Command::Confirm => self.confirm(),
+            Command::Cancel if self.props.running => {
+                let _ = self.action_tx.send(Action::StopSearch);
+            }
+            Command::Cancel => {
+                let _ = self.action_tx.send(Action::CloseFind);
+            }
+            _ => {}
+        }
+    }
+
+    fn check(&mut self) {}
+}
+
+pub struct RenderProps {
+    pub area: Rect,
+}
+
+impl<'a> ComponentRender<RenderProps> for FindPage<'a> {
+    fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
+        let button = self.props.fields.len();
+        let [fields_rec, results_rec, message_rec] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(button as u16 + 4),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ])
+            .split(props.area)
+        else {
+            panic!("Find layout should have 3 chunks")
+        };
+        let theme = &self.props.theme;
+        let name_width = self
+            .props
+            .fields
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut items: Vec<ListItem> = self
+            .props
+            .fields
+            .iter()
+            .map(|(name, value)| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!(" {:width$}  ", name, width = name_width)),
+                    Span::raw(value.clone()),
+                ]))
+            })
+            .collect();
+        items.push(ListItem::new(Line::from(match self.props.running {
+            true => " [ Stop ]",
+            false => " [ Find ]",
+        })));
+        items.push(ListItem::new(" [ Panel ]"));
+        let block = Block::bordered()
+            .border_type(BorderType::Double)
+            .title("Find file")
+            .style(theme.dialog.style());
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.dialog_cursor.style());
+        let mut state =
+            ListState::default().with_selected(Some(self.index).filter(|index| *index <= button + 1));
+        StatefulWidget::render(list, fields_rec, frame.buffer_mut(), &mut state);
+
+        if let Some(input) = &self.input {
+            let row = 1 + self.index as u16 - state.offset() as u16;
+            let column = name_width as u16 + 4;
+            if row + 1 < fields_rec.height && column + 1 < fields_rec.width {
+                let input_rec = Rect {
+                    x: fields_rec.x + column,
+                    y: fields_rec.y + row,
+                    width: fields_rec.width - column - 1,
+                    height: 1,
+                };
+                frame.render_widget(input.widget(), input_rec);
+            }
+        }
+
+        let results: Vec<ListItem> = self
+            .props
+            .results
+            .iter()
+            .map(|path| ListItem::new(format!(" {}", path)))
+            .collect();
+        let block = Block::bordered()
+            .border_type(BorderType::Double)
+            .title(match self.props.root.as_str() {
+                "" => String::from("Found"),
+                root => format!("Found in {}", root),
+            })
+            .style(theme.dialog.style());
+        let list = List::new(results)
+            .block(block)
+            .highlight_style(theme.dialog_cursor.style());
+        let mut state = ListState::default().with_selected(self.index.checked_sub(button + 2));
+        StatefulWidget::render(list, results_rec, frame.buffer_mut(), &mut state);
+
+        frame.render_widget(
+            Paragraph::new(self.props.message.clone()).style(theme.dialog_message.style()),
+            message_rec,
+        );
+    }
+}
@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+mod find_page;
+
+pub use find_page::{FindPage, RenderProps};
@@ -1,18 +1,27 @@
+use std::sync::Arc;
+
 use ratatui::{
     layout::Rect,
-    style::{Color, Style},
     text::{Line, Span},
     widgets::{Block, Paragraph},
 };
 
 use crate::{
+    keymap::{Command, KeyContext, Keymap},
     state_management::State,
+    theme::Theme,
     ui_management::components::{Component, ComponentRender},
 };
 
 struct Props {
     editor_active: bool,
     editor_modified: bool,
+    output_active: bool,
+    settings_active: bool,
+    connect_active: bool,
+    find_active: bool,
+    keymap: Arc<Keymap>,
+    theme: Arc<Theme>,
 }
 
 impl From<&State> for Props {
@@ -20,6 +29,12 @@ impl From<&State> for Props {
         Props {
             editor_active: value.editor_file.is_some(),
             editor_modified: value.editor_modified,
+            output_active: value.show_output,
+            settings_active: value.settings_open,
+            connect_active: value.connect_open,
+            find_active: value.find_open,
+            keymap: value.keymap.clone(),
+            theme: value.theme.clone(),
         }
     }
 }
@@ -66,37 +81,97 @@ pub struct RenderProps {
 
 impl ComponentRender<RenderProps> for HelpLine {
     fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
-        let message = if !self.props.editor_active {
-            Line::from(vec![
-                Span::raw(" ←→↑↓"),
-                Span::styled("Navigate", Style::default().bg(Color::Blue)),
-                Span::raw(" ↹"),
-                Span::styled("Switch panel", Style::default().bg(Color::Blue)),
-                Span::raw(" 4"),
-                Span::styled("Edit", Style::default().bg(Color::Blue)),
-                Span::raw(" 5"),
-                Span::styled("Copy", Style::default().bg(Color::Blue)),
-                Span::raw(" 6"),
-                Span::styled("Move", Style::default().bg(Color::Blue)),
-                Span::raw(" 7"),
-                Span::styled("MkDir", Style::default().bg(Color::Blue)),
-                Span::raw(" 8"),
-                Span::styled("Delete", Style::default().bg(Color::Blue)),
-                Span::raw(" 10"),
-                Span::styled("Quit", Style::default().bg(Color::Blue)),
-            ])
-        } else {
-            let mut line = Line::from(vec![
-                Span::raw("ESC "),
-                Span::styled("Quit", Style::default().bg(Color::Blue)),
-            ]);
-            if self.props.editor_modified {
-                line.push_span(Span::raw(" ^s"));
-                line.push_span(Span::styled("Save", Style::default().bg(Color::Blue)));
+        let (context, entries): (KeyContext, Vec<(&[Command], &str)>) =
+            if self.props.editor_active {
+                let mut entries: Vec<(&[Command], &str)> = vec![
+                    (&[Command::Close], "Quit"),
+                    (&[Command::Undo], "Undo"),
+                    (&[Command::Copy], "Copy"),
+                    (&[Command::Paste], "Paste"),
+                    (&[Command::DuplicateLine], "Dup line"),
+                    (&[Command::DeleteLine], "Del line"),
+                ];
+                if self.props.editor_modified {
+                    entries.push((&[Command::Save], "Save"));
+                }
+                (KeyContext::Editor, entries)
+            } else if self.props.connect_active {
+                (
+                    KeyContext::Popup,
+                    vec![
+                        (&[Command::CursorUp, Command::CursorDown], "Select"),
+                        (&[Command::Confirm], "Edit"),
+                        (&[Command::Cancel], "Close"),
+                    ],
+                )
+            } else if self.props.find_active {
+                (
+                    KeyContext::Popup,
+                    vec![
+                        (&[Command::CursorUp, Command::CursorDown], "Select"),
+                        (&[Command::Confirm], "Edit/Go to"),
+                        (&[Command::Cancel], "Stop/Close"),
+                    ],
+                )
+            } else if self.props.settings_active {
+                (
+                    KeyContext::Popup,
+                    vec![
+                        (&[Command::CursorUp, Command::CursorDown], "Select"),
+                        (&[Command::Confirm], "Edit"),
+                        (&[Command::Save], "Save"),
+                        (&[Command::Cancel], "Close"),
+                    ],
+                )
+            } else if self.props.output_active {
+                (
+                    KeyContext::Viewer,
+                    vec![
+                        (&[Command::CursorUp, Command::CursorDown], "Scroll"),
+                        (&[Command::ToggleOutput], "Panels"),
+                    ],
+                )
+            } else {
+                (
+                    KeyContext::Panel,
+                    vec![
+                        (
+                            &[
+                                Command::PageUp,
+                                Command::PageDown,
+                                Command::CursorUp,
+                                Command::CursorDown,
+                            ],
+                            "Navigate",
+                        ),
+                        (&[Command::SwitchPanel], "Switch panel"),
+                        (&[Command::View], "View"),
+                        (&[Command::Edit], "Edit"),
+                        (&[Command::Copy], "Copy"),
+                        (&[Command::Move], "Move"),
+                        (&[Command::MakeDir], "MkDir"),
+                        (&[Command::Delete], "Delete"),
+                        (&[Command::Settings], "Setup"),
+                        (&[Command::Quit], "Quit"),
+                    ],
+                )
+            };
+
+        let mut message = Line::default();
+        for (commands, title) in entries {
+            let keys: String = commands
+                .iter()
+                .filter_map(|command| self.props.keymap.label(context, *command))
+                .collect();
+            if keys.is_empty() {
+                continue;
             }
-            line
-        };
-        let line = Paragraph::new(message).block(Block::default());
+            message.push_span(Span::styled(format!(" {}", keys), self.props.theme.help_key.style()));
+            message.push_span(Span::styled(title, self.props.theme.help_label.style()));
+        }
+        let line = Paragraph::new(message)
+            .block(Block::default())
+            .style(self.props.theme.help_key.style());
 
         frame.render_widget(line, props.area);
     }
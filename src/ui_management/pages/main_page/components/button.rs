@@ -1,13 +1,15 @@
+use std::sync::Arc;
+
 use crossterm::event::KeyCode;
 use ratatui::{
     layout::{Alignment, Rect},
-    style::{Color, Style},
     widgets::{Block, Padding, Paragraph},
 };
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     state_management::action::Action,
+    theme::Theme,
     ui_management::components::{Component, ComponentRender},
 };
 
@@ -15,6 +17,7 @@ pub struct Button {
     action_tx: UnboundedSender<Action>,
     pub active: bool,
     title: String,
+    theme: Arc<Theme>,
 }
 
 impl Button {
@@ -26,7 +29,7 @@ impl Button {
 
 impl Component for Button {
     fn new(
-        _state: &crate::state_management::State,
+        state: &crate::state_management::State,
         action_tx: tokio::sync::mpsc::UnboundedSender<crate::state_management::action::Action>,
     ) -> Self
     where
@@ -35,15 +38,19 @@ impl Component for Button {
         Self {
             active: false,
             title: String::from("Ok"),
+            theme: state.theme.clone(),
             action_tx,
         }
     }
 
-    fn move_with_state(self, _state: &crate::state_management::State) -> Self
+    fn move_with_state(self, state: &crate::state_management::State) -> Self
     where
         Self: Sized,
     {
-        Self { ..self }
+        Self {
+            theme: state.theme.clone(),
+            ..self
+        }
     }
 
     fn name(&self) -> &str {
@@ -68,8 +75,8 @@ pub struct RenderProps {
 impl ComponentRender<RenderProps> for Button {
     fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
         let style = match self.active {
-            true => Style::new().bg(Color::Cyan),
-            false => Style::new().bg(Color::White),
+            true => self.theme.button_active.style(),
+            false => self.theme.button.style(),
         };
         let block = Block::new().style(style);
         let paragraph = Paragraph::new(self.title.clone())
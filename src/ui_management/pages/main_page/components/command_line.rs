@@ -0,0 +1,186 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::Style,
+    widgets::Paragraph,
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tui_textarea::{CursorMove, TextArea};
+
+use crate::{
+    state_management::{action::Action, State},
+    theme::Theme,
+    ui_management::components::{Component, ComponentRender},
+};
+
+struct Props {
+    cwd: PathBuf,
+    history: Vec<String>,
+    theme: Arc<Theme>,
+}
+
+impl From<&State> for Props {
+    fn from(value: &State) -> Self {
+        Props {
+            cwd: value.active_panel().path.clone(),
+            history: value.command_history.clone(),
+            theme: value.theme.clone(),
+        }
+    }
+}
+
+/// Shell command input under the panels
+pub struct CommandLine<'a> {
+    action_tx: UnboundedSender<Action>,
+    props: Props,
+    input: TextArea<'a>,
+    /// Position in history while browsing it
+    history_index: Option<usize>,
+}
+
+impl<'a> CommandLine<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.input.lines()[0].is_empty()
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        let line = &self.input.lines()[0];
+        if !line.is_empty() && !line.ends_with(' ') {
+            self.input.insert_char(' ');
+        }
+        self.input.insert_str(text);
+    }
+
+    /// Whether key should be handled by command line instead of panels
+    pub fn consumes(&self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char(_) => !ctrl && !alt,
+            KeyCode::Enter
+            | KeyCode::Esc
+            | KeyCode::Backspace
+            | KeyCode::Delete
+            | KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Home
+            | KeyCode::End => !ctrl && !alt && !self.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.input.move_cursor(CursorMove::Head);
+        self.input.delete_line_by_end();
+        self.input.insert_str(text);
+    }
+
+    pub fn history_prev(&mut self) {
+        let history = &self.props.history;
+        if history.is_empty() {
+            return;
+        }
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None => history.len() - 1,
+        };
+        self.history_index = Some(index);
+        let text = history[index].clone();
+        self.set_text(&text);
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            Some(index) if index + 1 < self.props.history.len() => {
+                self.history_index = Some(index + 1);
+                let text = self.props.history[index + 1].clone();
+                self.set_text(&text);
+            }
+            _ => {
+                self.history_index = None;
+                self.set_text("");
+            }
+        }
+    }
+}
+
+impl<'a> Component for CommandLine<'a> {
+    fn new(state: &State, action_tx: UnboundedSender<Action>) -> Self
+    where
+        Self: Sized,
+    {
+        let mut input = TextArea::default();
+        input.set_cursor_line_style(Style::default());
+        CommandLine {
+            action_tx,
+            props: Props::from(state),
+            input,
+            history_index: None,
+        }
+        .move_with_state(state)
+    }
+
+    fn move_with_state(self, state: &State) -> Self
+    where
+        Self: Sized,
+    {
+        let props = Props::from(state);
+        let mut input = self.input;
+        input.set_style(props.theme.command_line.style());
+        Self {
+            props,
+            input,
+            ..self
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Command line"
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                let command = self.input.lines()[0].trim().to_string();
+                self.set_text("");
+                self.history_index = None;
+                if !command.is_empty() {
+                    let _ = self.action_tx.send(Action::RunCommand(command));
+                }
+            }
+            KeyCode::Esc => {
+                self.set_text("");
+                self.history_index = None;
+            }
+            _ => {
+                self.input.input(key);
+            }
+        }
+    }
+
+    fn check(&mut self) {}
+}
+
+pub struct RenderProps {
+    pub area: Rect,
+}
+
+impl<'a> ComponentRender<RenderProps> for CommandLine<'a> {
+    fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
+        let prompt = format!("{}>", self.props.cwd.display());
+        let [prompt_rec, input_rec] = *Layout::horizontal([
+            Constraint::Length((prompt.chars().count() as u16).min(props.area.width / 2)),
+            Constraint::Min(0),
+        ])
+        .split(props.area) else {
+            panic!("Command line should have 2 chunks")
+        };
+        frame.render_widget(
+            Paragraph::new(prompt).style(self.props.theme.command_line.style()),
+            prompt_rec,
+        );
+        frame.render_widget(self.input.widget(), input_rec);
+    }
+}
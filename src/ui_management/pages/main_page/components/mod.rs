@@ -1,7 +1,9 @@
 mod button;
+mod command_line;
 mod panel;
 mod popup;
 
 pub use button::Button;
+pub use command_line::{CommandLine, RenderProps as CommandLineRenderProps};
 pub use panel::{Panel, RenderProps};
 pub use popup::{Popup, PopupRenderProps};
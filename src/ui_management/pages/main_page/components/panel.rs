@@ -1,15 +1,18 @@
-use std::{path::PathBuf, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use crossterm::event::KeyCode;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use ratatui::{
     layout::{Alignment, Rect},
-    style::{Color, Style},
+    style::Style,
     widgets::{Block, BorderType, List, ListItem, ListState, StatefulWidget},
 };
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
+    config::ViewMode,
+    highlight::Highlighting,
+    keymap::{Command, KeyChord, KeyContext, Keymap, Resolved},
+    theme::Theme,
     state_management::{action::Action, PanelItem, PanelPosition},
     ui_management::components::{Component, ComponentRender},
 };
@@ -18,7 +21,19 @@ struct Props {
     active: bool,
     items: Vec<PanelItem>,
     pub directory: Option<PathBuf>,
+    /// Filesystem shown before directory
+    prefix: String,
+    /// Saved connections are listed, first one after `..`
+    network: bool,
+    /// Files are listed instead of directory content
+    listed: bool,
+    /// Remote directory is being read, items are the ones shown before
+    loading: bool,
     index: usize,
+    view_mode: ViewMode,
+    theme: Arc<Theme>,
+    highlighting: Arc<Highlighting>,
+    keymap: Arc<Keymap>,
 }
 
 pub struct Panel {
@@ -53,8 +68,77 @@ impl Panel {
             .expect("No directory set for panel")
     }
 
+    /// Listed files are named relative to panel directory
     fn current_file_name(&mut self) -> String {
-        self.current_item().current_file_name()
+        self.current_item().name()
+    }
+
+    pub fn is_network(&self) -> bool {
+        self.props.network
+    }
+
+    /// Handles commands on saved connections, index of connection is the one of
+    /// its item without `..`
+    pub fn handle_network_command(&mut self, command: Command) {
+        let profile = self.props.index.checked_sub(1);
+        let action = match (command, profile) {
+            (Command::Open | Command::EnterArchive, None) => Action::Cd(String::from("..")),
+            (Command::Open | Command::EnterArchive, Some(index)) => Action::ConnectProfile(index),
+            (Command::MakeDir, _) => Action::AddProfile,
+            (Command::Edit, Some(index)) => Action::EditProfile(index),
+            (Command::Copy, Some(index)) => Action::DuplicateProfile(index),
+            (Command::Delete, Some(index)) => Action::DeleteProfileYesNo(index),
+            (command, _) => return self.handle_command(command),
+        };
+        let _ = self.action_tx.send(action);
+    }
+
+    /// Handles cursor movement and opening of current item
+    pub fn handle_command(&mut self, command: Command) {
+        let count = self.props.items.len();
+        match command {
+            Command::CursorDown => {
+                let _ = self.action_tx.send(Action::FileItemDown(1));
+            }
+            Command::CursorUp => {
+                let _ = self.action_tx.send(Action::FileItemUp(1));
+            }
+            Command::PageDown => {
+                let _ = self.action_tx.send(Action::FileItemDown(20));
+            }
+            Command::PageUp => {
+                let _ = self.action_tx.send(Action::FileItemUp(20));
+            }
+            Command::Home => {
+                let _ = self.action_tx.send(Action::FileItemUp(count));
+            }
+            Command::End => {
+                let _ = self.action_tx.send(Action::FileItemDown(count));
+            }
+            Command::Edit if !self.current_item().is_dir() => {
+                let file_to_edit = self.current_full_path().clone();
+                let _ = self.action_tx.send(Action::EditAssociated(file_to_edit));
+            }
+            Command::Open => {
+                if self.current_item().is_dir() {
+                    let newdir = self.current_file_name().clone();
+                    let _ = self.action_tx.send(Action::Cd(newdir));
+                } else {
+                    let file_to_open = self.current_full_path().clone();
+                    let _ = self.action_tx.send(Action::Open(file_to_open));
+                }
+            }
+            Command::EnterArchive => {
+                if self.current_item().is_dir() {
+                    let newdir = self.current_file_name().clone();
+                    let _ = self.action_tx.send(Action::Cd(newdir));
+                } else {
+                    let archive = self.current_full_path().clone();
+                    let _ = self.action_tx.send(Action::EnterArchive(archive));
+                }
+            }
+            _ => {}
+        }
     }
 }
 
@@ -76,8 +160,16 @@ impl Component for Panel {
             props: Props {
                 active: true,
                 directory: None,
+                prefix: String::new(),
+                network: false,
+                listed: false,
+                loading: false,
                 items: vec![],
                 index: 0,
+                view_mode: ViewMode::Brief,
+                theme: state.theme.clone(),
+                highlighting: state.highlighting.clone(),
+                keymap: state.keymap.clone(),
             },
             watcher,
             watch_rx,
@@ -99,16 +191,27 @@ impl Component for Panel {
             std::sync::mpsc::channel::<Result<notify::Event, notify::Error>>();
         let mut watcher = RecommendedWatcher::new(watch_tx, notify::Config::default())
             .expect("Faield to create watcher");
-        watcher
-            .watch(my_state.path.as_ref(), RecursiveMode::NonRecursive)
-            .expect("Failed to watch file event");
+        // other filesystems are reloaded after changes made from here only
+        if my_state.vfs.is_local() && !my_state.network {
+            watcher
+                .watch(my_state.path.as_ref(), RecursiveMode::NonRecursive)
+                .expect("Failed to watch file event");
+        }
 
         Self {
             props: Props {
                 directory: Some(my_state.path.clone()),
+                prefix: my_state.vfs.prefix(),
+                network: my_state.network,
+                listed: my_state.listed.is_some(),
+                loading: my_state.loading,
                 items: my_state.items.clone(),
                 active: my_state.active,
                 index: my_state.index,
+                view_mode: my_state.view_mode,
+                theme: state.theme.clone(),
+                highlighting: state.highlighting.clone(),
+                keymap: state.keymap.clone(),
             },
             watcher,
             watch_rx,
@@ -124,41 +227,9 @@ impl Component for Panel {
     }
 
     fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) {
-        match key.code {
-            KeyCode::Down => {
-                let _ = self.action_tx.send(Action::FileItemDown(1));
-            }
-            KeyCode::Up => {
-                let _ = self.action_tx.send(Action::FileItemUp(1));
-            }
-            KeyCode::PageDown => {
-                let _ = self.action_tx.send(Action::FileItemDown(20));
-            }
-            KeyCode::PageUp => {
-                let _ = self.action_tx.send(Action::FileItemUp(20));
-            }
-            KeyCode::Right => {
-                let _ = self.action_tx.send(Action::FileItemDown(20));
-            }
-            KeyCode::Left => {
-                let _ = self.action_tx.send(Action::FileItemUp(20));
-            }
-            KeyCode::F(4) => {
-                if !self.current_item().is_dir() {
-                    let file_to_edit = self.current_full_path().clone();
-                    let _ = self.action_tx.send(Action::Edit(file_to_edit));
-                }
-            }
-            KeyCode::Enter => {
-                if self.current_item().is_dir() {
-                    let newdir = self.current_file_name().clone();
-                    let _ = self.action_tx.send(Action::Cd(newdir));
-                } else {
-                    let file_to_open = self.current_full_path().clone();
-                    let _ = self.action_tx.send(Action::Open(file_to_open));
-                }
-            }
-            _ => {}
+        let keys = [KeyChord::from(key)];
+        if let Resolved::Command(command) = self.props.keymap.resolve(KeyContext::Panel, &keys) {
+            self.handle_command(command);
         }
     }
 
@@ -177,24 +248,42 @@ pub struct RenderProps {
 impl ComponentRender<RenderProps> for Panel {
     fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
         let title_text = match &self.props.directory {
+            _ if self.props.network => String::from("Network"),
             None => String::from(""),
-            Some(dir) => dir.display().to_string(),
+            Some(dir) if self.props.listed => format!("List: {}{}", self.props.prefix, dir.display()),
+            Some(dir) => format!("{}{}", self.props.prefix, dir.display()),
+        };
+        let title_text = match self.props.loading {
+            true => format!("{} Reading...", title_text),
+            false => title_text,
         };
 
-        let panel_items: Vec<ListItem> = self.props.items.iter().map(|item| item.into()).collect();
+        let theme = &self.props.theme;
+        // inside of borders, connections are always listed with details
+        let width = match self.props.view_mode {
+            ViewMode::Brief if !self.props.network => None,
+            _ => Some(props.area.width.saturating_sub(2) as usize),
+        };
+        let panel_items: Vec<ListItem> = self
+            .props
+            .items
+            .iter()
+            .map(|item| item.list_item(theme, &self.props.highlighting, width))
+            .collect();
 
-        let mut title_style = Style::new();
-        if self.props.active {
-            title_style = title_style.bg(Color::LightCyan).fg(Color::Black);
-        }
+        let title_style = if self.props.active {
+            theme.panel_title_active.style()
+        } else {
+            theme.panel_title.style()
+        };
         let panel_block = Block::bordered().border_type(BorderType::Double).border_style(Style::default())
             .title_style(title_style)
             .title(title_text)
             .title_alignment(Alignment::Center)
-            .style(Style::new().bg(Color::Black).fg(Color::White));
+            .style(theme.panel.style());
         let panel_list = List::new(panel_items)
             .block(panel_block)
-            .highlight_style(Style::new().bg(Color::Cyan).fg(Color::Black));
+            .highlight_style(theme.cursor.style());
         let mut state = ListState::default().with_selected(if self.props.active {
             Some(self.props.index)
         } else {
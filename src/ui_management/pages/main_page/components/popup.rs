@@ -1,15 +1,20 @@
+use std::sync::Arc;
+
+use crossterm::event::KeyCode;
+
 use crate::{
-    state_management::{action::Action, PopupType, State},
+    keymap::{Command, KeyContext, Keymap, PendingKeys, Resolved},
+    theme::Theme,
+    state_management::{action::Action, MenuItem, PopupType, State},
     ui_management::components::{Component, ComponentRender},
 };
-use crossterm::event::KeyCode;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
-    widgets::{Block, Borders, Clear, Padding, Paragraph, Wrap},
+    style::Style,
+    widgets::{Block, Borders, Clear, List, ListState, Padding, Paragraph, StatefulWidget, Wrap},
 };
 use tokio::sync::mpsc::UnboundedSender;
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 
 use super::Button;
 
@@ -18,6 +23,10 @@ struct Props {
     pub popup_type: PopupType,
     pub active: bool,
     pub popup_next_action: Option<Action>,
+    pub popup_menu: Vec<MenuItem>,
+    pub popup_input: String,
+    pub keymap: Arc<Keymap>,
+    pub theme: Arc<Theme>,
 }
 
 impl From<&State> for Props {
@@ -27,6 +36,10 @@ impl From<&State> for Props {
             active: !value.popup_msg.is_empty(),
             popup_msg: value.popup_msg.clone(),
             popup_type: value.popup_type.clone(),
+            popup_menu: value.popup_menu.clone(),
+            popup_input: value.popup_input.clone(),
+            keymap: value.keymap.clone(),
+            theme: value.theme.clone(),
         }
     }
 }
@@ -37,6 +50,8 @@ pub struct Popup<'a> {
     ok_button: Button,
     cancel_button: Button,
     input: TextArea<'a>,
+    menu_index: usize,
+    pending_keys: PendingKeys,
 }
 
 impl<'a> Popup<'a> {
@@ -44,6 +59,19 @@ impl<'a> Popup<'a> {
         self.props.active
     }
 
+    fn is_menu(&self) -> bool {
+        matches!(self.props.popup_type, PopupType::Menu | PopupType::Hotlist)
+    }
+
+    fn new_input(text: &str) -> TextArea<'a> {
+        let input_block = Block::default().padding(Padding { left: 0, right: 0, top: 0, bottom: 1 });
+        let mut textarea = TextArea::from([text]);
+        textarea.set_block(input_block);
+        textarea.set_cursor_line_style(Style::default());
+        textarea.move_cursor(CursorMove::End);
+        textarea
+    }
+
     fn render_ok_button(&self, frame: &mut ratatui::prelude::Frame, area: Rect) {
         self.ok_button
         .render(frame, super::button::RenderProps { area });
@@ -106,27 +134,42 @@ impl<'a> Component for Popup<'a> {
         let mut ok_button = Button::new(state, action_tx.clone()).title(String::from("Ok"));
         ok_button.active = true;
         let cancel_button = Button::new(state, action_tx.clone()).title(String::from("Cancel"));
-        let input_block = Block::default().padding(Padding { left: 0, right: 0, top: 0, bottom: 1 });
-        let mut textarea = TextArea::default();
-        textarea.set_block(input_block);
-        textarea.set_style(Style::default().bg(Color::Blue).fg(Color::White));
-        textarea.set_cursor_line_style(Style::default());
-        textarea.set_cursor_style(Style::default().bg(Color::Cyan));
         Popup {
             action_tx,
             props: Props::from(state),
-            input: textarea,
+            input: Self::new_input(""),
             ok_button,
             cancel_button,
+            menu_index: 0,
+            pending_keys: PendingKeys::default(),
         }
+        .move_with_state(state)
     }
 
     fn move_with_state(self, state: &crate::state_management::State) -> Self
     where
         Self: Sized,
     {
+        let props = Props::from(state);
+        let was_input = self.props.active && self.props.popup_type == PopupType::Input;
+        // input starts with text prepared by the action opening it
+        let mut input = if props.active && props.popup_type == PopupType::Input && !was_input {
+            Self::new_input(&props.popup_input)
+        } else {
+            self.input
+        };
+        input.set_style(props.theme.input.style());
+        input.set_cursor_style(props.theme.input_cursor.style());
+        let menu_index = match props.active {
+            true => self.menu_index.min(props.popup_menu.len().saturating_sub(1)),
+            false => 0,
+        };
         Self {
-            props: Props::from(state),
+            menu_index,
+            ok_button: self.ok_button.move_with_state(state),
+            cancel_button: self.cancel_button.move_with_state(state),
+            input,
+            props,
             ..self
         }
     }
@@ -136,30 +179,66 @@ impl<'a> Component for Popup<'a> {
     }
 
     fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) {
-        match key.code {
-            KeyCode::Right => {
+        let keymap = self.props.keymap.clone();
+        match self.pending_keys.resolve(&keymap, KeyContext::Popup, key) {
+            Resolved::Command(Command::FocusNext) => {
                 if self.ok_button.active {
                     self.ok_button.active = false;
                     self.cancel_button.active = true;
                 }
             }
-            KeyCode::Left => {
+            Resolved::Command(Command::FocusPrev) => {
                 if self.cancel_button.active {
                     self.ok_button.active = true;
                     self.cancel_button.active = false;
                 }
             }
-            KeyCode::Esc => {
+            Resolved::Command(Command::CursorUp) if self.is_menu() => {
+                self.menu_index = self.menu_index.saturating_sub(1);
+            }
+            Resolved::Command(Command::CursorDown)
+                if self.is_menu() && self.menu_index + 1 < self.props.popup_menu.len() =>
+            {
+                self.menu_index += 1;
+            }
+            Resolved::Command(Command::AddItem) if self.props.popup_type == PopupType::Hotlist => {
+                let _ = self.action_tx.send(Action::AddBookmarkInput);
+            }
+            Resolved::Command(Command::Edit)
+                if self.props.popup_type == PopupType::Hotlist && !self.props.popup_menu.is_empty() =>
+            {
+                let _ = self.action_tx.send(Action::RenameBookmarkInput(self.menu_index));
+            }
+            Resolved::Command(Command::Delete)
+                if self.props.popup_type == PopupType::Hotlist && !self.props.popup_menu.is_empty() =>
+            {
+                let _ = self.action_tx.send(Action::DeleteBookmark(self.menu_index));
+            }
+            Resolved::Command(Command::Cancel) => {
                 let _ = self.action_tx.send(Action::Cancel);
             }
-            KeyCode::Enter => {
+            Resolved::Command(Command::Confirm) if self.is_menu() => {
+                if let Some(item) = self.props.popup_menu.get(self.menu_index) {
+                    let _ = self.action_tx.send(item.action.clone());
+                }
+            }
+            Resolved::Command(Command::Confirm) => {
                 if (self.props.popup_type == PopupType::Error) || (self.cancel_button.active) {
                     let _ = self.action_tx.send(Action::Cancel);
                 } else if let Some(action) = self.props.popup_next_action.clone() {
                     let _ = self.action_tx.send(action);
                 }
             }
-            _ => {
+            Resolved::Pending | Resolved::Ignored => {}
+            Resolved::Unbound if self.props.popup_type == PopupType::Hotlist => {
+                // digit jumps to quick bookmark
+                if let KeyCode::Char(c) = key.code {
+                    if let Some(digit) = c.to_digit(10) {
+                        let _ = self.action_tx.send(Action::JumpQuickBookmark(digit as u8));
+                    }
+                }
+            }
+            Resolved::Unbound | Resolved::Command(_) => {
                 if self.props.popup_type == PopupType::Input {
                     self.input.input(key);
                 }
@@ -192,11 +271,11 @@ impl<'a> ComponentRender<PopupRenderProps> for Popup<'a> {
             };
             let block = Block::default()
                 .borders(Borders::all())
-                .style(Style::default().bg(Color::White).fg(Color::Black))
-                .title(if self.props.popup_type == PopupType::Error {
-                    "Error"
-                } else {
-                    "Please select"
+                .style(self.props.theme.popup.style())
+                .title(match self.props.popup_type {
+                    PopupType::Error => "Error",
+                    PopupType::Menu | PopupType::Hotlist => self.props.popup_msg.as_str(),
+                    _ => "Please select",
                 });
             frame.render_widget(block, popup_area);
             match self.props.popup_type {
@@ -216,6 +295,17 @@ impl<'a> ComponentRender<PopupRenderProps> for Popup<'a> {
                     frame.render_widget(self.input.widget(), input_rec);
                     self.render_ok_cancel_buttons(frame, button_rec)                    
                 },
+                PopupType::Menu | PopupType::Hotlist => {
+                    let items: Vec<String> = self.props.popup_menu.iter().map(|item| item.label.clone()).collect();
+                    let list = List::new(items)
+                        .highlight_style(self.props.theme.menu_cursor.style());
+                    let mut state = ListState::default().with_selected(Some(self.menu_index));
+                    let menu_rec = Rect {
+                        height: data_rec.height + button_rec.height,
+                        ..data_rec
+                    };
+                    StatefulWidget::render(list, menu_rec, frame.buffer_mut(), &mut state);
+                },
                 PopupType::YesNo => {
                     frame.render_widget(Paragraph::new(self.props.popup_msg.clone()).wrap(Wrap {trim: false}), data_rec);
                     self.render_ok_cancel_buttons(frame, button_rec)
@@ -1,19 +1,30 @@
-use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
-use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use std::sync::Arc;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    widgets::Gauge,
+};
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
-    state_management::{action::Action, PanelItem, PanelPosition, State},
+    config::SortOrder,
+    keymap::{Command, KeyContext, Keymap, PendingKeys, Resolved},
+    state_management::{action::Action, JobStatus, PanelItem, PanelPosition, State},
+    theme::Theme,
     ui_management::components::{Component, ComponentRender},
 };
 
 use super::{
-    components::{Popup, PopupRenderProps},
+    components::{CommandLine, Popup, PopupRenderProps},
     Panel,
 };
 
 struct Props {
     active_panel: PanelPosition,
+    keymap: Arc<Keymap>,
+    job: Option<JobStatus>,
+    theme: Arc<Theme>,
 }
 
 impl From<&State> for Props {
@@ -24,6 +35,9 @@ impl From<&State> for Props {
             } else {
                 PanelPosition::L
             },
+            keymap: value.keymap.clone(),
+            job: value.job.clone(),
+            theme: value.theme.clone(),
         }
     }
 }
@@ -37,6 +51,8 @@ pub struct MainPage<'a> {
     l_panel: Panel,
     r_panel: Panel,
     popup: Popup<'a>,
+    command_line: CommandLine<'a>,
+    pending_keys: PendingKeys,
 }
 
 impl<'a> MainPage<'a> {
@@ -57,6 +73,149 @@ impl<'a> MainPage<'a> {
     fn opposite_path(&mut self) -> String {
         self.opposite_panel().current_dir().display().to_string()
     }
+
+    fn active_panel_mut(&mut self) -> &mut Panel {
+        match self.props.active_panel {
+            PanelPosition::L => &mut self.l_panel,
+            PanelPosition::R => &mut self.r_panel,
+        }
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        if self.active_panel_mut().is_network() {
+            match command {
+                Command::Open
+                | Command::EnterArchive
+                | Command::Edit
+                | Command::Copy
+                | Command::MakeDir
+                | Command::Delete => {
+                    return self.active_panel_mut().handle_network_command(command);
+                }
+                // saved connections aren't files
+                Command::View
+                | Command::ExternalView
+                | Command::ExternalEdit
+                | Command::OpenWith
+                | Command::Move
+                | Command::Rename
+                | Command::Pack
+                | Command::InsertFileName
+                | Command::InsertPath
+                | Command::Mark
+                | Command::MarkAll => return,
+                _ => {}
+            }
+        }
+        match command {
+            Command::SwitchPanel => {
+                let _ = self.action_tx.send(Action::SwitchTabs);
+            }
+            Command::InsertFileName => {
+                let file_name = self.current_item().current_file_name();
+                self.command_line.insert(&file_name);
+            }
+            Command::InsertPath => {
+                let full_path = self.current_item().file_full_path();
+                self.command_line.insert(&full_path);
+            }
+            Command::HistoryPrev => self.command_line.history_prev(),
+            Command::HistoryNext => self.command_line.history_next(),
+            Command::ExternalView if !self.current_item().is_dir() => {
+                let file_to_view = self.current_item().file_full_path();
+                let _ = self.action_tx.send(Action::ExternalView(file_to_view));
+            }
+            Command::ExternalEdit if !self.current_item().is_dir() => {
+                let file_to_edit = self.current_item().file_full_path();
+                let _ = self.action_tx.send(Action::ExternalEdit(file_to_edit));
+            }
+            Command::View if !self.current_item().is_dir() => {
+                let file_to_view = self.current_item().file_full_path();
+                let _ = self.action_tx.send(Action::View(file_to_view));
+            }
+            Command::OpenWith if !self.current_item().is_dir() => {
+                let file_to_open = self.current_item().file_full_path();
+                let _ = self.action_tx.send(Action::OpenWithMenu(file_to_open));
+            }
+            Command::Copy => {
+                let copy_from = self.current_item().file_full_path();
+                let copy_to = self.opposite_path();
+                let _ = self.action_tx.send(Action::Copy(copy_from, copy_to));
+            }
+            Command::Move => {
+                let move_from = self.current_item().file_full_path();
+                let move_to = self.opposite_path();
+                let _ = self.action_tx.send(Action::MoveInput(move_from, move_to));
+            }
+            Command::Rename if self.current_item().current_file_name() != ".." => {
+                let file = self.current_item().file_full_path();
+                let _ = self.action_tx.send(Action::RenameInput(file));
+            }
+            Command::Connect => {
+                let _ = self.action_tx.send(Action::OpenConnect);
+            }
+            Command::Network => {
+                let _ = self.action_tx.send(Action::OpenNetwork);
+            }
+            Command::Find => {
+                let _ = self.action_tx.send(Action::OpenFind);
+            }
+            Command::Mark => {
+                let _ = self.action_tx.send(Action::Mark);
+            }
+            Command::MarkAll => {
+                let _ = self.action_tx.send(Action::MarkAll);
+            }
+            Command::Pack => {
+                let file = self.current_item().file_full_path();
+                let _ = self.action_tx.send(Action::PackInput(file));
+            }
+            Command::MakeDir => {
+                let _ = self.action_tx.send(Action::MkDirInput);
+            }
+            Command::Delete => {
+                let remove = self.current_item().file_full_path();
+                let _ = self.action_tx.send(Action::RmYesNo(remove));
+            }
+            Command::Settings => {
+                let _ = self.action_tx.send(Action::OpenSettings);
+            }
+            Command::Quit => {
+                let _ = self.action_tx.send(Action::Exit);
+            }
+            Command::ToggleOutput => {
+                let _ = self.action_tx.send(Action::ToggleOutput);
+            }
+            Command::SortByName => {
+                let _ = self.action_tx.send(Action::SortBy(SortOrder::Name));
+            }
+            Command::SortByExtension => {
+                let _ = self.action_tx.send(Action::SortBy(SortOrder::Extension));
+            }
+            Command::SortBySize => {
+                let _ = self.action_tx.send(Action::SortBy(SortOrder::Size));
+            }
+            Command::SortByModified => {
+                let _ = self.action_tx.send(Action::SortBy(SortOrder::Modified));
+            }
+            Command::ToggleViewMode => {
+                let _ = self.action_tx.send(Action::ToggleViewMode);
+            }
+            Command::HistoryBack => {
+                let _ = self.action_tx.send(Action::HistoryBack);
+            }
+            Command::HistoryForward => {
+                let _ = self.action_tx.send(Action::HistoryForward);
+            }
+            Command::FolderHistory => {
+                let _ = self.action_tx.send(Action::FolderHistory);
+            }
+            Command::Hotlist => {
+                let _ = self.action_tx.send(Action::OpenHotlist);
+            }
+            command => self.active_panel_mut().handle_command(command),
+        }
+    }
 }
 
 impl<'a> Component for MainPage<'a> {
@@ -69,12 +228,15 @@ impl<'a> Component for MainPage<'a> {
         let mut r_panel = Panel::new(state, action_tx.clone());
         r_panel.set_placement(PanelPosition::R);
         let popup = Popup::new(state, action_tx.clone());
+        let command_line = CommandLine::new(state, action_tx.clone());
         MainPage {
             action_tx: action_tx.clone(),
             props: Props::from(state),
             l_panel,
             r_panel,
             popup,
+            command_line,
+            pending_keys: PendingKeys::default(),
         }
         .move_with_state(state)
     }
@@ -88,6 +250,7 @@ impl<'a> Component for MainPage<'a> {
             l_panel: self.l_panel.move_with_state(state),
             r_panel: self.r_panel.move_with_state(state),
             popup: self.popup.move_with_state(state),
+            command_line: self.command_line.move_with_state(state),
             ..self
         }
     }
@@ -104,58 +267,24 @@ impl<'a> Component for MainPage<'a> {
                 return;
             }
 
-            if self.props.active_panel == PanelPosition::L {
-                self.l_panel.handle_key_event(key)
-            } else {
-                self.r_panel.handle_key_event(key)
+            if self.pending_keys.is_empty() && self.command_line.consumes(&key) {
+                self.command_line.handle_key_event(key);
+                return;
             }
 
-            match key.code {
-                KeyCode::Tab => {
-                    let _ = self.action_tx.send(Action::SwitchTabs);
-                }
-                KeyCode::F(4) => {
-                    if !self.current_item().is_dir() {
-                        let file_to_edit = self.current_item().current_file_name();
-                        let _ = self.action_tx.send(Action::Edit(file_to_edit));
+            let keymap = self.props.keymap.clone();
+            match self.pending_keys.resolve(&keymap, KeyContext::Panel, key) {
+                Resolved::Command(Command::QuickBookmark) => {
+                    if let Some(digit) = key_digit(&key) {
+                        let _ = self.action_tx.send(Action::JumpQuickBookmark(digit));
                     }
                 }
-                KeyCode::F(5) => {
-                    let copy_from = self.current_item().file_full_path();
-                    let copy_to = self.opposite_path();
-                    let _ = self.action_tx.send(Action::Copy(copy_from, copy_to));
-                }
-                KeyCode::F(6) => {
-                    let copy_from = self.current_item().file_full_path();
-                    let copy_to = self.opposite_path();
-                    let _ = self.action_tx.send(Action::Move(copy_from, copy_to));
-                }
-                KeyCode::F(7) => {
-                    let _ = self.action_tx.send(Action::MkDirInput);
-                }
-                KeyCode::F(8) => {
-                    let remove = self.current_item().file_full_path();
-                    let _ = self.action_tx.send(Action::RmYesNo(remove));
-                }
-                KeyCode::F(10) => {
-                    let _ = self.action_tx.send(Action::Exit);
-                }
-                KeyCode::Char('d') => {
-                    let remove = self.current_item().file_full_path();
-                    let _ = self.action_tx.send(Action::RmYesNo(remove));
-                }
-                KeyCode::Char('q') => {
-                    let _ = self.action_tx.send(Action::Exit);
-                }
-                KeyCode::Char('c') => {
-                    if key.modifiers.contains(KeyModifiers::CONTROL) {
-                        let _ = self.action_tx.send(Action::Exit);
-                    } else {
-                        let copy_from = self.current_item().file_full_path();
-                        let copy_to = self.opposite_path();
-                        let _ = self.action_tx.send(Action::Copy(copy_from, copy_to));
+                Resolved::Command(Command::SetQuickBookmark) => {
+                    if let Some(digit) = key_digit(&key) {
+                        let _ = self.action_tx.send(Action::SetQuickBookmark(digit));
                     }
                 }
+                Resolved::Command(command) => self.handle_command(command),
                 _ => {}
             }
         }
@@ -167,24 +296,66 @@ impl<'a> Component for MainPage<'a> {
     }
 }
 
+/// Digit of the key, quick bookmark commands take number from it
+fn key_digit(key: &KeyEvent) -> Option<u8> {
+    match key.code {
+        KeyCode::Char(c) => c.to_digit(10).map(|digit| digit as u8),
+        _ => None,
+    }
+}
+
 pub struct RenderProps {
     pub area: Rect,
 }
 
 impl<'a> ComponentRender<RenderProps> for MainPage<'a> {
     fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
+        let job_height = if self.props.job.is_some() { 1 } else { 0 };
+        let [panels_rec, job_rec, command_line_rec] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(job_height),
+                Constraint::Length(1),
+            ])
+            .split(props.area)
+        else {
+            panic!("The main layout should have 3 chunks")
+        };
         let [l_panel_rec, r_panel_rec] = *Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(props.area)
+            .split(panels_rec)
         else {
-            panic!("The main layout should have 2 chunks")
+            panic!("The panels layout should have 2 chunks")
         };
 
         self.l_panel
             .render(frame, super::components::RenderProps { area: l_panel_rec });
         self.r_panel
             .render(frame, super::components::RenderProps { area: r_panel_rec });
+        if let Some(job) = &self.props.job {
+            let label = format!(
+                "{} {:.0}% {}",
+                job.title,
+                job.ratio() * 100.0,
+                job.file
+            );
+            frame.render_widget(
+                Gauge::default()
+                    .ratio(job.ratio())
+                    .label(label)
+                    .style(self.props.theme.dialog.style())
+                    .gauge_style(self.props.theme.dialog_cursor.style()),
+                job_rec,
+            );
+        }
+        self.command_line.render(
+            frame,
+            super::components::CommandLineRenderProps {
+                area: command_line_rec,
+            },
+        );
         self.popup
             .render(frame, PopupRenderProps { area: props.area })
     }
@@ -5,17 +5,29 @@ use ratatui::{
 
 use crate::state_management::State;
 
-use self::{editor_page::EditorPage, help_line::HelpLine, main_page::MainPage};
+use self::{
+    connect_page::ConnectPage, editor_page::EditorPage, find_page::FindPage, help_line::HelpLine, main_page::MainPage,
+    output_page::OutputPage, settings_page::SettingsPage,
+};
 
 use super::components::{Component, ComponentRender};
 
+mod connect_page;
+mod find_page;
 mod help_line;
 mod main_page;
 mod editor_page;
+mod output_page;
+mod settings_page;
 
+#[allow(clippy::enum_variant_names)]
 enum ActivePage {
     MainPage,
     EditorPage,
+    OutputPage,
+    SettingsPage,
+    ConnectPage,
+    FindPage,
 }
 
 struct Props {
@@ -25,10 +37,18 @@ struct Props {
 impl From<&State> for Props {
     fn from(state: &State) -> Self {
         Props {
-            active_page: if state.editor_file.is_none() {
-                ActivePage::MainPage
-            } else {
+            active_page: if state.editor_file.is_some() {
                 ActivePage::EditorPage
+            } else if state.connect_open {
+                ActivePage::ConnectPage
+            } else if state.find_open {
+                ActivePage::FindPage
+            } else if state.settings_open {
+                ActivePage::SettingsPage
+            } else if state.show_output {
+                ActivePage::OutputPage
+            } else {
+                ActivePage::MainPage
             },
         }
     }
@@ -38,14 +58,23 @@ pub struct AppRouter<'a> {
     props: Props,
     main_page: MainPage<'a>,
     editor_page: EditorPage<'a>,
+    output_page: OutputPage,
+    settings_page: SettingsPage<'a>,
+    connect_page: ConnectPage<'a>,
+    find_page: FindPage<'a>,
     help_line: HelpLine,
 }
 
 impl<'a> AppRouter<'a> {
+    #[allow(dead_code)]
     fn get_active_page_component(&self) -> &dyn Component {
         match self.props.active_page {
             ActivePage::MainPage => &self.main_page,
             ActivePage::EditorPage => &self.editor_page,
+            ActivePage::OutputPage => &self.output_page,
+            ActivePage::SettingsPage => &self.settings_page,
+            ActivePage::ConnectPage => &self.connect_page,
+            ActivePage::FindPage => &self.find_page,
         }
     }
 
@@ -53,6 +82,10 @@ impl<'a> AppRouter<'a> {
         match self.props.active_page {
             ActivePage::MainPage => &mut self.main_page,
             ActivePage::EditorPage => &mut self.editor_page,
+            ActivePage::OutputPage => &mut self.output_page,
+            ActivePage::SettingsPage => &mut self.settings_page,
+            ActivePage::ConnectPage => &mut self.connect_page,
+            ActivePage::FindPage => &mut self.find_page,
         }
     }
 }
@@ -69,6 +102,10 @@ impl<'a> Component for AppRouter<'a> {
             props: Props::from(state),
             main_page: MainPage::new(state, action_tx.clone()),
             editor_page: EditorPage::new(state, action_tx.clone()),
+            output_page: OutputPage::new(state, action_tx.clone()),
+            settings_page: SettingsPage::new(state, action_tx.clone()),
+            connect_page: ConnectPage::new(state, action_tx.clone()),
+            find_page: FindPage::new(state, action_tx.clone()),
             help_line: HelpLine::new(state, action_tx.clone()),
         }
         .move_with_state(state)
@@ -82,6 +119,10 @@ impl<'a> Component for AppRouter<'a> {
             props: Props::from(state),
             main_page: self.main_page.move_with_state(state),
             editor_page: self.editor_page.move_with_state(state),
+            output_page: self.output_page.move_with_state(state),
+            settings_page: self.settings_page.move_with_state(state),
+            connect_page: self.connect_page.move_with_state(state),
+            find_page: self.find_page.move_with_state(state),
             help_line: self.help_line.move_with_state(state),
         }
     }
@@ -93,10 +134,18 @@ impl<'a> Component for AppRouter<'a> {
     fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) {
         self.get_active_page_component_mut().handle_key_event(key)
     }
+
+    fn handle_paste(&mut self, text: String) {
+        self.get_active_page_component_mut().handle_paste(text)
+    }
     
     fn check(&mut self) {
         self.main_page.check();
         self.editor_page.check();
+        self.output_page.check();
+        self.settings_page.check();
+        self.connect_page.check();
+        self.find_page.check();
         self.help_line.check();
     }
 }
@@ -116,7 +165,19 @@ impl<'a> ComponentRender<()> for AppRouter<'a> {
                 .render(frame, main_page::RenderProps { area: page_area }),
             ActivePage::EditorPage => self
                 .editor_page
-                .render(frame, editor_page::RenderProps { area: page_area })
+                .render(frame, editor_page::RenderProps { area: page_area }),
+            ActivePage::OutputPage => self
+                .output_page
+                .render(frame, output_page::RenderProps { area: page_area }),
+            ActivePage::SettingsPage => self
+                .settings_page
+                .render(frame, settings_page::RenderProps { area: page_area }),
+            ActivePage::ConnectPage => self
+                .connect_page
+                .render(frame, connect_page::RenderProps { area: page_area }),
+            ActivePage::FindPage => self
+                .find_page
+                .render(frame, find_page::RenderProps { area: page_area }),
         }
         self.help_line.render(
             frame,
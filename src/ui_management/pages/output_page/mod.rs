@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+mod output_page;
+
+pub use output_page::{OutputPage, RenderProps};
@@ -0,0 +1,178 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    text::Line,
+    widgets::{Block, Paragraph},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    keymap::{Command, KeyChord, KeyContext, Keymap, PendingKeys, Resolved},
+    state_management::{action::Action, State},
+    theme::Theme,
+    ui_management::components::{Component, ComponentRender},
+};
+
+struct Props {
+    /// Finished lines and the one being written
+    lines: Arc<VecDeque<String>>,
+    current: String,
+    subshell_busy: bool,
+    keymap: Arc<Keymap>,
+    theme: Arc<Theme>,
+}
+
+impl Props {
+    fn line_count(&self) -> usize {
+        self.lines.len() + usize::from(!self.current.is_empty())
+    }
+}
+
+impl From<&State> for Props {
+    fn from(value: &State) -> Self {
+        Props {
+            lines: value.terminal_output.lines(),
+            current: String::from(value.terminal_output.current()),
+            subshell_busy: value.subshell_busy,
+            keymap: value.keymap.clone(),
+            theme: value.theme.clone(),
+        }
+    }
+}
+
+/// Output of commands run from command line, shown instead of panels
+pub struct OutputPage {
+    props: Props,
+    /// Number of lines scrolled up from the bottom
+    scroll: usize,
+    pending_keys: PendingKeys,
+    pub action_tx: UnboundedSender<Action>,
+}
+
+impl Component for OutputPage {
+    fn new(state: &State, action_tx: UnboundedSender<Action>) -> Self
+    where
+        Self: Sized,
+    {
+        OutputPage {
+            props: Props::from(state),
+            scroll: 0,
+            pending_keys: PendingKeys::default(),
+            action_tx,
+        }
+    }
+
+    fn move_with_state(self, state: &State) -> Self
+    where
+        Self: Sized,
+    {
+        let props = Props::from(state);
+        // stay on the same lines while new output arrives
+        let scroll = if self.scroll > 0 {
+            self.scroll + props.line_count().saturating_sub(self.props.line_count())
+        } else {
+            0
+        };
+        OutputPage {
+            props,
+            scroll,
+            ..self
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Terminal output"
+    }
+
+    fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let keymap = self.props.keymap.clone();
+        if self.props.subshell_busy {
+            // running program gets all keys, only single key toggles output
+            let keys = [KeyChord::from(key)];
+            if keymap.resolve(KeyContext::Viewer, &keys) == Resolved::Command(Command::ToggleOutput) {
+                self.scroll = 0;
+                let _ = self.action_tx.send(Action::ToggleOutput);
+            } else if let Some(input) = terminal_input(key) {
+                self.scroll = 0;
+                let _ = self.action_tx.send(Action::SubshellInput(input));
+            }
+            return;
+        }
+        let Resolved::Command(command) = self.pending_keys.resolve(&keymap, KeyContext::Viewer, key)
+        else {
+            return;
+        };
+        let max_scroll = self.props.line_count().saturating_sub(1);
+        match command {
+            Command::ToggleOutput | Command::Close => {
+                self.scroll = 0;
+                let _ = self.action_tx.send(Action::ToggleOutput);
+            }
+            Command::CursorUp => self.scroll = (self.scroll + 1).min(max_scroll),
+            Command::CursorDown => self.scroll = self.scroll.saturating_sub(1),
+            Command::PageUp => self.scroll = (self.scroll + 20).min(max_scroll),
+            Command::PageDown => self.scroll = self.scroll.saturating_sub(20),
+            Command::Home => self.scroll = max_scroll,
+            Command::End => self.scroll = 0,
+            _ => {}
+        }
+    }
+
+    fn check(&mut self) {}
+}
+
+/// Bytes terminal would send for the key
+fn terminal_input(key: crossterm::event::KeyEvent) -> Option<String> {
+    let input = match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let c = c.to_ascii_lowercase();
+            if !c.is_ascii_lowercase() {
+                return None;
+            }
+            String::from((c as u8 - b'a' + 1) as char)
+        }
+        KeyCode::Char(c) => String::from(c),
+        KeyCode::Enter => String::from("\r"),
+        KeyCode::Backspace => String::from("\x7f"),
+        KeyCode::Tab => String::from("\t"),
+        KeyCode::Esc => String::from("\x1b"),
+        KeyCode::Up => String::from("\x1b[A"),
+        KeyCode::Down => String::from("\x1b[B"),
+        KeyCode::Right => String::from("\x1b[C"),
+        KeyCode::Left => String::from("\x1b[D"),
+        KeyCode::Home => String::from("\x1b[H"),
+        KeyCode::End => String::from("\x1b[F"),
+        KeyCode::Delete => String::from("\x1b[3~"),
+        _ => return None,
+    };
+    Some(input)
+}
+
+pub struct RenderProps {
+    pub area: Rect,
+}
+
+impl ComponentRender<RenderProps> for OutputPage {
+    fn render(&self, frame: &mut ratatui::prelude::Frame, props: RenderProps) {
+        let height = props.area.height as usize;
+        let bottom = self.props.line_count().saturating_sub(self.scroll);
+        let top = bottom.saturating_sub(height);
+        let lines = &self.props.lines;
+        let text: Vec<Line> = lines
+            .iter()
+            .chain(Some(&self.props.current).filter(|current| !current.is_empty()))
+            .skip(top)
+            .take(bottom - top)
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        let paragraph = Paragraph::new(text)
+            .block(Block::default())
+            .style(self.props.theme.output.style());
+        frame.render_widget(paragraph, props.area);
+    }
+}
@@ -1,40 +1,69 @@
 use std::{
-    io::{self, Stdout},
+    fs,
+    io::{self, Read, Stdout, Write},
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
     time::Duration,
 };
 
 use anyhow::Context;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, Event, EventStream},
+    event::{
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, EventStream, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
 };
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use ratatui::prelude::*;
 use tokio::sync::{
     broadcast,
-    mpsc::{self, UnboundedReceiver},
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
 };
 use tokio_stream::StreamExt;
 
 use crate::{
-    state_management::{action::Action, State},
+    config::Settings,
+    state_management::{action::Action, ExternalCommand, PanelPosition, State},
     termination::Interrupted,
     ui_management::components::{Component, ComponentRender},
 };
 
 use super::pages::AppRouter;
 
-const RENDERING_TICK_RATE: Duration = Duration::from_millis(250);
-
 pub struct UiManager {
     action_tx: mpsc::UnboundedSender<Action>,
+    tick_rate: Duration,
+    /// Capture mouse events instead of leaving them to terminal
+    mouse: bool,
 }
 
 impl UiManager {
-    pub fn new() -> (Self, UnboundedReceiver<Action>) {
+    pub fn new(settings: &Settings, mouse: bool) -> (Self, UnboundedReceiver<Action>) {
         let (action_tx, action_rx) = mpsc::unbounded_channel();
 
-        (Self { action_tx }, action_rx)
+        (
+            Self {
+                action_tx,
+                tick_rate: settings.tick_rate(),
+                mouse,
+            },
+            action_rx,
+        )
+    }
+
+    /// Queues action as if it came from UI, e.g. to open file given on command line
+    pub fn dispatch(&self, action: Action) {
+        let _ = self.action_tx.send(action);
     }
 
     pub async fn main_loop(
@@ -49,8 +78,9 @@ impl UiManager {
             AppRouter::new(&state, self.action_tx.clone())
         };
 
-        let mut terminal = setup_terminal()?;
-        let mut ticker = tokio::time::interval(RENDERING_TICK_RATE);
+        let mut terminal = setup_terminal(self.mouse)?;
+        let mut tick_rate = self.tick_rate;
+        let mut ticker = tokio::time::interval(tick_rate);
         let mut crossterm_events = EventStream::new();
 
         let result: anyhow::Result<Interrupted> = loop {
@@ -62,12 +92,37 @@ impl UiManager {
                     Some(Ok(Event::Key(key)))  => {
                         app_router.handle_key_event(key);
                     },
+                    Some(Ok(Event::Paste(text))) => {
+                        app_router.handle_paste(text);
+                    },
+                    Some(Ok(Event::Resize(cols, rows))) => {
+                        let _ = self.action_tx.send(Action::Resize(cols, rows));
+                    },
                     None => break Ok(Interrupted::UserInt),
                     _ => (),
                 },
                 // Handle state updates
                 Some(state) = state_rx.recv() => {
                     app_router = app_router.move_with_state(&state);
+                    if state.settings.tick_rate() != tick_rate {
+                        tick_rate = state.settings.tick_rate();
+                        ticker = tokio::time::interval(tick_rate);
+                    }
+                    if let Some(command) = &state.external_command {
+                        // event stream would steal input from the program, so it is recreated afterwards
+                        drop(crossterm_events);
+                        let result = run_external(&mut terminal, command, &self.action_tx, self.mouse);
+                        crossterm_events = EventStream::new();
+                        match result {
+                            Ok(()) => {
+                                let _ = self.action_tx.send(Action::Reload(PanelPosition::L));
+                                let _ = self.action_tx.send(Action::Reload(PanelPosition::R));
+                            },
+                            Err(err) => {
+                                let _ = self.action_tx.send(Action::Error(err.to_string()));
+                            },
+                        }
+                    }
                 },
                 // Catch and handle interrupt signal to gracefully shutdown
                 Ok(interrupted) = interrupt_rx.recv() => {
@@ -90,12 +145,150 @@ impl UiManager {
     }
 }
 
-fn setup_terminal() -> anyhow::Result<Terminal<CrosstermBackend<Stdout>>> {
+/// Suspends UI, runs program in foreground and brings UI back
+fn run_external(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    command: &ExternalCommand,
+    action_tx: &UnboundedSender<Action>,
+    mouse: bool,
+) -> anyhow::Result<()> {
+    restore_terminal(terminal)?;
+    let result = match command.capture {
+        true => run_captured(command, action_tx),
+        false => run_foreground(command),
+    };
+    if let Some(dir) = &command.temp_dir {
+        let _ = fs::remove_dir_all(dir);
+    }
+    *terminal = setup_terminal(mouse)?;
+    result
+}
+
+fn run_foreground(command: &ExternalCommand) -> anyhow::Result<()> {
+    let status = Command::new(&command.program)
+        .args(&command.args)
+        .current_dir(&command.cwd)
+        .status()
+        .with_context(|| format!("could not run {}", command.program))?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", command.program, status);
+    }
+    Ok(())
+}
+
+/// Runs program in pseudo terminal, so it still gets keys and can tell it's
+/// interactive, with its output shown in terminal and copied to scrollback as it
+/// arrives. Exit status is added to scrollback when program fails.
+fn run_captured(
+    command: &ExternalCommand,
+    action_tx: &UnboundedSender<Action>,
+) -> anyhow::Result<()> {
+    let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+    let pair = native_pty_system().openpty(PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    })?;
+    let mut builder = CommandBuilder::new(&command.program);
+    builder.args(&command.args);
+    builder.cwd(&command.cwd);
+    let mut child = pair
+        .slave
+        .spawn_command(builder)
+        .with_context(|| format!("could not run {}", command.program))?;
+    // output ends when the program and its children close their side
+    drop(pair.slave);
+    let output = tee(pair.master.try_clone_reader()?, io::stdout(), action_tx.clone());
+    // pseudo terminal echoes keys and handles Ctrl+C itself
+    enable_raw_mode()?;
+    let done = Arc::new(AtomicBool::new(false));
+    let input = forward_input(pair.master.take_writer()?, done.clone());
+    let status = child.wait();
+    done.store(true, Ordering::Relaxed);
+    let _ = input.join();
+    let _ = output.join();
+    let status = status.with_context(|| format!("could not run {}", command.program))?;
+    if !status.success() {
+        let _ = action_tx.send(Action::CommandOutput(format!("[{}]\n", status)));
+    }
+    Ok(())
+}
+
+/// Copies keys typed in terminal to program until it's `done`. Terminal is polled,
+/// so the thread takes no keys meant for UI after that.
+#[cfg(unix)]
+fn forward_input(mut to: Box<dyn Write + Send>, done: Arc<AtomicBool>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf = [0u8; 1024];
+        while !done.load(Ordering::Relaxed) {
+            let mut stdin = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut stdin, 1, 50) } <= 0 {
+                continue;
+            }
+            let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 || to.write_all(&buf[..n as usize]).is_err() {
+                break;
+            }
+            let _ = to.flush();
+        }
+    })
+}
+
+/// Terminal can't be polled, program gets no keys
+#[cfg(not(unix))]
+fn forward_input(_to: Box<dyn Write + Send>, _done: Arc<AtomicBool>) -> JoinHandle<()> {
+    thread::spawn(|| {})
+}
+
+fn tee(
+    mut from: impl Read + Send + 'static,
+    mut to: impl Write + Send + 'static,
+    action_tx: UnboundedSender<Action>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        // bytes of utf-8 character split between reads
+        let mut pending: Vec<u8> = vec![];
+        while let Ok(n) = from.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let _ = to.write_all(&buf[..n]);
+            let _ = to.flush();
+            pending.extend_from_slice(&buf[..n]);
+            let valid = match std::str::from_utf8(&pending) {
+                Ok(text) => text.len(),
+                Err(err) if err.error_len().is_none() => err.valid_up_to(),
+                Err(_) => pending.len(),
+            };
+            let text = String::from_utf8_lossy(&pending[..valid]).to_string();
+            pending.drain(..valid);
+            let _ = action_tx.send(Action::CommandOutput(text));
+        }
+    })
+}
+
+fn setup_terminal(mouse: bool) -> anyhow::Result<Terminal<CrosstermBackend<Stdout>>> {
     let mut stdout = io::stdout();
 
     enable_raw_mode()?;
 
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
+    if mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    // keys like Ctrl+1 are reported only with enhanced keyboard protocol
+    if supports_keyboard_enhancement().unwrap_or(false) {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+    }
 
     Ok(Terminal::new(CrosstermBackend::new(stdout))?)
 }
@@ -103,10 +296,13 @@ fn setup_terminal() -> anyhow::Result<Terminal<CrosstermBackend<Stdout>>> {
 fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> anyhow::Result<()> {
     disable_raw_mode()?;
 
+    // terminals without keyboard enhancement ignore it
     execute!(
         terminal.backend_mut(),
+        PopKeyboardEnhancementFlags,
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
 
     Ok(terminal.show_cursor()?)
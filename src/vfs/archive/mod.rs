@@ -0,0 +1,278 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+};
+
+use super::{local_copy, DirEntry, FileInfo, FileWriter, Vfs, VfsHandle};
+
+pub use self::pack::{pack, PackOptions};
+
+mod pack;
+mod tar;
+mod zip;
+
+/// Reads content of archive entries
+trait EntryReader: Send + Sync {
+    fn open(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>>;
+}
+
+/// Compression of whole tar stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+/// Kind of archive, told by file name
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Zip,
+    Tar(Compression),
+}
+
+const EXTENSIONS: &[(&str, Format)] = &[
+    (".zip", Format::Zip),
+    (".jar", Format::Zip),
+    (".tar", Format::Tar(Compression::None)),
+    (".tar.gz", Format::Tar(Compression::Gzip)),
+    (".tgz", Format::Tar(Compression::Gzip)),
+    (".tar.bz2", Format::Tar(Compression::Bzip2)),
+    (".tbz2", Format::Tar(Compression::Bzip2)),
+    (".tbz", Format::Tar(Compression::Bzip2)),
+    (".tar.xz", Format::Tar(Compression::Xz)),
+    (".txz", Format::Tar(Compression::Xz)),
+    (".tar.zst", Format::Tar(Compression::Zstd)),
+    (".tzst", Format::Tar(Compression::Zstd)),
+];
+
+impl Format {
+    fn of(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        EXTENSIONS
+            .iter()
+            .find(|(extension, _)| name.ends_with(extension))
+            .map(|(_, format)| *format)
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    Format::of(path).is_some()
+}
+
+/// Opens archive file of `parent` filesystem as read-only filesystem.
+/// Archives on other filesystems are copied to local disk first.
+pub fn open_archive(parent: &VfsHandle, path: &Path) -> anyhow::Result<VfsHandle> {
+    let Some(format) = Format::of(path) else {
+        anyhow::bail!("{} is not a known archive", path.display());
+    };
+    let file = local_copy(parent, path)?;
+    let (index, reader) = match format {
+        Format::Zip => self::zip::open(&file)?,
+        Format::Tar(compression) => self::tar::open(&file, compression)?,
+    };
+    Ok(VfsHandle::new(ArchiveFs {
+        parent: parent.clone(),
+        archive: path.to_path_buf(),
+        entries: index.entries,
+        links: index.links,
+        reader,
+    }))
+}
+
+/// Absolute path of entry inside archive. Names leaving the archive, absolute ones
+/// or ones with `..`, give None.
+fn entry_path(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::from("/");
+    for component in name.components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    path.parent().is_some().then_some(path)
+}
+
+/// Entries of archive by absolute path inside it, with directories archive
+/// only implies by paths of their files
+#[derive(Debug, Default)]
+struct Index {
+    entries: BTreeMap<PathBuf, FileInfo>,
+    /// Targets of symbolic links
+    links: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl Index {
+    /// Adds entry and its missing parent directories. Entry below a file or link,
+    /// e.g. one meant to be written through a link, is left out.
+    fn insert(&mut self, path: PathBuf, info: FileInfo) {
+        if path
+            .ancestors()
+            .skip(1)
+            .any(|dir| self.entries.get(dir).is_some_and(|parent| !parent.is_dir))
+        {
+            return;
+        }
+        for dir in path.ancestors().skip(1) {
+            if dir.parent().is_none() {
+                break;
+            }
+            self.entries.entry(dir.to_path_buf()).or_insert(FileInfo {
+                is_dir: true,
+                ..Default::default()
+            });
+        }
+        self.entries.insert(path, info);
+    }
+}
+
+/// Read-only view of archive file. Entries are indexed once when archive is opened.
+struct ArchiveFs {
+    /// Filesystem archive file is on and its path there
+    parent: VfsHandle,
+    archive: PathBuf,
+    entries: BTreeMap<PathBuf, FileInfo>,
+    links: BTreeMap<PathBuf, PathBuf>,
+    reader: Box<dyn EntryReader>,
+}
+
+impl ArchiveFs {
+    fn read_only(&self) -> anyhow::Error {
+        anyhow::anyhow!("{} is read only", self.archive.display())
+    }
+}
+
+impl Vfs for ArchiveFs {
+    fn prefix(&self) -> String {
+        format!("{}{}:", self.parent.prefix(), self.archive.display())
+    }
+
+    fn parent(&self) -> Option<(VfsHandle, PathBuf)> {
+        Some((self.parent.clone(), self.archive.clone()))
+    }
+
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<DirEntry>> {
+        if !self.stat(path)?.is_dir {
+            anyhow::bail!("{} is not a directory", path.display());
+        }
+        Ok(self
+            .entries
+            .iter()
+            .filter(|(entry, _)| entry.parent() == Some(path))
+            .filter_map(|(entry, info)| {
+                Some(DirEntry {
+                    name: entry.file_name()?.to_string_lossy().to_string(),
+                    info: info.clone(),
+                })
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<FileInfo> {
+        if path.parent().is_none() {
+            return Ok(FileInfo {
+                is_dir: true,
+                ..Default::default()
+            });
+        }
+        match self.entries.get(path) {
+            Some(info) => Ok(info.clone()),
+            None => anyhow::bail!("{}: No such file in archive", path.display()),
+        }
+    }
+
+    fn open_read(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>> {
+        if self.stat(path)?.is_dir {
+            anyhow::bail!("{} is a directory", path.display());
+        }
+        self.reader.open(path)
+    }
+
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        match self.links.get(path) {
+            Some(target) => Ok(target.clone()),
+            None => anyhow::bail!("{} is not a symbolic link", path.display()),
+        }
+    }
+
+    fn open_write(&self, _path: &Path) -> anyhow::Result<Box<dyn FileWriter>> {
+        Err(self.read_only())
+    }
+
+    fn mkdir(&self, _path: &Path) -> anyhow::Result<()> {
+        Err(self.read_only())
+    }
+
+    fn remove(&self, _path: &Path) -> anyhow::Result<()> {
+        Err(self.read_only())
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> anyhow::Result<()> {
+        Err(self.read_only())
+    }
+}
+
+/// Runs `produce` on its own thread, handing what it writes to returned reader.
+/// Lets entries be streamed from archive readers that can't outlive a borrow.
+fn pipe_reader(
+    produce: impl FnOnce(&mut dyn Write) -> anyhow::Result<()> + Send + 'static,
+) -> Box<dyn Read + Send> {
+    let (tx, rx) = mpsc::sync_channel(4);
+    thread::spawn(move || {
+        let mut writer = PipeWriter(tx.clone());
+        if let Err(err) = produce(&mut writer) {
+            let _ = tx.send(Err(io::Error::other(format!("{:#}", err))));
+        }
+    });
+    Box::new(PipeReader {
+        rx,
+        chunk: vec![],
+        pos: 0,
+    })
+}
+
+struct PipeWriter(SyncSender<io::Result<Vec<u8>>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(Ok(buf.to_vec()))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct PipeReader {
+    rx: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.pos = 0;
+                }
+                // producer has finished
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::vfs::{self, FileInfo, VfsHandle};
+
+use super::{local_copy, Format};
+
+/// What `pack` puts into which archive
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// Filesystem of packed files
+    pub source: VfsHandle,
+    /// Files and directories packed with their content
+    pub files: Vec<PathBuf>,
+    /// Filesystem archive is written to
+    pub target: VfsHandle,
+    /// Archive file, its format is told by the name
+    pub archive: PathBuf,
+    /// Directory inside archive files are put in
+    pub base: PathBuf,
+    /// From 1, fastest, to 9, smallest archive
+    pub level: u32,
+    /// Adds files to existing archive instead of creating new one
+    pub append: bool,
+}
+
+/// File going into archive
+pub(super) struct NewEntry {
+    pub source: PathBuf,
+    /// Absolute path inside archive
+    pub path: PathBuf,
+    pub info: FileInfo,
+}
+
+/// Reads content of packed files, reporting progress
+pub(super) struct Packer<'a> {
+    source: VfsHandle,
+    done: u64,
+    total: u64,
+    report: &'a mut dyn FnMut(&Path, u64, u64),
+}
+
+impl<'a> Packer<'a> {
+    pub fn open(&mut self, entry: &NewEntry) -> anyhow::Result<ProgressReader<'_, 'a>> {
+        let inner = self
+            .source
+            .open_read(&entry.source)
+            .with_context(|| format!("Can't read {}", entry.source.display()))?;
+        Ok(ProgressReader {
+            inner,
+            path: entry.source.clone(),
+            packer: self,
+        })
+    }
+
+    pub fn read_link(&self, entry: &NewEntry) -> anyhow::Result<PathBuf> {
+        self.source.read_link(&entry.source)
+    }
+}
+
+pub(super) struct ProgressReader<'p, 'a> {
+    inner: Box<dyn Read + Send>,
+    path: PathBuf,
+    packer: &'p mut Packer<'a>,
+}
+
+impl Read for ProgressReader<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        let packer = &mut self.packer;
+        packer.done += len as u64;
+        (packer.report)(&self.path, packer.done, packer.total);
+        Ok(len)
+    }
+}
+
+/// Packs files into archive, calling `report` with file being read, bytes read
+/// and bytes to read. Archive is built in temporary directory and copied to its
+/// place when complete. Pipes, sockets and devices are left out.
+pub fn pack(options: &PackOptions, report: &mut dyn FnMut(&Path, u64, u64)) -> anyhow::Result<()> {
+    let Some(format) = Format::of(&options.archive) else {
+        anyhow::bail!("{} is not a known archive type", options.archive.display());
+    };
+    if !options.append && options.target.stat(&options.archive).is_ok() {
+        anyhow::bail!("{} already exists", options.archive.display());
+    }
+    let mut entries = vec![];
+    for file in &options.files {
+        let Some(name) = file.file_name() else {
+            continue;
+        };
+        collect(&options.source, file, options.base.join(name), &mut entries)?;
+    }
+    let total = entries
+        .iter()
+        .filter(|entry| !entry.info.is_dir && !entry.info.is_symlink)
+        .map(|entry| entry.info.size)
+        .sum();
+    let mut packer = Packer {
+        source: options.source.clone(),
+        done: 0,
+        total,
+        report,
+    };
+
+    let existing = match options.append {
+        true => Some(local_copy(&options.target, &options.archive)?),
+        false => None,
+    };
+    let dir = vfs::new_temp_dir()?;
+    let temp = dir.join(options.archive.file_name().unwrap_or_default());
+    let result = File::create(&temp)
+        .map_err(anyhow::Error::from)
+        .and_then(|out| match format {
+            Format::Zip => super::zip::write(
+                out,
+                existing.as_deref(),
+                &entries,
+                options.level,
+                &mut packer,
+            ),
+            Format::Tar(compression) => super::tar::write(
+                out,
+                compression,
+                existing.as_deref(),
+                &entries,
+                options.level,
+                &mut packer,
+            ),
+        })
+        .and_then(|()| {
+            vfs::copy(
+                &VfsHandle::default(),
+                &temp,
+                &options.target,
+                &options.archive,
+                &mut |_, _| {},
+            )
+        });
+    let _ = fs::remove_dir_all(dir);
+    result
+}
+
+fn collect(
+    vfs: &VfsHandle,
+    source: &Path,
+    path: PathBuf,
+    entries: &mut Vec<NewEntry>,
+) -> anyhow::Result<()> {
+    let info = vfs
+        .stat(source)
+        .with_context(|| format!("Can't read {}", source.display()))?;
+    if info.pipe || info.socket || info.device {
+        return Ok(());
+    }
+    let is_dir = info.is_dir && !info.is_symlink;
+    entries.push(NewEntry {
+        source: source.to_path_buf(),
+        path: path.clone(),
+        info,
+    });
+    if is_dir {
+        for entry in vfs.list(source)? {
+            collect(
+                vfs,
+                &source.join(&entry.name),
+                path.join(&entry.name),
+                entries,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Name of entry as stored in archive, relative and with `/` separators
+pub(super) fn entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_dir::TestDir, vfs::open_archive};
+
+    fn names(archive: &VfsHandle, path: &str) -> Vec<String> {
+        let mut names: Vec<String> = archive
+            .list(Path::new(path))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn pack_test() {
+        let dir = TestDir::new("pack");
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/sub")).unwrap();
+        fs::write(root.join("src/a.txt"), "alpha").unwrap();
+        fs::write(root.join("src/sub/b.txt"), "beta").unwrap();
+        fs::write(root.join("notes.txt"), "notes").unwrap();
+        let local = VfsHandle::default();
+
+        for name in [
+            "test.zip",
+            "test.tar.gz",
+            "test.tar.xz",
+            "test.tar.zst",
+            "test.tar.bz2",
+        ] {
+            let mut options = PackOptions {
+                source: local.clone(),
+                files: vec![root.join("src")],
+                target: local.clone(),
+                archive: root.join(name),
+                base: PathBuf::from("/"),
+                level: 6,
+                append: false,
+            };
+            let mut last = (0, 0);
+            pack(&options, &mut |_, done, total| last = (done, total)).unwrap();
+            assert_eq!(last, (9, 9));
+            assert!(pack(&options, &mut |_, _, _| {}).is_err());
+
+            options.files = vec![root.join("notes.txt"), root.join("src/a.txt")];
+            options.base = PathBuf::from("/src");
+            options.append = true;
+            pack(&options, &mut |_, _, _| {}).unwrap();
+
+            let archive = open_archive(&local, &root.join(name)).unwrap();
+            assert_eq!(
+                names(&archive, "/src"),
+                vec!["a.txt", "notes.txt", "sub"],
+                "{}",
+                name
+            );
+            let mut content = String::new();
+            archive
+                .open_read(Path::new("/src/sub/b.txt"))
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!(content, "beta");
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use tar::{Archive, Builder, EntryType, Header};
+
+use crate::vfs::{self, FileInfo};
+
+use super::{
+    entry_path,
+    pack::{entry_name, NewEntry, Packer},
+    pipe_reader, Compression, EntryReader, Index,
+};
+
+/// Entries of local tar file. Compressed archive is decompressed once into a
+/// temporary file, entries are then read from their position in it.
+struct TarEntries {
+    /// Uncompressed tar file
+    file: PathBuf,
+    /// Position and size of entry data in `file`, sparse entries are left out
+    data: HashMap<PathBuf, (u64, u64)>,
+    /// Hard links and the entries holding their data
+    hard_links: HashMap<PathBuf, PathBuf>,
+    /// Holds decompressed copy, removed with the reader
+    temp_dir: Option<PathBuf>,
+}
+
+impl EntryReader for TarEntries {
+    fn open(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>> {
+        let wanted = self
+            .hard_links
+            .get(path)
+            .cloned()
+            .unwrap_or(path.to_path_buf());
+        if let Some((position, size)) = self.data.get(&wanted) {
+            let mut file = File::open(&self.file)?;
+            file.seek(SeekFrom::Start(*position))?;
+            return Ok(Box::new(BufReader::new(file).take(*size)));
+        }
+        // sparse entries are assembled by going through the archive
+        let file = self.file.clone();
+        Ok(pipe_reader(move |writer| {
+            let mut archive = Archive::new(decoder(&file, Compression::None)?);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if entry_path(&entry.path()?).as_ref() == Some(&wanted) {
+                    io::copy(&mut entry, writer)?;
+                    return Ok(());
+                }
+            }
+            anyhow::bail!("{}: No such file in archive", wanted.display())
+        }))
+    }
+}
+
+impl Drop for TarEntries {
+    fn drop(&mut self) {
+        if let Some(dir) = self.temp_dir.take() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Indexes tar file on local disk by reading it through once, compressed one after
+/// decompressing it. Entries with absolute paths or `..` are left out, as are hard
+/// links to such entries.
+pub(super) fn open(
+    file: &Path,
+    compression: Compression,
+) -> anyhow::Result<(Index, Box<dyn EntryReader>)> {
+    let (file, temp_dir) = match compression {
+        Compression::None => (file.to_path_buf(), None),
+        _ => {
+            let dir = vfs::new_temp_dir()?;
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            let copy = dir.join(format!("{}.tar", name));
+            let result = File::options()
+                .write(true)
+                .create_new(true)
+                .open(&copy)
+                .map_err(anyhow::Error::from)
+                .and_then(|out| {
+                    io::copy(&mut decoder(file, compression)?, &mut BufWriter::new(out))?;
+                    Ok(())
+                });
+            if let Err(err) = result {
+                let _ = fs::remove_dir_all(&dir);
+                return Err(err);
+            }
+            (copy, Some(dir))
+        }
+    };
+    let mut reader = TarEntries {
+        file,
+        data: HashMap::new(),
+        hard_links: HashMap::new(),
+        temp_dir,
+    };
+    let mut archive = Archive::new(File::open(&reader.file)?);
+    let mut index = Index::default();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let Some(path) = entry_path(&entry.path()?) else {
+            continue;
+        };
+        let header = entry.header();
+        let mode = header.mode().ok().map(|mode| mode & 0o7777);
+        let entry_type = header.entry_type();
+        let mut info = FileInfo {
+            is_dir: entry_type.is_dir(),
+            is_symlink: entry_type.is_symlink(),
+            pipe: entry_type.is_fifo(),
+            device: entry_type.is_character_special() || entry_type.is_block_special(),
+            size: header.size().unwrap_or_default(),
+            modified: header
+                .mtime()
+                .ok()
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            mode,
+            ..Default::default()
+        };
+        match entry_type {
+            EntryType::Symlink => {
+                let Some(target) = entry.link_name()? else {
+                    continue;
+                };
+                index.links.insert(path.clone(), target.to_path_buf());
+            }
+            EntryType::Link => {
+                let Some(target) = entry.link_name()?.as_deref().and_then(entry_path) else {
+                    continue;
+                };
+                let Some(target_info) = index.entries.get(&target) else {
+                    continue;
+                };
+                info = FileInfo {
+                    modified: info.modified,
+                    ..target_info.clone()
+                };
+                reader.hard_links.insert(path.clone(), target);
+            }
+            EntryType::GNUSparse => {}
+            _ => {
+                let data = (entry.raw_file_position(), entry.size());
+                reader.data.insert(path.clone(), data);
+            }
+        }
+        info.executable =
+            !info.is_dir && !info.is_symlink && mode.is_some_and(|mode| mode & 0o111 != 0);
+        index.insert(path, info);
+    }
+    Ok((index, Box::new(reader)))
+}
+
+/// Writes tar archive with entries of `existing` one, if given, and new entries,
+/// which replace old entries with the same path
+pub(super) fn write(
+    out: File,
+    compression: Compression,
+    existing: Option<&Path>,
+    entries: &[NewEntry],
+    level: u32,
+    packer: &mut Packer,
+) -> anyhow::Result<()> {
+    let mut tar = Builder::new(encoder(out, compression, level)?);
+    if let Some(existing) = existing {
+        let replaced: HashSet<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+        let mut old = Archive::new(decoder(existing, compression)?);
+        for entry in old.entries()? {
+            let mut entry = entry?;
+            let Some(path) = entry_path(&entry.path()?) else {
+                continue;
+            };
+            if replaced.contains(path.as_path()) {
+                continue;
+            }
+            let mut header = entry.header().clone();
+            let link = entry.link_name()?.map(Cow::into_owned);
+            match link {
+                Some(target)
+                    if header.entry_type().is_symlink() || header.entry_type().is_hard_link() =>
+                {
+                    tar.append_link(&mut header, entry_name(&path), target)?
+                }
+                _ => tar.append_data(&mut header, entry_name(&path), &mut entry)?,
+            }
+        }
+    }
+    for entry in entries {
+        let info = &entry.info;
+        let mut header = Header::new_gnu();
+        let default_mode = if info.is_dir { 0o755 } else { 0o644 };
+        header.set_mode(info.mode.unwrap_or(default_mode));
+        let mtime = info
+            .modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+        header.set_mtime(mtime.unwrap_or_default().as_secs());
+        let name = entry_name(&entry.path);
+        if info.is_symlink {
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            tar.append_link(&mut header, name, packer.read_link(entry)?)?;
+        } else if info.is_dir {
+            header.set_entry_type(EntryType::Directory);
+            header.set_size(0);
+            tar.append_data(&mut header, name, io::empty())?;
+        } else {
+            header.set_entry_type(EntryType::Regular);
+            header.set_size(info.size);
+            tar.append_data(&mut header, name, packer.open(entry)?)?;
+        }
+    }
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Compressing writer, `finish` writes end of the stream
+trait Encoder: Write {
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl Encoder for BufWriter<File> {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl Encoder for flate2::write::GzEncoder<BufWriter<File>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl Encoder for bzip2::write::BzEncoder<BufWriter<File>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl Encoder for xz2::write::XzEncoder<BufWriter<File>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl Encoder for zstd::stream::write::Encoder<'static, BufWriter<File>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+/// Compresses tar stream written to the file. Level goes from 1 to 9 as for gzip,
+/// for zstd it is scaled to its levels up to 19.
+fn encoder(file: File, compression: Compression, level: u32) -> anyhow::Result<Box<dyn Encoder>> {
+    let file = BufWriter::new(file);
+    Ok(match compression {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(flate2::write::GzEncoder::new(
+            file,
+            flate2::Compression::new(level),
+        )),
+        Compression::Bzip2 => Box::new(bzip2::write::BzEncoder::new(
+            file,
+            bzip2::Compression::new(level.max(1)),
+        )),
+        Compression::Xz => Box::new(xz2::write::XzEncoder::new(file, level)),
+        Compression::Zstd => Box::new(zstd::stream::write::Encoder::new(
+            file,
+            (level * 19 / 9).max(1) as i32,
+        )?),
+    })
+}
+
+/// Uncompressed tar stream of the file
+fn decoder(file: &Path, compression: Compression) -> anyhow::Result<Box<dyn Read>> {
+    let file = BufReader::new(File::open(file)?);
+    Ok(match compression {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(file)),
+        Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(file)),
+        Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(file)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(file)?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use tar::{Builder, Header};
+
+    use super::*;
+    use crate::{
+        test_dir::TestDir,
+        vfs::{copy, open_archive, VfsHandle},
+    };
+
+    fn header(entry_type: EntryType, size: u64, mode: u32) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(mode);
+        header.set_mtime(1_700_000_000);
+        header
+    }
+
+    #[test]
+    fn tar_test() {
+        let dir = TestDir::new("tar");
+        let root = dir.path();
+        let file = root.join("build.tar.gz");
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(&file).unwrap(), Default::default());
+        let mut tar = Builder::new(encoder);
+        let content = b"#!/bin/sh\necho built\n";
+        tar.append_data(
+            &mut header(EntryType::Regular, content.len() as u64, 0o755),
+            "bin/build.sh",
+            &content[..],
+        )
+        .unwrap();
+        tar.append_link(
+            &mut header(EntryType::Symlink, 0, 0o777),
+            "bin/latest",
+            "build.sh",
+        )
+        .unwrap();
+        tar.append_link(
+            &mut header(EntryType::Link, 0, 0o755),
+            "bin/copy.sh",
+            "bin/build.sh",
+        )
+        .unwrap();
+        // builder refuses to write traversal entries, so the name is set by hand
+        let mut escape = header(EntryType::Regular, 4, 0o644);
+        escape.as_gnu_mut().unwrap().name[..13].copy_from_slice(b"../escape.txt");
+        escape.set_cksum();
+        tar.append(&escape, &b"evil"[..]).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let local = VfsHandle::default();
+        let archive = open_archive(&local, &file).unwrap();
+        let names: Vec<String> = archive
+            .list(Path::new("/"))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["bin"]);
+        let info = archive.stat(Path::new("/bin/build.sh")).unwrap();
+        assert!(info.executable);
+        assert_eq!(info.size, content.len() as u64);
+        assert_eq!(
+            info.modified,
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert!(archive.stat(Path::new("/bin/latest")).unwrap().is_symlink);
+        let mut copied = String::new();
+        archive
+            .open_read(Path::new("/bin/copy.sh"))
+            .unwrap()
+            .read_to_string(&mut copied)
+            .unwrap();
+        assert_eq!(copied.as_bytes(), content);
+
+        copy(&archive, Path::new("/bin"), &local, &root.join("bin"), &mut |_, _| {}).unwrap();
+        assert_eq!(fs::read(root.join("bin/build.sh")).unwrap(), content);
+        assert!(!root.join("escape.txt").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(root.join("bin/build.sh"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+            assert_eq!(
+                fs::read_link(root.join("bin/latest")).unwrap(),
+                PathBuf::from("build.sh")
+            );
+        }
+    }
+}
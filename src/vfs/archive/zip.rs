@@ -0,0 +1,247 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use zip::{write::FileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use crate::vfs::FileInfo;
+
+use super::{
+    entry_path,
+    pack::{entry_name, NewEntry, Packer},
+    pipe_reader, EntryReader, Index,
+};
+
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Entries of local zip file, read by their index in the archive
+struct ZipEntries {
+    file: PathBuf,
+    indexes: HashMap<PathBuf, usize>,
+}
+
+impl EntryReader for ZipEntries {
+    fn open(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>> {
+        let Some(&index) = self.indexes.get(path) else {
+            anyhow::bail!("{}: No such file in archive", path.display());
+        };
+        let file = self.file.clone();
+        Ok(pipe_reader(move |writer| {
+            let mut archive = ZipArchive::new(File::open(file)?)?;
+            io::copy(&mut archive.by_index(index)?, writer)?;
+            Ok(())
+        }))
+    }
+}
+
+/// Indexes zip file on local disk. Entries with absolute paths or `..` are left out.
+pub(super) fn open(file: &Path) -> anyhow::Result<(Index, Box<dyn EntryReader>)> {
+    let mut archive = ZipArchive::new(File::open(file)?)?;
+    let mut index = Index::default();
+    let mut indexes = HashMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let Some(path) = entry_path(Path::new(entry.name())) else {
+            continue;
+        };
+        let mode = entry.unix_mode();
+        let info = FileInfo {
+            is_dir: entry.is_dir(),
+            is_symlink: mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK),
+            executable: !entry.is_dir() && mode.is_some_and(|mode| mode & 0o111 != 0),
+            size: entry.size(),
+            modified: modified(entry.last_modified()),
+            mode: mode.map(|mode| mode & 0o7777),
+            ..Default::default()
+        };
+        if info.is_symlink {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            index.links.insert(path.clone(), PathBuf::from(target));
+        } else if !info.is_dir {
+            indexes.insert(path.clone(), i);
+        }
+        index.insert(path, info);
+    }
+    let reader = ZipEntries {
+        file: file.to_path_buf(),
+        indexes,
+    };
+    Ok((index, Box::new(reader)))
+}
+
+/// Writes zip archive with entries of `existing` one, if given, and new entries,
+/// which replace old entries with the same path
+pub(super) fn write(
+    out: File,
+    existing: Option<&Path>,
+    entries: &[NewEntry],
+    level: u32,
+    packer: &mut Packer,
+) -> anyhow::Result<()> {
+    let mut zip = ZipWriter::new(out);
+    if let Some(existing) = existing {
+        let replaced: HashSet<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+        let mut old = ZipArchive::new(File::open(existing)?)?;
+        for i in 0..old.len() {
+            let entry = old.by_index_raw(i)?;
+            if entry_path(Path::new(entry.name()))
+                .is_some_and(|path| replaced.contains(path.as_path()))
+            {
+                continue;
+            }
+            zip.raw_copy_file(entry)?;
+        }
+    }
+    for entry in entries {
+        let name = entry_name(&entry.path);
+        let mut options = FileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(level as i32))
+            .large_file(entry.info.size >= u32::MAX as u64);
+        if let Some(mode) = entry.info.mode {
+            options = options.unix_permissions(mode);
+        }
+        if let Some(time) = entry.info.modified.and_then(zip_time) {
+            options = options.last_modified_time(time);
+        }
+        if entry.info.is_symlink {
+            let target = packer.read_link(entry)?;
+            zip.add_symlink(name, target.to_string_lossy(), options)?;
+        } else if entry.info.is_dir {
+            zip.add_directory(name, options)?;
+        } else {
+            zip.start_file(name, options)?;
+            io::copy(&mut packer.open(entry)?, &mut zip)?;
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Zip stores local time of the machine archive was made on, taken here as local time
+#[cfg(unix)]
+fn modified(time: DateTime) -> Option<SystemTime> {
+    // SAFETY: all-zero is a valid value of the plain C struct
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = time.year() as i32 - 1900;
+    tm.tm_mon = time.month() as i32 - 1;
+    tm.tm_mday = time.day() as i32;
+    tm.tm_hour = time.hour() as i32;
+    tm.tm_min = time.minute() as i32;
+    tm.tm_sec = time.second() as i32;
+    tm.tm_isdst = -1;
+    // SAFETY: tm is initialized and only read and normalized by mktime
+    let secs = unsafe { libc::mktime(&mut tm) };
+    (secs >= 0).then(|| UNIX_EPOCH + Duration::from_secs(secs as u64))
+}
+
+#[cfg(not(unix))]
+fn modified(_time: DateTime) -> Option<SystemTime> {
+    None
+}
+
+/// Local time for zip entry, zip can't store times before 1980
+#[cfg(unix)]
+fn zip_time(time: SystemTime) -> Option<DateTime> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs() as libc::time_t;
+    // SAFETY: localtime_r only writes to given struct, all zero bytes are a valid `tm`
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return None;
+    }
+    DateTime::from_date_and_time(
+        u16::try_from(tm.tm_year + 1900).ok()?,
+        tm.tm_mon as u8 + 1,
+        tm.tm_mday as u8,
+        tm.tm_hour as u8,
+        tm.tm_min as u8,
+        tm.tm_sec.min(59) as u8,
+    )
+    .ok()
+}
+
+#[cfg(not(unix))]
+fn zip_time(_time: SystemTime) -> Option<DateTime> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Write};
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+    use crate::{
+        test_dir::TestDir,
+        vfs::{copy, open_archive, VfsHandle},
+    };
+
+    #[test]
+    fn zip_test() {
+        let dir = TestDir::new("zip");
+        let root = dir.path();
+        let file = root.join("test.zip");
+        let mut zip = ZipWriter::new(File::create(&file).unwrap());
+        zip.add_directory("docs/", FileOptions::default()).unwrap();
+        zip.start_file(
+            "docs/run.sh",
+            FileOptions::default().unix_permissions(0o755),
+        )
+        .unwrap();
+        zip.write_all(b"echo hello").unwrap();
+        zip.start_file("src/deep/main.rs", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"fn main() {}").unwrap();
+        zip.start_file("../escape.txt", FileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+
+        let local = VfsHandle::default();
+        let archive = open_archive(&local, &file).unwrap();
+        let mut names: Vec<String> = archive
+            .list(Path::new("/"))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["docs", "src"]);
+        assert!(archive.stat(Path::new("/src/deep")).unwrap().is_dir);
+        let info = archive.stat(Path::new("/docs/run.sh")).unwrap();
+        assert!(info.executable);
+        assert_eq!(info.size, 10);
+        assert!(info.modified.is_some());
+        let mut content = String::new();
+        archive
+            .open_read(Path::new("/src/deep/main.rs"))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "fn main() {}");
+        assert!(archive.mkdir(Path::new("/new")).is_err());
+        assert_eq!(archive.parent().unwrap().1, file);
+
+        copy(&archive, Path::new("/docs"), &local, &root.join("docs"), &mut |_, _| {}).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("docs/run.sh")).unwrap(),
+            "echo hello"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(root.join("docs/run.sh"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use quick_xml::{events::Event, Reader};
+
+/// Percent-encodes all but unreserved characters, and `/` unless `slash` is set
+pub(super) fn uri_encode(text: &str, slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !slash => encoded.push('/'),
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Text with `%XX` sequences decoded, invalid ones are kept
+pub(super) fn uri_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Text of XML elements with path of their names, e.g. `Error/Code`, in document order.
+/// Empty elements like `<collection/>` have empty text.
+pub(super) fn xml_values(xml: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut names: Vec<String> = vec![];
+    let mut values = vec![];
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) => {
+                names.push(String::from_utf8_lossy(tag.local_name().as_ref()).to_string())
+            }
+            Event::End(_) => {
+                names.pop();
+            }
+            Event::Empty(tag) => {
+                names.push(String::from_utf8_lossy(tag.local_name().as_ref()).to_string());
+                values.push((names.join("/"), String::new()));
+                names.pop();
+            }
+            Event::Text(text) if !names.is_empty() => {
+                values.push((names.join("/"), text.unescape()?.to_string()));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(values)
+}
+
+/// Time of HTTP header, e.g. `Wed, 12 Oct 2009 17:50:00 GMT`
+pub(super) fn parse_http_time(text: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    let [_, day, month, year, time, _] = parts.as_slice() else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|name| name == month)? as u64 + 1;
+    let time: Vec<u64> = time
+        .split(':')
+        .filter_map(|part| part.parse().ok())
+        .collect();
+    let [hours, minutes, seconds] = time.as_slice() else {
+        return None;
+    };
+    time_of(
+        year.parse().ok()?,
+        month,
+        day.parse().ok()?,
+        hours * 3600 + minutes * 60 + seconds,
+    )
+}
+
+/// Time of UTC date, `secs` since its midnight
+pub(crate) fn time_of(year: u64, month: u64, day: u64, secs: u64) -> Option<SystemTime> {
+    let days = days_from_civil(year as i64, month as i64, day as i64);
+    let secs = u64::try_from(days).ok()? * 86400 + secs;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Days since 1970-01-01 of proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Date of days since 1970-01-01
+pub(super) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_test() {
+        assert_eq!(uri_encode("/bucket/a b+c", false), "/bucket/a%20b%2Bc");
+        assert_eq!(uri_encode("a/b", true), "a%2Fb");
+        assert_eq!(uri_decode("/a%20b%2Bc%zz%C3%A4"), "/a b+c%zzä");
+        let time = UNIX_EPOCH + Duration::from_secs(1_255_369_830);
+        assert_eq!(parse_http_time("Mon, 12 Oct 2009 17:50:30 GMT"), Some(time));
+        assert_eq!(civil_from_days(14529), (2009, 10, 12));
+        let values = xml_values(
+            "<?xml version=\"1.0\"?><Error><Code>NoSuchKey</Code>\
+             <Message>Key &amp; more</Message><d:collection/></Error>",
+        )
+        .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                (String::from("Error/Code"), String::from("NoSuchKey")),
+                (String::from("Error/Message"), String::from("Key & more")),
+                (String::from("Error/collection"), String::new()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1300 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore,
+    SignatureScheme, StreamOwned,
+};
+use sha2::{Digest, Sha256};
+
+use super::{
+    formats::{civil_from_days, time_of},
+    DirEntry, FileInfo, FileWriter, UnknownHost, Vfs,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Stalled connection fails after this, so the transfer can be resumed
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+/// Idle connection is checked with NOOP before reuse after this
+const IDLE_CHECK: Duration = Duration::from_secs(15);
+/// Connections kept open for later requests
+const MAX_IDLE: usize = 4;
+/// Times a transfer is resumed after its connection fails
+const RESUME_ATTEMPTS: usize = 3;
+/// Last bytes of upload kept to be sent again if the server didn't get them
+const RESUME_WINDOW: usize = 4 * 1024 * 1024;
+
+/// Server and login of FTP connection
+#[derive(Debug, Clone)]
+pub struct FtpOptions {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    /// Explicit FTPS, control and data connections are upgraded with AUTH TLS
+    pub tls: bool,
+    /// Server connects to us for data instead of passive mode
+    pub active: bool,
+}
+
+/// Remote directories over FTP or FTPS. Each request takes an idle connection
+/// or opens a new one, so transfers don't block browsing.
+pub struct FtpFs {
+    prefix: String,
+    pool: Arc<Pool>,
+    /// Server answers `MLST`, cleared once it doesn't
+    mlst: AtomicBool,
+}
+
+impl FtpFs {
+    /// Connects and logs in. Certificate not signed by known authority gives
+    /// `UnknownHost` error unless its fingerprint is `trusted`.
+    pub fn connect(options: &FtpOptions, trusted: Option<&str>) -> anyhow::Result<FtpFs> {
+        let tls = match options.tls {
+            true => Some(Arc::new(Tls::new(&options.host, trusted)?)),
+            false => None,
+        };
+        let pool = Pool {
+            options: options.clone(),
+            tls,
+            idle: Mutex::new(vec![]),
+        };
+        let session = pool.connect()?;
+        pool.put(session);
+        Ok(FtpFs {
+            prefix: prefix(options),
+            pool: Arc::new(pool),
+            mlst: AtomicBool::new(true),
+        })
+    }
+
+    /// Directory the server puts the user in, where browsing starts
+    pub fn home(&self) -> anyhow::Result<PathBuf> {
+        let reply = self.with_session(|session| session.command("PWD", 2))?;
+        parse_pwd(&reply.text).context("Server didn't tell current directory")
+    }
+
+    fn with_session<T>(
+        &self,
+        run: impl FnOnce(&mut Session) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let mut session = self.pool.take()?;
+        let result = run(&mut session);
+        self.pool.put(session);
+        result
+    }
+
+    /// Parsed `LIST` of directory without `.` and `..`
+    fn listing(&self, path: &Path) -> anyhow::Result<Vec<Listed>> {
+        self.with_session(|session| {
+            let lines = session.list(&path.to_string_lossy())?;
+            let now = SystemTime::now();
+            let mut listed: Vec<Listed> = lines
+                .iter()
+                .filter_map(|line| parse_list_line(line, now))
+                .filter(|entry| entry.name != "." && entry.name != "..")
+                .collect();
+            // links are followed, so linked directories can be entered
+            for entry in listed.iter_mut().filter(|entry| entry.info.is_symlink) {
+                let link = path.join(&entry.name);
+                entry.info.is_dir = session
+                    .command(&format!("CWD {}", link.display()), 2)
+                    .is_ok();
+            }
+            Ok(listed)
+        })
+    }
+
+    /// Properties told by `MLST`, None when server doesn't support it
+    fn mlst(&self, path: &Path) -> anyhow::Result<Option<FileInfo>> {
+        self.with_session(|session| {
+            let reply = session.send_command(&format!("MLST {}", path.display()))?;
+            match reply.code {
+                500..=504 => return Ok(None),
+                code if code / 100 != 2 => anyhow::bail!("MLST failed: {}", reply),
+                _ => {}
+            }
+            let Some(mut info) = reply.text.lines().skip(1).find_map(parse_mlst_line) else {
+                return Ok(None);
+            };
+            // links are followed, as in listing
+            if info.is_symlink {
+                info.is_dir = session
+                    .command(&format!("CWD {}", path.display()), 2)
+                    .is_ok();
+            }
+            Ok(Some(info))
+        })
+    }
+
+    fn listed(&self, path: &Path) -> anyhow::Result<Listed> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            anyhow::bail!("{} has no parent directory", path.display());
+        };
+        self.listing(parent)?
+            .into_iter()
+            .find(|entry| name == entry.name.as_str())
+            .with_context(|| format!("{}: No such file", path.display()))
+    }
+}
+
+impl Vfs for FtpFs {
+    fn prefix(&self) -> String {
+        self.prefix.clone()
+    }
+
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<DirEntry>> {
+        Ok(self
+            .listing(path)?
+            .into_iter()
+            .map(|entry| DirEntry {
+                name: entry.name,
+                info: entry.info,
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<FileInfo> {
+        let dir = FileInfo {
+            is_dir: true,
+            ..Default::default()
+        };
+        if path.parent().is_none() {
+            return Ok(dir);
+        }
+        if self.mlst.load(Ordering::Relaxed) {
+            match self.mlst(path)? {
+                Some(info) => return Ok(info),
+                None => self.mlst.store(false, Ordering::Relaxed),
+            }
+        }
+        // listing whole parent is the only way left
+        match self.listed(path) {
+            Ok(entry) => Ok(entry.info),
+            // parent may be unreadable, e.g. above home directory
+            Err(err) => self
+                .with_session(|session| session.command(&format!("CWD {}", path.display()), 2))
+                .map(|_| dir)
+                .map_err(|_| err),
+        }
+    }
+
+    fn open_read(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>> {
+        let path = path.to_string_lossy().to_string();
+        let mut session = self.pool.take()?;
+        let data = session.transfer(&format!("RETR {}", path))?;
+        Ok(Box::new(FtpReader {
+            pool: self.pool.clone(),
+            path,
+            session: Some(session),
+            data: Some(data),
+            position: 0,
+            resumes: 0,
+        }))
+    }
+
+    fn open_write(&self, path: &Path) -> anyhow::Result<Box<dyn FileWriter>> {
+        let path = path.to_string_lossy().to_string();
+        let mut session = self.pool.take()?;
+        let data = session.transfer(&format!("STOR {}", path))?;
+        Ok(Box::new(FtpWriter {
+            pool: self.pool.clone(),
+            path,
+            session: Some(session),
+            data: Some(data),
+            written: 0,
+            tail: vec![],
+            resumes: 0,
+        }))
+    }
+
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()> {
+        self.with_session(|session| session.command(&format!("MKD {}", path.display()), 2))?;
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        let info = self.stat(path)?;
+        let command = match info.is_dir && !info.is_symlink {
+            true => "RMD",
+            false => "DELE",
+        };
+        self.with_session(|session| {
+            session.command(&format!("{} {}", command, path.display()), 2)
+        })?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        self.with_session(|session| {
+            session.command(&format!("RNFR {}", from.display()), 3)?;
+            session.command(&format!("RNTO {}", to.display()), 2)
+        })?;
+        Ok(())
+    }
+
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        match self.listed(path)?.target {
+            Some(target) => Ok(PathBuf::from(target)),
+            None => anyhow::bail!("{} is not a symbolic link", path.display()),
+        }
+    }
+
+    /// Servers without SITE CHMOD, e.g. on Windows, keep their own modes
+    fn set_mode(&self, path: &Path, mode: u32) -> anyhow::Result<()> {
+        let _ = self.with_session(|session| {
+            session.command(&format!("SITE CHMOD {:o} {}", mode, path.display()), 2)
+        });
+        Ok(())
+    }
+}
+
+/// Entry of parsed directory listing
+#[derive(Debug)]
+struct Listed {
+    name: String,
+    info: FileInfo,
+    /// Target of symbolic link
+    target: Option<String>,
+}
+
+/// Logged in connections to one server
+struct Pool {
+    options: FtpOptions,
+    tls: Option<Arc<Tls>>,
+    idle: Mutex<Vec<Session>>,
+}
+
+impl Pool {
+    /// Idle connection if it still works, otherwise new one
+    fn take(&self) -> anyhow::Result<Session> {
+        loop {
+            let Some(mut session) = self.idle.lock().unwrap().pop() else {
+                return self.connect();
+            };
+            if session.used.elapsed() < IDLE_CHECK || session.command("NOOP", 2).is_ok() {
+                return Ok(session);
+            }
+        }
+    }
+
+    /// Keeps working connection for later requests
+    fn put(&self, mut session: Session) {
+        let mut idle = self.idle.lock().unwrap();
+        if !session.broken && idle.len() < MAX_IDLE {
+            session.used = Instant::now();
+            idle.push(session);
+        }
+    }
+
+    fn connect(&self) -> anyhow::Result<Session> {
+        let result = self.login();
+        if let (Err(_), Some(tls)) = (&result, &self.tls) {
+            if let Some(fingerprint) = tls.verifier.rejected.lock().unwrap().take() {
+                return Err(UnknownHost {
+                    host: self.options.host.clone(),
+                    fingerprint,
+                }
+                .into());
+            }
+        }
+        result
+    }
+
+    fn login(&self) -> anyhow::Result<Session> {
+        let options = &self.options;
+        let address = (options.host.as_str(), options.port)
+            .to_socket_addrs()
+            .with_context(|| format!("Can't resolve {}", options.host))?
+            .next()
+            .with_context(|| format!("Can't resolve {}", options.host))?;
+        let tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+            .with_context(|| format!("Can't connect to {}:{}", options.host, options.port))?;
+        set_timeouts(&tcp)?;
+        let mut session = Session {
+            peer: tcp.peer_addr()?.ip(),
+            local: tcp.local_addr()?.ip(),
+            control: BufReader::new(Stream::Tcp(tcp)),
+            active: options.active,
+            tls: self.tls.clone(),
+            used: Instant::now(),
+            broken: false,
+        };
+        let mut greeting = session.reply()?;
+        while greeting.code / 100 == 1 {
+            greeting = session.reply()?;
+        }
+        if greeting.code / 100 != 2 {
+            anyhow::bail!("Server refused connection: {}", greeting);
+        }
+        if let Some(tls) = &self.tls {
+            session.command("AUTH TLS", 2)?;
+            let Stream::Tcp(tcp) = session.control.into_inner() else {
+                anyhow::bail!("Connection is already secured");
+            };
+            session.control = BufReader::new(tls.wrap(tcp)?);
+        }
+
+        let user = session.send_command(&format!("USER {}", options.user))?;
+        match user.code {
+            230 => {}
+            331 | 332 => {
+                session
+                    .command(&format!("PASS {}", options.password), 2)
+                    .with_context(|| format!("Authentication of {} failed", options.user))?;
+            }
+            _ => anyhow::bail!("Authentication of {} failed: {}", options.user, user),
+        }
+        if self.tls.is_some() {
+            session.command("PBSZ 0", 2)?;
+            session.command("PROT P", 2)?;
+        }
+        session.command("TYPE I", 2)?;
+        // servers without UTF-8 names refuse it, names are shown as sent then
+        let _ = session.command("OPTS UTF8 ON", 2);
+        Ok(session)
+    }
+}
+
+/// Reply to a command
+#[derive(Debug)]
+struct Reply {
+    code: u32,
+    /// Text of all lines
+    text: String,
+}
+
+impl std::fmt::Display for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.code, self.text)
+    }
+}
+
+/// Logged in control connection
+struct Session {
+    control: BufReader<Stream>,
+    /// Server address, passive data connections go there
+    peer: IpAddr,
+    /// Our address, server connects there in active mode
+    local: IpAddr,
+    active: bool,
+    tls: Option<Arc<Tls>>,
+    used: Instant,
+    /// Connection failed and can't be reused
+    broken: bool,
+}
+
+impl Session {
+    /// Sends command and returns reply of any code
+    fn send_command(&mut self, command: &str) -> anyhow::Result<Reply> {
+        if command.contains(['\r', '\n']) {
+            anyhow::bail!("Names with line breaks can't be sent over FTP");
+        }
+        let stream = self.control.get_mut();
+        if let Err(err) = stream
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .and_then(|_| stream.flush())
+        {
+            self.broken = true;
+            return Err(err).context("Connection to server failed");
+        }
+        self.reply()
+    }
+
+    /// Sends command and checks first digit of reply code, 2 for completion
+    /// and 3 when server waits for next command. Errors show just the verb,
+    /// so passwords aren't shown.
+    fn command(&mut self, command: &str, class: u32) -> anyhow::Result<Reply> {
+        let reply = self.send_command(command)?;
+        if reply.code / 100 != class {
+            let verb = command.split(' ').next().unwrap_or_default();
+            anyhow::bail!("{} failed: {}", verb, reply);
+        }
+        Ok(reply)
+    }
+
+    fn reply(&mut self) -> anyhow::Result<Reply> {
+        let result = read_reply(&mut self.control);
+        if result.is_err() {
+            self.broken = true;
+        }
+        result
+    }
+
+    /// Opens data connection for command like RETR, which replies when it
+    /// starts transfer and again when it completes
+    fn transfer(&mut self, command: &str) -> anyhow::Result<Stream> {
+        let tcp = match self.active {
+            false => {
+                let pasv = match self.peer {
+                    IpAddr::V4(_) => self.command("PASV", 2)?,
+                    IpAddr::V6(_) => self.command("EPSV", 2)?,
+                };
+                // address in reply is often private one of server behind NAT
+                let port = parse_passive_port(&pasv.text).context("Invalid passive reply")?;
+                let tcp =
+                    TcpStream::connect_timeout(&SocketAddr::new(self.peer, port), CONNECT_TIMEOUT)
+                        .context("Can't open data connection")?;
+                self.start(command)?;
+                tcp
+            }
+            true => {
+                let listener = TcpListener::bind(SocketAddr::new(self.local, 0))?;
+                let address = listener.local_addr()?;
+                self.command(&port_command(address), 2)?;
+                self.start(command)?;
+                accept(&listener)?
+            }
+        };
+        set_timeouts(&tcp)?;
+        match &self.tls {
+            Some(tls) => tls.wrap(tcp),
+            None => Ok(Stream::Tcp(tcp)),
+        }
+    }
+
+    fn start(&mut self, command: &str) -> anyhow::Result<()> {
+        let reply = self.send_command(command)?;
+        if reply.code / 100 != 1 {
+            let verb = command.split(' ').next().unwrap_or_default();
+            anyhow::bail!("{} failed: {}", verb, reply);
+        }
+        Ok(())
+    }
+
+    /// Reply after data connection is closed
+    fn complete(&mut self) -> anyhow::Result<()> {
+        let reply = self.reply()?;
+        if reply.code / 100 != 2 {
+            anyhow::bail!("Transfer failed: {}", reply);
+        }
+        Ok(())
+    }
+
+    /// Lines of directory listing
+    fn list(&mut self, path: &str) -> anyhow::Result<Vec<String>> {
+        // names with spaces are safe in CWD, unlike as LIST argument
+        self.command(&format!("CWD {}", path), 2)?;
+        let mut data = self.transfer("LIST")?;
+        let mut content = vec![];
+        let result = data.read_to_end(&mut content);
+        let _ = data.close();
+        result.context("Listing failed")?;
+        self.complete()?;
+        Ok(String::from_utf8_lossy(&content)
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+
+    fn size(&mut self, path: &str) -> anyhow::Result<u64> {
+        let reply = self.command(&format!("SIZE {}", path), 2)?;
+        reply.text.trim().parse().context("Invalid SIZE reply")
+    }
+}
+
+fn read_reply(control: &mut impl BufRead) -> anyhow::Result<Reply> {
+    let mut read_line = || -> anyhow::Result<String> {
+        let mut line = String::new();
+        if control
+            .read_line(&mut line)
+            .context("Connection to server failed")?
+            == 0
+        {
+            anyhow::bail!("Server closed connection");
+        }
+        Ok(line.trim_end().to_string())
+    };
+    let first = read_line()?;
+    let code: u32 = first
+        .get(..3)
+        .and_then(|code| code.parse().ok())
+        .with_context(|| format!("Invalid reply {}", first))?;
+    let mut text = first.get(4..).unwrap_or_default().to_string();
+    // multiline reply ends with line starting with code and space
+    if first.as_bytes().get(3) == Some(&b'-') {
+        loop {
+            let line = read_line()?;
+            let last =
+                line.len() >= 4 && line.starts_with(&first[..3]) && line.as_bytes()[3] == b' ';
+            text.push('\n');
+            text.push_str(if last { &line[4..] } else { &line });
+            if last {
+                break;
+            }
+        }
+    }
+    Ok(Reply { code, text })
+}
+
+fn set_timeouts(tcp: &TcpStream) -> io::Result<()> {
+    tcp.set_read_timeout(Some(IO_TIMEOUT))?;
+    tcp.set_write_timeout(Some(IO_TIMEOUT))
+}
+
+/// Waits for server to open data connection of active mode
+fn accept(listener: &TcpListener) -> anyhow::Result<TcpStream> {
+    listener.set_nonblocking(true)?;
+    let start = Instant::now();
+    loop {
+        match listener.accept() {
+            Ok((tcp, _)) => {
+                tcp.set_nonblocking(false)?;
+                return Ok(tcp);
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                if start.elapsed() > CONNECT_TIMEOUT {
+                    anyhow::bail!("Server didn't open data connection");
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn port_command(address: SocketAddr) -> String {
+    match address {
+        SocketAddr::V4(address) => {
+            let ip = address.ip().octets();
+            format!(
+                "PORT {},{},{},{},{},{}",
+                ip[0],
+                ip[1],
+                ip[2],
+                ip[3],
+                address.port() / 256,
+                address.port() % 256
+            )
+        }
+        SocketAddr::V6(address) => format!("EPRT |2|{}|{}|", address.ip(), address.port()),
+    }
+}
+
+/// Port of PASV reply `(h1,h2,h3,h4,p1,p2)` or EPSV reply `(|||port|)`
+fn parse_passive_port(text: &str) -> Option<u16> {
+    let start = text.find('(')?;
+    let end = start + text[start..].find(')')?;
+    let inside = &text[start + 1..end];
+    if inside.contains('|') {
+        return inside.split('|').nth(3)?.parse().ok();
+    }
+    let numbers: Vec<u32> = inside
+        .split(',')
+        .map(|number| number.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers.as_slice() {
+        [_, _, _, _, high, low] if *low < 256 => u16::try_from(high * 256 + low).ok(),
+        _ => None,
+    }
+}
+
+/// Directory of `257 "/path" is current directory`, where `""` is a quote
+fn parse_pwd(text: &str) -> Option<PathBuf> {
+    let quoted = &text[text.find('"')? + 1..];
+    let mut path = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('"', Some('"')) => {
+                path.push('"');
+                chars.next();
+            }
+            ('"', _) => return Some(PathBuf::from(path)),
+            (c, _) => path.push(c),
+        }
+    }
+    None
+}
+
+/// Downloads file, continuing from the last byte if the connection fails
+struct FtpReader {
+    pool: Arc<Pool>,
+    path: String,
+    session: Option<Session>,
+    /// None after the transfer completes
+    data: Option<Stream>,
+    position: u64,
+    resumes: usize,
+}
+
+impl FtpReader {
+    fn complete(&mut self) -> anyhow::Result<()> {
+        if let Some(data) = self.data.take() {
+            let _ = data.close();
+        }
+        if let Some(mut session) = self.session.take() {
+            session.complete()?;
+            self.pool.put(session);
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self) -> anyhow::Result<()> {
+        self.data = None;
+        self.session = None;
+        let mut session = self.pool.connect()?;
+        session.command(&format!("REST {}", self.position), 3)?;
+        self.data = Some(session.transfer(&format!("RETR {}", self.path))?);
+        self.session = Some(session);
+        Ok(())
+    }
+}
+
+impl Read for FtpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(data) = &mut self.data else {
+                return Ok(0);
+            };
+            let err = match data.read(buf) {
+                // transfer cut by dropped connection ends without completion reply
+                Ok(0) => match self.complete() {
+                    Ok(()) => return Ok(0),
+                    Err(err) => io_error(err),
+                },
+                Ok(read) => {
+                    self.position += read as u64;
+                    return Ok(read);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => err,
+            };
+            if self.resumes >= RESUME_ATTEMPTS {
+                return Err(err);
+            }
+            self.resumes += 1;
+            self.resume()
+                .map_err(|resume| io_error(resume.context(err)))?;
+        }
+    }
+}
+
+/// Uploads file. If the connection fails, the server is asked how much it got
+/// and the rest is appended.
+struct FtpWriter {
+    pool: Arc<Pool>,
+    path: String,
+    session: Option<Session>,
+    data: Option<Stream>,
+    written: u64,
+    /// Last written bytes, up to RESUME_WINDOW
+    tail: Vec<u8>,
+    resumes: usize,
+}
+
+impl FtpWriter {
+    fn complete(&mut self) -> anyhow::Result<()> {
+        if let Some(data) = self.data.take() {
+            data.close()?;
+        }
+        let Some(mut session) = self.session.take() else {
+            anyhow::bail!("Upload of {} failed", self.path);
+        };
+        session.complete()?;
+        self.pool.put(session);
+        Ok(())
+    }
+
+    /// Appends to what the server got on new connection, `buf` is being written
+    fn resume(&mut self, buf: &[u8]) -> anyhow::Result<()> {
+        self.data = None;
+        self.session = None;
+        let mut session = self.pool.connect()?;
+        let size = session.size(&self.path)?;
+        let start = self.written - self.tail.len() as u64;
+        if size < start || size > self.written + buf.len() as u64 {
+            anyhow::bail!(
+                "Can't resume upload of {}, server has {} bytes",
+                self.path,
+                size
+            );
+        }
+        let mut data = session.transfer(&format!("APPE {}", self.path))?;
+        let skip = (size - start) as usize;
+        let pending: Vec<u8> = self.tail.iter().chain(buf).skip(skip).copied().collect();
+        data.write_all(&pending)?;
+        self.data = Some(data);
+        self.session = Some(session);
+        Ok(())
+    }
+}
+
+impl Write for FtpWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(data) = &mut self.data else {
+            return Err(io::Error::other(format!("Upload of {} failed", self.path)));
+        };
+        let mut result = data.write_all(buf);
+        while let Err(err) = result {
+            if self.resumes >= RESUME_ATTEMPTS {
+                return Err(err);
+            }
+            self.resumes += 1;
+            result = self
+                .resume(buf)
+                .map_err(|resume| io_error(resume.context(err)));
+        }
+        self.tail.extend_from_slice(buf);
+        if self.tail.len() > RESUME_WINDOW {
+            self.tail.drain(..self.tail.len() - RESUME_WINDOW);
+        }
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FileWriter for FtpWriter {
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        loop {
+            let Err(err) = self.complete() else {
+                return Ok(());
+            };
+            if self.resumes >= RESUME_ATTEMPTS {
+                return Err(err);
+            }
+            self.resumes += 1;
+            self.resume(&[]).map_err(|resume| resume.context(err))?;
+        }
+    }
+}
+
+fn io_error(err: anyhow::Error) -> io::Error {
+    io::Error::other(format!("{:#}", err))
+}
+
+/// Plain or TLS connection
+enum Stream {
+    Tcp(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Stream {
+    /// Ends data connection, so the server knows upload is complete
+    fn close(self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(tcp) => tcp.shutdown(Shutdown::Write),
+            Stream::Tls(mut tls) => {
+                tls.conn.send_close_notify();
+                tls.flush()?;
+                tls.sock.shutdown(Shutdown::Write)
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(tcp) => tcp.read(buf),
+            // many servers close data connection without TLS close_notify,
+            // the reply on control connection tells if the transfer completed
+            Stream::Tls(tls) => match tls.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+                result => result,
+            },
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(tcp) => tcp.write(buf),
+            Stream::Tls(tls) => tls.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(tcp) => tcp.flush(),
+            Stream::Tls(tls) => tls.flush(),
+        }
+    }
+}
+
+/// TLS settings shared by connections to one server. Data connections use
+/// the same config, so they resume the TLS session of control connection
+/// as servers require.
+struct Tls {
+    config: Arc<ClientConfig>,
+    name: ServerName<'static>,
+    verifier: Arc<CertVerifier>,
+}
+
+impl Tls {
+    fn new(host: &str, trusted: Option<&str>) -> anyhow::Result<Tls> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let verifier = Arc::new(CertVerifier {
+            webpki: WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()?,
+            provider: provider.clone(),
+            pinned: trusted.map(String::from),
+            rejected: Mutex::new(None),
+        });
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone())
+            .with_no_client_auth();
+        Ok(Tls {
+            config: Arc::new(config),
+            name: ServerName::try_from(host.to_string())
+                .with_context(|| format!("Invalid host name {}", host))?,
+            verifier,
+        })
+    }
+
+    fn wrap(&self, tcp: TcpStream) -> anyhow::Result<Stream> {
+        let connection = ClientConnection::new(self.config.clone(), self.name.clone())?;
+        Ok(Stream::Tls(Box::new(StreamOwned::new(connection, tcp))))
+    }
+}
+
+/// Accepts certificates of known authorities, and self-signed one the user
+/// trusted, which devices in local network usually have
+#[derive(Debug)]
+struct CertVerifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    provider: Arc<CryptoProvider>,
+    /// Fingerprint of unknown certificate user trusted
+    pinned: Option<String>,
+    /// Fingerprint of refused certificate, shown to the user
+    rejected: Mutex<Option<String>>,
+}
+
+impl ServerCertVerifier for CertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if self
+            .webpki
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            .is_ok()
+        {
+            return Ok(ServerCertVerified::assertion());
+        }
+        let hash = Sha256::digest(end_entity.as_ref());
+        let fingerprint = format!("SHA256:{}", STANDARD.encode(hash).trim_end_matches('='));
+        if self.pinned.as_ref() == Some(&fingerprint) {
+            return Ok(ServerCertVerified::assertion());
+        }
+        *self.rejected.lock().unwrap() = Some(fingerprint);
+        Err(rustls::Error::InvalidCertificate(
+            CertificateError::UnknownIssuer,
+        ))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Shown before remote paths, port is left out when it is the default one
+fn prefix(options: &FtpOptions) -> String {
+    let scheme = match options.tls {
+        true => "ftps",
+        false => "ftp",
+    };
+    match options.port {
+        21 => format!("{}://{}@{}", scheme, options.user, options.host),
+        port => format!("{}://{}@{}:{}", scheme, options.user, options.host, port),
+    }
+}
+
+/// Entry of Unix `ls -l` style or Windows `dir` style listing line
+fn parse_list_line(line: &str, now: SystemTime) -> Option<Listed> {
+    parse_unix_line(line, now).or_else(|| parse_windows_line(line))
+}
+
+/// `drwxr-xr-x 2 user group 4096 Jan 01 12:00 name`, where time is replaced
+/// by year for files older than half a year. Group is missing on some servers.
+fn parse_unix_line(line: &str, now: SystemTime) -> Option<Listed> {
+    let tokens = tokens(line);
+    let perms = tokens.first()?.1.as_bytes();
+    if perms.len() < 10 || !b"-dlcbps".contains(&perms[0]) {
+        return None;
+    }
+    let i = (2..tokens.len().saturating_sub(3))
+        .find(|&i| month(tokens[i].1).is_some() && tokens[i + 1].1.parse::<u64>().is_ok())?;
+    let month = month(tokens[i].1)?;
+    let day: u64 = tokens[i + 1].1.parse().ok()?;
+    let modified = match tokens[i + 2].1.split_once(':') {
+        Some((hours, minutes)) => {
+            let secs = hours.parse::<u64>().ok()? * 3600 + minutes.parse::<u64>().ok()? * 60;
+            let days = now.duration_since(UNIX_EPOCH).ok()?.as_secs() / 86400;
+            let year = civil_from_days(days as i64).0 as u64;
+            // dates with time are within last half year, so can be last year
+            let this_year = time_of(year, month, day, secs)?;
+            match this_year > now + Duration::from_secs(86400) {
+                true => time_of(year - 1, month, day, secs),
+                false => Some(this_year),
+            }
+        }
+        None => time_of(tokens[i + 2].1.parse().ok()?, month, day, 0),
+    };
+
+    let mut name = &line[tokens[i + 3].0..];
+    let mut target = None;
+    if perms[0] == b'l' {
+        if let Some((link, link_target)) = name.split_once(" -> ") {
+            name = link;
+            target = Some(link_target.to_string());
+        }
+    }
+    let mode = mode_of(&perms[1..10]);
+    Some(Listed {
+        name: name.to_string(),
+        info: FileInfo {
+            is_dir: perms[0] == b'd',
+            is_symlink: perms[0] == b'l',
+            executable: perms[0] == b'-' && mode & 0o111 != 0,
+            setuid: mode & 0o4000 != 0,
+            setgid: mode & 0o2000 != 0,
+            pipe: perms[0] == b'p',
+            socket: perms[0] == b's',
+            device: matches!(perms[0], b'c' | b'b'),
+            // devices show major and minor number instead
+            size: tokens[i - 1].1.parse().unwrap_or_default(),
+            modified,
+            mode: Some(mode),
+            ..Default::default()
+        },
+        target,
+    })
+}
+
+/// `01-15-24  03:45PM       <DIR>          name`, or size instead of `<DIR>`
+fn parse_windows_line(line: &str) -> Option<Listed> {
+    let tokens = tokens(line);
+    if tokens.len() < 4 {
+        return None;
+    }
+    let date: Vec<u64> = tokens[0]
+        .1
+        .split('-')
+        .map(|number| number.parse().ok())
+        .collect::<Option<_>>()?;
+    let [month, day, year] = date.as_slice() else {
+        return None;
+    };
+    let year = match *year {
+        year if year < 70 => year + 2000,
+        year if year < 100 => year + 1900,
+        year => year,
+    };
+    let time = tokens[1].1.to_uppercase();
+    let (clock, pm) = match (time.strip_suffix("PM"), time.strip_suffix("AM")) {
+        (Some(clock), _) => (clock, Some(true)),
+        (_, Some(clock)) => (clock, Some(false)),
+        _ => (time.as_str(), None),
+    };
+    let (hours, minutes) = clock.split_once(':')?;
+    let mut hours: u64 = hours.parse().ok()?;
+    if let Some(pm) = pm {
+        hours = hours % 12 + if pm { 12 } else { 0 };
+    }
+    let secs = hours * 3600 + minutes.parse::<u64>().ok()? * 60;
+    let (is_dir, size) = match tokens[2].1 {
+        "<DIR>" => (true, 0),
+        size => (false, size.parse().ok()?),
+    };
+    Some(Listed {
+        name: line[tokens[3].0..].to_string(),
+        info: FileInfo {
+            is_dir,
+            size,
+            modified: time_of(year, *month, *day, secs),
+            ..Default::default()
+        },
+        target: None,
+    })
+}
+
+/// Facts of `MLST` reply line like ` type=file;size=12;modify=20240101120000; /a.txt`,
+/// where time is UTC
+fn parse_mlst_line(line: &str) -> Option<FileInfo> {
+    let (facts, _path) = line.trim_start().split_once(' ')?;
+    let mut info = FileInfo::default();
+    let mut typed = false;
+    for (name, value) in facts.split(';').filter_map(|fact| fact.split_once('=')) {
+        match name.to_lowercase().as_str() {
+            "type" => {
+                typed = true;
+                let value = value.to_lowercase();
+                info.is_dir = matches!(value.as_str(), "dir" | "cdir" | "pdir");
+                info.is_symlink = value.starts_with("os.unix=slink") || value == "os.unix=symlink";
+            }
+            "size" => info.size = value.parse().unwrap_or_default(),
+            "modify" => info.modified = parse_mlst_time(value),
+            "unix.mode" => info.mode = u32::from_str_radix(value, 8).ok(),
+            _ => {}
+        }
+    }
+    if let Some(mode) = info.mode {
+        info.executable = !info.is_dir && mode & 0o111 != 0;
+        info.setuid = mode & 0o4000 != 0;
+        info.setgid = mode & 0o2000 != 0;
+    }
+    typed.then_some(info)
+}
+
+/// `YYYYMMDDHHMMSS`, possibly with fraction of second
+fn parse_mlst_time(text: &str) -> Option<SystemTime> {
+    let number = |range: std::ops::Range<usize>| text.get(range)?.parse::<u64>().ok();
+    let secs = number(8..10)? * 3600 + number(10..12)? * 60 + number(12..14)?;
+    time_of(number(0..4)?, number(4..6)?, number(6..8)?, secs)
+}
+
+/// Words of line with their byte offsets
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(from)) => {
+                tokens.push((from, &line[from..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        tokens.push((from, &line[from..]));
+    }
+    tokens
+}
+
+fn month(name: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let name = name.to_lowercase();
+    MONTHS
+        .iter()
+        .position(|month| *month == name)
+        .map(|i| i as u64 + 1)
+}
+
+/// Permission bits of `rwxr-sr-t` style text
+fn mode_of(perms: &[u8]) -> u32 {
+    let mut mode = 0;
+    for (i, c) in perms.iter().take(9).enumerate() {
+        if !matches!(c, b'-' | b'S' | b'T') {
+            mode |= 0o400 >> i;
+        }
+    }
+    if matches!(perms[2], b's' | b'S') {
+        mode |= 0o4000;
+    }
+    if matches!(perms[5], b's' | b'S') {
+        mode |= 0o2000;
+    }
+    if matches!(perms[8], b't' | b'T') {
+        mode |= 0o1000;
+    }
+    mode
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        // 2024-03-01
+        let now = UNIX_EPOCH + Duration::from_secs(1_709_251_200);
+        let entry = parse_list_line(
+            "-rwsr-xr-x   1 joe  staff   1234 Feb 27 13:05 my file.txt",
+            now,
+        )
+        .unwrap();
+        assert_eq!(entry.name, "my file.txt");
+        assert_eq!(entry.info.size, 1234);
+        assert_eq!(entry.info.mode, Some(0o4755));
+        assert!(entry.info.executable && entry.info.setuid);
+        assert_eq!(
+            entry.info.modified,
+            time_of(2024, 2, 27, 13 * 3600 + 5 * 60)
+        );
+        // time of future date is from last year
+        let entry = parse_list_line("drwxr-xr-x 2 ftp 4096 Dec 24 10:00 old", now).unwrap();
+        assert!(entry.info.is_dir);
+        assert_eq!(entry.info.modified, time_of(2023, 12, 24, 10 * 3600));
+        let entry =
+            parse_list_line("lrwxrwxrwx 1 root root 7 Jan  5  2020 bin -> usr/bin", now).unwrap();
+        assert_eq!(entry.name, "bin");
+        assert_eq!(entry.target.as_deref(), Some("usr/bin"));
+        assert!(entry.info.is_symlink);
+
+        let entry =
+            parse_list_line("01-15-24  03:45PM       <DIR>          Program Files", now).unwrap();
+        assert_eq!(entry.name, "Program Files");
+        assert!(entry.info.is_dir);
+        assert_eq!(
+            entry.info.modified,
+            time_of(2024, 1, 15, 15 * 3600 + 45 * 60)
+        );
+        let entry = parse_list_line("12-31-1999  12:10AM  2048 a.log", now).unwrap();
+        assert_eq!(entry.info.size, 2048);
+        assert_eq!(entry.info.modified, time_of(1999, 12, 31, 10 * 60));
+        assert!(parse_list_line("total 12", now).is_none());
+
+        let info =
+            parse_mlst_line(" type=file;size=1234;modify=20240227130501.5;UNIX.mode=0755; /a b.txt")
+                .unwrap();
+        assert_eq!(info.size, 1234);
+        assert_eq!(info.mode, Some(0o755));
+        assert!(info.executable && !info.is_dir);
+        assert_eq!(
+            info.modified,
+            time_of(2024, 2, 27, 13 * 3600 + 5 * 60 + 1)
+        );
+        assert!(parse_mlst_line(" Type=dir;perm=el; /pub").unwrap().is_dir);
+        assert!(parse_mlst_line(" type=OS.unix=slink:/usr/bin; /bin").unwrap().is_symlink);
+        assert!(parse_mlst_line("End").is_none());
+    }
+
+    #[test]
+    fn reply_test() {
+        let mut control = "220-Welcome\r\n 220 is not the end\r\n220 Ready\r\n".as_bytes();
+        let reply = read_reply(&mut control).unwrap();
+        assert_eq!(reply.code, 220);
+        assert_eq!(reply.text, "Welcome\n 220 is not the end\nReady");
+        assert_eq!(
+            parse_passive_port("Entering Passive Mode (192,168,1,2,19,137)"),
+            Some(5001)
+        );
+        assert_eq!(parse_passive_port("Entering Passive Mode (1,2,3,4,300,1)"), None);
+        assert_eq!(parse_passive_port("Entering Passive Mode (1,2,3,4,1,300)"), None);
+        assert_eq!(
+            parse_passive_port("Entering Extended Passive Mode (|||6446|)"),
+            Some(6446)
+        );
+        assert_eq!(
+            parse_pwd("\"/home/a \"\"b\"\"\" is current directory"),
+            Some(PathBuf::from("/home/a \"b\""))
+        );
+        assert_eq!(
+            port_command("10.0.0.1:5001".parse().unwrap()),
+            "PORT 10,0,0,1,19,137"
+        );
+    }
+
+    /// Runs against a server given as `user:password@host:port` in FIR_TEST_FTP,
+    /// e.g. vsftpd in a container. FIR_TEST_FTP_TLS=1 uses explicit FTPS and
+    /// trusts its certificate once shown, FIR_TEST_FTP_ACTIVE=1 uses active mode.
+    #[test]
+    #[ignore = "needs FIR_TEST_FTP server"]
+    fn ftp_test() {
+        let server = env::var("FIR_TEST_FTP").expect("FIR_TEST_FTP should be set");
+        let (login, address) = server.split_once('@').unwrap();
+        let (user, password) = login.split_once(':').unwrap();
+        let (host, port) = address.split_once(':').unwrap_or((address, "21"));
+        let options = FtpOptions {
+            host: host.to_string(),
+            port: port.parse().unwrap(),
+            user: user.to_string(),
+            password: password.to_string(),
+            tls: env::var("FIR_TEST_FTP_TLS").is_ok(),
+            active: env::var("FIR_TEST_FTP_ACTIVE").is_ok(),
+        };
+        let ftp = match FtpFs::connect(&options, None) {
+            Ok(ftp) => ftp,
+            Err(err) => {
+                let unknown = err.downcast_ref::<UnknownHost>().unwrap();
+                FtpFs::connect(&options, Some(&unknown.fingerprint)).unwrap()
+            }
+        };
+
+        let dir = ftp
+            .home()
+            .unwrap()
+            .join(format!("fir-test-{}", std::process::id()));
+        ftp.mkdir(&dir).unwrap();
+        let mut writer = ftp.open_write(&dir.join("a.txt")).unwrap();
+        writer.write_all(b"remote").unwrap();
+        writer.finish().unwrap();
+        ftp.rename(&dir.join("a.txt"), &dir.join("b c.txt"))
+            .unwrap();
+        let names: Vec<String> = ftp
+            .list(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["b c.txt"]);
+        assert_eq!(ftp.stat(&dir.join("b c.txt")).unwrap().size, 6);
+        let mut content = String::new();
+        ftp.open_read(&dir.join("b c.txt"))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "remote");
+        ftp.remove(&dir.join("b c.txt")).unwrap();
+        ftp.remove(&dir).unwrap();
+    }
+}
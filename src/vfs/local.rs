@@ -0,0 +1,148 @@
+use std::{
+    fs::{self, File, Metadata},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{DirEntry, FileInfo, FileWriter, Vfs};
+
+/// Local disk
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFs;
+
+impl Vfs for LocalFs {
+    fn prefix(&self) -> String {
+        String::new()
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<DirEntry>> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            entries.push(DirEntry {
+                name: entry.file_name().to_string_lossy().to_string(),
+                info: file_info(&entry.path()),
+            });
+        }
+        Ok(entries)
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<FileInfo> {
+        fs::symlink_metadata(path)?;
+        Ok(file_info(path))
+    }
+
+    fn open_read(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn open_write(&self, path: &Path) -> anyhow::Result<Box<dyn FileWriter>> {
+        Ok(Box::new(LocalWriter(BufWriter::new(File::create(path)?))))
+    }
+
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()> {
+        Ok(fs::create_dir(path)?)
+    }
+
+    fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.is_dir() {
+            fs::remove_dir(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        Ok(fs::rename(from, to)?)
+    }
+
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(fs::read_link(path)?)
+    }
+
+    #[cfg(unix)]
+    fn symlink(&self, target: &Path, path: &Path) -> anyhow::Result<()> {
+        Ok(std::os::unix::fs::symlink(target, path)?)
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        Ok(fs::set_permissions(path, fs::Permissions::from_mode(mode))?)
+    }
+
+    /// Resolves symbolic links too
+    fn normalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(path.canonicalize()?)
+    }
+}
+
+struct LocalWriter(BufWriter<File>);
+
+impl Write for LocalWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl FileWriter for LocalWriter {
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        Ok(self.0.flush()?)
+    }
+}
+
+/// Properties of local file, links are followed. Missing file gives default values.
+fn file_info(path: &Path) -> FileInfo {
+    let link = fs::symlink_metadata(path).ok();
+    let target = fs::metadata(path).ok();
+    let is_symlink = link.as_ref().is_some_and(|m| m.file_type().is_symlink());
+    let Some(metadata) = target.or(link) else {
+        return FileInfo::default();
+    };
+    let mut info = FileInfo {
+        is_dir: metadata.is_dir(),
+        is_symlink,
+        broken_link: is_symlink && !path.exists(),
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        ..Default::default()
+    };
+    read_mode(&mut info, path, &metadata);
+    info
+}
+
+#[cfg(unix)]
+fn read_mode(info: &mut FileInfo, _path: &Path, metadata: &Metadata) {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let mode = metadata.permissions().mode();
+    let file_type = metadata.file_type();
+    info.executable = metadata.is_file() && mode & 0o111 != 0;
+    info.setuid = mode & 0o4000 != 0;
+    info.setgid = mode & 0o2000 != 0;
+    info.pipe = file_type.is_fifo();
+    info.socket = file_type.is_socket();
+    info.device = file_type.is_block_device() || file_type.is_char_device();
+    info.mode = Some(mode & 0o7777);
+}
+
+#[cfg(not(unix))]
+fn read_mode(info: &mut FileInfo, path: &Path, metadata: &Metadata) {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    info.executable =
+        metadata.is_file() && ["exe", "com", "bat", "cmd"].contains(&extension.as_str());
+}
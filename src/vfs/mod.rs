@@ -0,0 +1,465 @@
+use std::{
+    env, fmt, fs,
+    io::{self, Read, Write},
+    ops::Deref,
+    path::{Component, Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use anyhow::Context;
+
+pub use self::{
+    archive::{is_archive, open_archive, pack, PackOptions},
+    ftp::{FtpFs, FtpOptions},
+    local::LocalFs,
+    s3::S3Fs,
+    sftp::{SftpAuth, SftpFs, UnknownHost},
+    webdav::{DavAuth, WebDavFs},
+};
+/// Local dates fall back to UTC where there is no time zone support
+#[cfg(not(unix))]
+pub(crate) use self::formats::time_of;
+
+mod archive;
+mod formats;
+mod ftp;
+mod local;
+mod s3;
+mod sftp;
+mod webdav;
+
+/// File properties as listed by filesystem
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileInfo {
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    /// Symlink pointing to missing file
+    pub broken_link: bool,
+    pub executable: bool,
+    pub setuid: bool,
+    pub setgid: bool,
+    pub pipe: bool,
+    pub socket: bool,
+    pub device: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Unix permission bits, kept when file is copied
+    pub mode: Option<u32>,
+}
+
+/// File of listed directory
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub info: FileInfo,
+}
+
+/// Writer of new file content. File is complete only after `finish`, which reports
+/// errors that can't be reported by dropping the writer, e.g. failed upload.
+pub trait FileWriter: Write + Send {
+    fn finish(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+/// Filesystem panels can show. Paths are absolute paths within the filesystem.
+pub trait Vfs: Send + Sync {
+    /// Shown before paths, e.g. `sftp://user@host`, empty for local disk.
+    /// Filesystems with the same prefix are the same filesystem.
+    fn prefix(&self) -> String;
+
+    /// Paths can be given to external programs as they are
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    /// Filesystem and path of the file this filesystem is read from, e.g. of archive.
+    /// Going up from root returns there.
+    fn parent(&self) -> Option<(VfsHandle, PathBuf)> {
+        None
+    }
+
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<DirEntry>>;
+    fn stat(&self, path: &Path) -> anyhow::Result<FileInfo>;
+    fn open_read(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>>;
+    /// Creates file or truncates existing one
+    fn open_write(&self, path: &Path) -> anyhow::Result<Box<dyn FileWriter>>;
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()>;
+    /// Removes file or empty directory
+    fn remove(&self, path: &Path) -> anyhow::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()>;
+
+    /// Target of symbolic link
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        anyhow::bail!("{} is not a symbolic link", path.display())
+    }
+
+    /// Creates symbolic link at `path` pointing to `target`
+    fn symlink(&self, _target: &Path, _path: &Path) -> anyhow::Result<()> {
+        anyhow::bail!("Symbolic links are not supported on {}", self.prefix())
+    }
+
+    /// Filesystems without permissions ignore it
+    fn set_mode(&self, _path: &Path, _mode: u32) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Absolute path without `.` and `..`
+    fn normalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(normalize(path))
+    }
+}
+
+/// Shared filesystem of a panel, local disk by default
+#[derive(Clone)]
+pub struct VfsHandle(Arc<dyn Vfs>);
+
+impl VfsHandle {
+    pub fn new(vfs: impl Vfs + 'static) -> Self {
+        VfsHandle(Arc::new(vfs))
+    }
+
+    pub fn same(&self, other: &VfsHandle) -> bool {
+        self.prefix() == other.prefix()
+    }
+}
+
+impl Default for VfsHandle {
+    fn default() -> Self {
+        VfsHandle::new(LocalFs)
+    }
+}
+
+impl Deref for VfsHandle {
+    type Target = dyn Vfs;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl fmt::Debug for VfsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("VfsHandle").field(&self.prefix()).finish()
+    }
+}
+
+/// Removes `.` and `..` without looking at filesystem
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => normalized = PathBuf::from(prefix.as_os_str()),
+            Component::RootDir => normalized.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+        }
+    }
+    normalized
+}
+
+/// Directory of temporary copies made by this process, created on first use so that
+/// only the user can access it
+pub fn temp_dir() -> anyhow::Result<PathBuf> {
+    let mut dir = TEMP_DIR.lock().unwrap_or_else(|err| err.into_inner());
+    if dir.is_none() {
+        *dir = Some(private_dir(&env::temp_dir(), "fir")?);
+    }
+    Ok(dir.clone().expect("Temporary directory should be set"))
+}
+
+/// Removes `temp_dir` with everything in it, if it was created
+pub fn remove_temp_dir() {
+    let mut dir = TEMP_DIR.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(dir) = dir.take() {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+static TEMP_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Empty directory of its own in `temp_dir`
+pub fn new_temp_dir() -> anyhow::Result<PathBuf> {
+    static DIRS: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = temp_dir()?.join(DIRS.fetch_add(1, Ordering::Relaxed).to_string());
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
+
+/// New directory in `parent` only the user can access, named after prefix and
+/// process. Directories which already exist are never reused, someone else may
+/// have made them.
+pub fn private_dir(parent: &Path, prefix: &str) -> anyhow::Result<PathBuf> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    let seed = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or(0);
+    for attempt in 0..100u32 {
+        let name = format!("{}-{}-{:x}", prefix, process::id(), seed.wrapping_add(attempt));
+        let dir = parent.join(name);
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Can't create {}", dir.display()))
+            }
+        }
+    }
+    anyhow::bail!("Can't create temporary directory in {}", parent.display())
+}
+
+/// Path external programs can open: the file itself on local disk, otherwise
+/// its copy in a new temporary directory
+pub fn local_copy(vfs: &VfsHandle, path: &Path) -> anyhow::Result<PathBuf> {
+    if vfs.is_local() {
+        return Ok(path.to_path_buf());
+    }
+    let Some(name) = path.file_name() else {
+        anyhow::bail!("Can't copy {}", path.display());
+    };
+    let dir = new_temp_dir()?;
+    let copy_path = dir.join(name);
+    copy(vfs, path, &VfsHandle::default(), &copy_path, &mut |_, _| {})?;
+    Ok(copy_path)
+}
+
+/// Copies file or directory tree, possibly between different filesystems.
+/// `to` is full path of the copy. Symbolic links are copied as links.
+/// `report` is called with file being copied and number of its bytes just read.
+pub fn copy(
+    from: &VfsHandle,
+    from_path: &Path,
+    to: &VfsHandle,
+    to_path: &Path,
+    report: &mut dyn FnMut(&Path, u64),
+) -> anyhow::Result<()> {
+    if from.same(to) && to_path.starts_with(from_path) {
+        anyhow::bail!("Can't copy {} into itself", from_path.display());
+    }
+    let info = from
+        .stat(from_path)
+        .with_context(|| format!("Can't read {}", from_path.display()))?;
+    copy_entry(from, from_path, &info, to, to_path, report)
+}
+
+/// `copy` of file whose `info` is known, e.g. from listing its directory
+fn copy_entry(
+    from: &VfsHandle,
+    from_path: &Path,
+    info: &FileInfo,
+    to: &VfsHandle,
+    to_path: &Path,
+    report: &mut dyn FnMut(&Path, u64),
+) -> anyhow::Result<()> {
+    if info.is_symlink {
+        let target = from.read_link(from_path)?;
+        if to.stat(to_path).is_ok_and(|existing| !existing.is_dir) {
+            to.remove(to_path)?;
+        }
+        return to
+            .symlink(&target, to_path)
+            .with_context(|| format!("Can't create link {}", to_path.display()));
+    }
+    if info.is_dir {
+        if !to.stat(to_path).is_ok_and(|target| target.is_dir) {
+            to.mkdir(to_path)
+                .with_context(|| format!("Can't create {}", to_path.display()))?;
+        }
+        for entry in from.list(from_path)? {
+            let (from_path, to_path) = (from_path.join(&entry.name), to_path.join(&entry.name));
+            copy_entry(from, &from_path, &entry.info, to, &to_path, report)?;
+        }
+    } else {
+        let mut reader = ReportingReader {
+            inner: from
+                .open_read(from_path)
+                .with_context(|| format!("Can't read {}", from_path.display()))?,
+            path: from_path,
+            report,
+        };
+        let mut writer = to
+            .open_write(to_path)
+            .with_context(|| format!("Can't write {}", to_path.display()))?;
+        io::copy(&mut reader, &mut writer)
+            .with_context(|| format!("Failed to copy {}", from_path.display()))?;
+        writer.finish()?;
+    }
+    if let Some(mode) = info.mode {
+        to.set_mode(to_path, mode)?;
+    }
+    Ok(())
+}
+
+/// Renames within filesystem, otherwise copies and removes original. Only
+/// copying is reported.
+pub fn move_to(
+    from: &VfsHandle,
+    from_path: &Path,
+    to: &VfsHandle,
+    to_path: &Path,
+    report: &mut dyn FnMut(&Path, u64),
+) -> anyhow::Result<()> {
+    if from.same(to) && from.rename(from_path, to_path).is_ok() {
+        return Ok(());
+    }
+    copy(from, from_path, to, to_path, report)?;
+    remove_all(from, from_path, &mut |_, _| {})
+}
+
+/// Removes file or directory with its content. `report` is called with
+/// each removed file and its size.
+pub fn remove_all(
+    vfs: &VfsHandle,
+    path: &Path,
+    report: &mut dyn FnMut(&Path, u64),
+) -> anyhow::Result<()> {
+    let info = vfs
+        .stat(path)
+        .with_context(|| format!("Can't read {}", path.display()))?;
+    remove_entry(vfs, path, &info, report)
+}
+
+/// `remove_all` of file whose `info` is known, e.g. from listing its directory
+fn remove_entry(
+    vfs: &VfsHandle,
+    path: &Path,
+    info: &FileInfo,
+    report: &mut dyn FnMut(&Path, u64),
+) -> anyhow::Result<()> {
+    // link to directory is removed, not its content
+    if info.is_dir && !info.is_symlink {
+        for entry in vfs.list(path)? {
+            remove_entry(vfs, &path.join(&entry.name), &entry.info, report)?;
+        }
+    }
+    vfs.remove(path)
+        .with_context(|| format!("Can't remove {}", path.display()))?;
+    if !info.is_dir && !info.is_symlink {
+        report(path, info.size);
+    }
+    Ok(())
+}
+
+/// Creates directory and its missing parents
+pub fn create_dir_all(vfs: &VfsHandle, path: &Path) -> anyhow::Result<()> {
+    if vfs.stat(path).is_ok_and(|info| info.is_dir) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        create_dir_all(vfs, parent)?;
+    }
+    vfs.mkdir(path)
+        .with_context(|| format!("Can't create {}", path.display()))
+}
+
+/// Bytes of files in directory tree, links don't count
+pub fn tree_size(vfs: &VfsHandle, path: &Path) -> anyhow::Result<u64> {
+    entry_size(vfs, path, &vfs.stat(path)?)
+}
+
+fn entry_size(vfs: &VfsHandle, path: &Path, info: &FileInfo) -> anyhow::Result<u64> {
+    if info.is_symlink {
+        return Ok(0);
+    }
+    if !info.is_dir {
+        return Ok(info.size);
+    }
+    let mut size = 0;
+    for entry in vfs.list(path)? {
+        size += entry_size(vfs, &path.join(&entry.name), &entry.info)?;
+    }
+    Ok(size)
+}
+
+/// Passes number of bytes read to `report`
+struct ReportingReader<'a> {
+    inner: Box<dyn Read + Send>,
+    path: &'a Path,
+    report: &'a mut dyn FnMut(&Path, u64),
+}
+
+impl Read for ReportingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        (self.report)(self.path, len as u64);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn normalize_test() {
+        assert_eq!(normalize(Path::new("/a/b/../c/./d")), PathBuf::from("/a/c/d"));
+        assert_eq!(normalize(Path::new("/..")), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn copy_move_test() {
+        let dir = TestDir::new("vfs");
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/sub")).unwrap();
+        fs::write(root.join("src/a.txt"), "alpha").unwrap();
+        fs::write(root.join("src/sub/b.txt"), "beta").unwrap();
+        let local = VfsHandle::default();
+
+        let mut copied = 0;
+        copy(&local, &root.join("src"), &local, &root.join("copy"), &mut |_, len| copied += len).unwrap();
+        assert_eq!(copied, 9);
+        assert_eq!(tree_size(&local, &root.join("copy")).unwrap(), 9);
+        assert_eq!(fs::read_to_string(root.join("copy/sub/b.txt")).unwrap(), "beta");
+        assert!(copy(&local, &root.join("src"), &local, &root.join("src/sub/x"), &mut |_, _| {}).is_err());
+
+        move_to(&local, &root.join("copy"), &local, &root.join("moved"), &mut |_, _| {}).unwrap();
+        assert!(!root.join("copy").exists());
+        assert_eq!(fs::read_to_string(root.join("moved/a.txt")).unwrap(), "alpha");
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("a.txt", root.join("src/link")).unwrap();
+            copy(&local, &root.join("src/link"), &local, &root.join("moved/link"), &mut |_, _| {}).unwrap();
+            assert_eq!(fs::read_link(root.join("moved/link")).unwrap(), PathBuf::from("a.txt"));
+        }
+
+        create_dir_all(&local, &root.join("moved/new/deeper")).unwrap();
+        assert!(root.join("moved/new/deeper").is_dir());
+
+        let mut removed = 0;
+        remove_all(&local, &root.join("moved"), &mut |_, size| removed += size).unwrap();
+        assert_eq!(removed, 9);
+        assert!(!root.join("moved").exists());
+    }
+
+    #[test]
+    fn private_dir_test() {
+        let root = TestDir::new("private");
+        let first = private_dir(root.path(), "fir").unwrap();
+        let second = private_dir(root.path(), "fir").unwrap();
+        assert_ne!(first, second);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&first).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+    }
+}
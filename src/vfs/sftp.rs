@@ -0,0 +1,396 @@
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ssh2::{
+    CheckResult, FileStat, FileType, HashType, HostKeyType, KnownHostFileKind, Session, Sftp,
+};
+
+use super::{DirEntry, FileInfo, FileWriter, Vfs};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Timeout of single SFTP request, in milliseconds
+const REQUEST_TIMEOUT: u32 = 30_000;
+
+/// How user proves who they are to the server
+#[derive(Debug, Clone)]
+pub enum SftpAuth {
+    Password(String),
+    /// Private key file, passphrase if it is encrypted
+    KeyFile(PathBuf, Option<String>),
+    /// Keys of running ssh-agent
+    Agent,
+}
+
+/// Server whose host key isn't in known_hosts, or FTPS server with certificate
+/// of unknown authority. Connecting again with its fingerprint trusts it.
+#[derive(Debug)]
+pub struct UnknownHost {
+    pub host: String,
+    /// SHA256 fingerprint as shown by ssh
+    pub fingerprint: String,
+}
+
+impl fmt::Display for UnknownHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Identity of {} is unknown, its fingerprint is {}",
+            self.host, self.fingerprint
+        )
+    }
+}
+
+impl std::error::Error for UnknownHost {}
+
+/// Remote directories over SFTP. Requests block until the server answers.
+pub struct SftpFs {
+    prefix: String,
+    sftp: Sftp,
+    /// Kept open as long as the filesystem is used
+    _session: Session,
+}
+
+impl SftpFs {
+    /// Connects and logs in, checking host key against `~/.ssh/known_hosts`.
+    /// Unknown key gives `UnknownHost` error unless its fingerprint is `trusted`.
+    pub fn connect(
+        host: &str,
+        port: u16,
+        user: &str,
+        auth: &SftpAuth,
+        trusted: Option<&str>,
+    ) -> anyhow::Result<SftpFs> {
+        let address = (host, port)
+            .to_socket_addrs()
+            .with_context(|| format!("Can't resolve {}", host))?
+            .next()
+            .with_context(|| format!("Can't resolve {}", host))?;
+        let tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+            .with_context(|| format!("Can't connect to {}:{}", host, port))?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.set_timeout(REQUEST_TIMEOUT);
+        session.handshake().context("SSH handshake failed")?;
+        check_host_key(&session, host, port, trusted)?;
+
+        let result = match auth {
+            SftpAuth::Password(password) => session.userauth_password(user, password),
+            SftpAuth::KeyFile(key, passphrase) => {
+                session.userauth_pubkey_file(user, None, key, passphrase.as_deref())
+            }
+            SftpAuth::Agent => session.userauth_agent(user),
+        };
+        result.with_context(|| format!("Authentication of {} failed", user))?;
+        if !session.authenticated() {
+            anyhow::bail!("Authentication of {} failed", user);
+        }
+        let sftp = session.sftp().context("Server has no SFTP subsystem")?;
+        Ok(SftpFs {
+            prefix: prefix(host, port, user),
+            sftp,
+            _session: session,
+        })
+    }
+
+    /// Directory of the user on the server, where browsing starts
+    pub fn home(&self) -> anyhow::Result<PathBuf> {
+        Ok(self.sftp.realpath(Path::new("."))?)
+    }
+}
+
+impl Vfs for SftpFs {
+    fn prefix(&self) -> String {
+        self.prefix.clone()
+    }
+
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<DirEntry>> {
+        let mut entries = vec![];
+        for (file, stat) in self.sftp.readdir(path)? {
+            let Some(name) = file.file_name() else {
+                continue;
+            };
+            entries.push(DirEntry {
+                name: name.to_string_lossy().to_string(),
+                info: self.link_info(&file, &stat),
+            });
+        }
+        Ok(entries)
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<FileInfo> {
+        let stat = self.sftp.lstat(path)?;
+        Ok(self.link_info(path, &stat))
+    }
+
+    fn open_read(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(self.sftp.open(path)?))
+    }
+
+    fn open_write(&self, path: &Path) -> anyhow::Result<Box<dyn FileWriter>> {
+        Ok(Box::new(SftpWriter(self.sftp.create(path)?)))
+    }
+
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()> {
+        Ok(self.sftp.mkdir(path, 0o755)?)
+    }
+
+    fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        match self.sftp.lstat(path)?.is_dir() {
+            true => self.sftp.rmdir(path)?,
+            false => self.sftp.unlink(path)?,
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        Ok(self.sftp.rename(from, to, None)?)
+    }
+
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(self.sftp.readlink(path)?)
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> anyhow::Result<()> {
+        Ok(self.sftp.symlink(target, path)?)
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> anyhow::Result<()> {
+        let stat = FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: Some(mode),
+            atime: None,
+            mtime: None,
+        };
+        Ok(self.sftp.setstat(path, stat)?)
+    }
+}
+
+impl SftpFs {
+    /// Properties of file listed without following links. Links are followed
+    /// here, so linked directories can be entered.
+    fn link_info(&self, path: &Path, stat: &FileStat) -> FileInfo {
+        if stat.file_type() != FileType::Symlink {
+            return file_info(stat);
+        }
+        match self.sftp.stat(path) {
+            Ok(target) => FileInfo {
+                is_symlink: true,
+                ..file_info(&target)
+            },
+            Err(_) => FileInfo {
+                is_symlink: true,
+                broken_link: true,
+                ..file_info(stat)
+            },
+        }
+    }
+}
+
+struct SftpWriter(ssh2::File);
+
+impl Write for SftpWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl FileWriter for SftpWriter {
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.0.flush()?;
+        Ok(self.0.close()?)
+    }
+}
+
+fn file_info(stat: &FileStat) -> FileInfo {
+    let mode = stat.perm.unwrap_or_default();
+    let file_type = stat.file_type();
+    FileInfo {
+        is_dir: file_type.is_dir(),
+        executable: file_type.is_file() && mode & 0o111 != 0,
+        setuid: mode & 0o4000 != 0,
+        setgid: mode & 0o2000 != 0,
+        pipe: file_type == FileType::NamedPipe,
+        socket: file_type == FileType::Socket,
+        device: matches!(file_type, FileType::CharDevice | FileType::BlockDevice),
+        size: stat.size.unwrap_or_default(),
+        modified: stat
+            .mtime
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+        mode: stat.perm.map(|mode| mode & 0o7777),
+        ..Default::default()
+    }
+}
+
+/// Shown before remote paths, port is left out when it is the default one
+fn prefix(host: &str, port: u16, user: &str) -> String {
+    match port {
+        22 => format!("sftp://{}@{}", user, host),
+        port => format!("sftp://{}@{}:{}", user, host, port),
+    }
+}
+
+/// Name of host in known_hosts, with port only when it isn't the default one
+fn known_host_name(host: &str, port: u16) -> String {
+    match port {
+        22 => host.to_string(),
+        port => format!("[{}]:{}", host, port),
+    }
+}
+
+fn known_hosts_path() -> anyhow::Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME is not set")?;
+    Ok(PathBuf::from(home).join(".ssh").join("known_hosts"))
+}
+
+/// Refuses server whose key differs from known one. New key is trusted only when
+/// its fingerprint is the one user accepted, and is appended to known_hosts by hand,
+/// so entries libssh2 can't parse are kept.
+fn check_host_key(
+    session: &Session,
+    host: &str,
+    port: u16,
+    trusted: Option<&str>,
+) -> anyhow::Result<()> {
+    let (key, key_type) = session.host_key().context("Server sent no host key")?;
+    let file = known_hosts_path()?;
+    let mut known_hosts = session.known_hosts()?;
+    if file.exists() {
+        // lines of key types libssh2 doesn't know make reading stop early
+        let _ = known_hosts.read_file(&file, KnownHostFileKind::OpenSSH);
+    }
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => anyhow::bail!(
+            "Host key of {} doesn't match the one in {}, someone may be intercepting the connection",
+            host,
+            file.display()
+        ),
+        CheckResult::NotFound | CheckResult::Failure => {
+            let hash = session
+                .host_key_hash(HashType::Sha256)
+                .context("Server sent no host key")?;
+            let fingerprint = format!("SHA256:{}", STANDARD.encode(hash).trim_end_matches('='));
+            match trusted {
+                Some(trusted) if trusted == fingerprint => {}
+                Some(_) => anyhow::bail!(
+                    "Host key of {} changed since it was shown, someone may be intercepting the connection",
+                    host
+                ),
+                None => {
+                    return Err(UnknownHost {
+                        host: known_host_name(host, port),
+                        fingerprint,
+                    }
+                    .into())
+                }
+            }
+            let line = format!(
+                "{} {} {}\n",
+                known_host_name(host, port),
+                key_type_name(key_type)?,
+                STANDARD.encode(key)
+            );
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&file)?
+                .write_all(line.as_bytes())
+                .with_context(|| format!("Can't write {}", file.display()))
+        }
+    }
+}
+
+fn key_type_name(key_type: HostKeyType) -> anyhow::Result<&'static str> {
+    Ok(match key_type {
+        HostKeyType::Rsa => "ssh-rsa",
+        HostKeyType::Dss => "ssh-dss",
+        HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+        HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+        HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+        HostKeyType::Ed25519 => "ssh-ed25519",
+        HostKeyType::Unknown => anyhow::bail!("Host key has unknown type"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn names_test() {
+        assert_eq!(prefix("example.com", 22, "joe"), "sftp://joe@example.com");
+        assert_eq!(
+            prefix("localhost", 2222, "joe"),
+            "sftp://joe@localhost:2222"
+        );
+        assert_eq!(known_host_name("example.com", 22), "example.com");
+        assert_eq!(known_host_name("localhost", 2222), "[localhost]:2222");
+        let info = file_info(&FileStat {
+            size: Some(10),
+            uid: None,
+            gid: None,
+            perm: Some(0o100755),
+            atime: None,
+            mtime: Some(1_700_000_000),
+        });
+        assert!(info.executable && !info.is_dir);
+        assert_eq!(info.mode, Some(0o755));
+    }
+
+    /// Runs against a server given as `user:password@host:port` in FIR_TEST_SFTP,
+    /// e.g. sshd in a container, whose host key is already trusted
+    #[test]
+    #[ignore = "needs FIR_TEST_SFTP server"]
+    fn sftp_test() {
+        let server = env::var("FIR_TEST_SFTP").expect("FIR_TEST_SFTP should be set");
+        let (login, address) = server.split_once('@').unwrap();
+        let (user, password) = login.split_once(':').unwrap();
+        let (host, port) = address.split_once(':').unwrap_or((address, "22"));
+        let auth = SftpAuth::Password(password.to_string());
+        let sftp = SftpFs::connect(host, port.parse().unwrap(), user, &auth, None).unwrap();
+
+        let dir = sftp
+            .home()
+            .unwrap()
+            .join(format!("fir-test-{}", std::process::id()));
+        sftp.mkdir(&dir).unwrap();
+        let mut writer = sftp.open_write(&dir.join("a.txt")).unwrap();
+        writer.write_all(b"remote").unwrap();
+        writer.finish().unwrap();
+        sftp.rename(&dir.join("a.txt"), &dir.join("b.txt")).unwrap();
+        let names: Vec<String> = sftp
+            .list(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["b.txt"]);
+        let mut content = String::new();
+        sftp.open_read(&dir.join("b.txt"))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "remote");
+        sftp.remove(&dir.join("b.txt")).unwrap();
+        sftp.remove(&dir).unwrap();
+    }
+}
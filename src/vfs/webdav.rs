@@ -0,0 +1,414 @@
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use super::{
+    formats::{parse_http_time, uri_decode, uri_encode, xml_values},
+    DirEntry, FileInfo, FileWriter, Vfs,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Chunks of upload waiting for the request to send them
+const UPLOAD_CHUNKS: usize = 4;
+/// Properties asked in PROPFIND
+const PROPFIND: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+    <d:propfind xmlns:d=\"DAV:\"><d:prop>\
+    <d:resourcetype/><d:getcontentlength/><d:getlastmodified/>\
+    </d:prop></d:propfind>";
+
+/// How requests prove who the user is
+#[derive(Debug, Clone)]
+pub enum DavAuth {
+    None,
+    Basic {
+        user: String,
+        password: String,
+    },
+    /// OAuth access token
+    Bearer(String),
+}
+
+/// Remote directories of WebDAV share. Path `/` is the collection of share URL.
+pub struct WebDavFs {
+    prefix: String,
+    agent: ureq::Agent,
+    /// Scheme, host and port of share URL
+    origin: String,
+    /// Path of share URL, as sent, without trailing `/`
+    base_path: String,
+    /// Value of Authorization header
+    authorization: Option<String>,
+}
+
+/// Resource of PROPFIND response
+struct Resource {
+    /// Decoded path without trailing `/`
+    path: String,
+    info: FileInfo,
+}
+
+impl WebDavFs {
+    /// `url` is the share, e.g. `https://cloud.example.com/remote.php/dav/files/joe`.
+    /// Nothing is sent until first request.
+    pub fn new(url: &str, auth: DavAuth) -> anyhow::Result<WebDavFs> {
+        let url = url.trim_end_matches('/');
+        let (scheme, rest) = url
+            .split_once("://")
+            .with_context(|| format!("Invalid URL {}", url))?;
+        let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if host.is_empty() {
+            anyhow::bail!("Invalid URL {}", url);
+        }
+        let (prefix, authorization) = match auth {
+            DavAuth::None => (url.to_string(), None),
+            DavAuth::Basic { user, password } => (
+                format!("{}://{}@{}{}", scheme, user, host, path),
+                Some(format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{}:{}", user, password))
+                )),
+            ),
+            DavAuth::Bearer(token) => (url.to_string(), Some(format!("Bearer {}", token))),
+        };
+        Ok(WebDavFs {
+            prefix,
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(CONNECT_TIMEOUT)
+                .build(),
+            origin: format!("{}://{}", scheme, host),
+            base_path: path.to_string(),
+            authorization,
+        })
+    }
+
+    /// URL of resource, collections end with `/`
+    fn url(&self, path: &Path, collection: bool) -> String {
+        let mut url = format!(
+            "{}{}{}",
+            self.origin,
+            self.base_path,
+            uri_encode(&path.to_string_lossy(), false)
+        );
+        if collection && !url.ends_with('/') {
+            url.push('/');
+        }
+        url
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = self.agent.request(method, url);
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+
+    /// Resource at path and with depth 1 its members
+    fn propfind(&self, path: &Path, depth: u32) -> anyhow::Result<Vec<Resource>> {
+        let xml = self
+            .request("PROPFIND", &self.url(path, depth > 0))
+            .set("Depth", &depth.to_string())
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(PROPFIND)
+            .map_err(|err| error(path, err))?
+            .into_string()?;
+        let mut resources: Vec<Resource> = vec![];
+        for (name, value) in xml_values(&xml)? {
+            let Some(field) = name.strip_prefix("multistatus/response/") else {
+                continue;
+            };
+            if field == "href" {
+                resources.push(Resource {
+                    path: href_path(&value),
+                    info: FileInfo::default(),
+                });
+                continue;
+            }
+            let (Some(resource), Some(property)) =
+                (resources.last_mut(), field.strip_prefix("propstat/prop/"))
+            else {
+                continue;
+            };
+            match property {
+                "resourcetype/collection" => resource.info.is_dir = true,
+                "getcontentlength" => resource.info.size = value.parse().unwrap_or_default(),
+                "getlastmodified" => resource.info.modified = parse_http_time(&value),
+                _ => {}
+            }
+        }
+        Ok(resources)
+    }
+
+    /// Decoded path of resource as server shows it in href
+    fn resource_path(&self, path: &Path) -> String {
+        format!("{}{}", uri_decode(&self.base_path), path.to_string_lossy())
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+impl Vfs for WebDavFs {
+    fn prefix(&self) -> String {
+        self.prefix.clone()
+    }
+
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<DirEntry>> {
+        let own_path = self.resource_path(path);
+        Ok(self
+            .propfind(path, 1)?
+            .into_iter()
+            .filter(|resource| resource.path != own_path)
+            .filter_map(|resource| {
+                let name = resource.path.rsplit('/').next()?.to_string();
+                Some(DirEntry {
+                    name,
+                    info: resource.info,
+                })
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<FileInfo> {
+        self.propfind(path, 0)?
+            .into_iter()
+            .next()
+            .map(|resource| resource.info)
+            .with_context(|| format!("{}: No such file", path.display()))
+    }
+
+    fn open_read(&self, path: &Path) -> anyhow::Result<Box<dyn Read + Send>> {
+        let response = self
+            .request("GET", &self.url(path, false))
+            .call()
+            .map_err(|err| error(path, err))?;
+        Ok(Box::new(response.into_reader()))
+    }
+
+    fn open_write(&self, path: &Path) -> anyhow::Result<Box<dyn FileWriter>> {
+        let (sender, receiver) = mpsc::sync_channel(UPLOAD_CHUNKS);
+        let request = self.request("PUT", &self.url(path, false));
+        let path = path.to_path_buf();
+        let upload = thread::spawn(move || {
+            request
+                .send(ChannelReader {
+                    receiver,
+                    chunk: vec![],
+                    offset: 0,
+                    done: false,
+                })
+                .map(|_| ())
+                .map_err(|err| error(&path, err))
+        });
+        Ok(Box::new(DavWriter {
+            sender: Some(sender),
+            upload: Some(upload),
+        }))
+    }
+
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()> {
+        self.request("MKCOL", &self.url(path, true))
+            .call()
+            .map_err(|err| error(path, err))?;
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        let is_dir = self.stat(path)?.is_dir;
+        self.request("DELETE", &self.url(path, is_dir))
+            .call()
+            .map_err(|err| error(path, err))?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        let is_dir = self.stat(from)?.is_dir;
+        self.request("MOVE", &self.url(from, is_dir))
+            .set("Destination", &self.url(to, is_dir))
+            .call()
+            .map_err(|err| error(from, err))?;
+        Ok(())
+    }
+}
+
+/// Uploads file in a thread streaming the written chunks as request body
+struct DavWriter {
+    /// None marks the end of file, dropping sender without it cancels upload
+    sender: Option<SyncSender<Option<Vec<u8>>>>,
+    upload: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl DavWriter {
+    fn wait(&mut self) -> anyhow::Result<()> {
+        self.sender = None;
+        match self.upload.take() {
+            Some(upload) => upload
+                .join()
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Upload failed"))),
+            None => anyhow::bail!("Upload failed"),
+        }
+    }
+}
+
+impl Write for DavWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let sent = match &self.sender {
+            Some(sender) => sender.send(Some(buf.to_vec())).is_ok(),
+            None => false,
+        };
+        if !sent {
+            // request ended early, its error tells why
+            let err = self
+                .wait()
+                .err()
+                .unwrap_or_else(|| anyhow::anyhow!("Upload failed"));
+            return Err(io::Error::other(format!("{:#}", err)));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FileWriter for DavWriter {
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(None);
+        }
+        self.wait()
+    }
+}
+
+/// Request body of chunks sent by `DavWriter`
+struct ChannelReader {
+    receiver: Receiver<Option<Vec<u8>>>,
+    chunk: Vec<u8>,
+    offset: usize,
+    done: bool,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.receiver.recv() {
+                Ok(Some(chunk)) => {
+                    self.chunk = chunk;
+                    self.offset = 0;
+                }
+                Ok(None) => self.done = true,
+                Err(_) => return Err(io::Error::other("Upload was cancelled")),
+            }
+        }
+        let size = buf.len().min(self.chunk.len() - self.offset);
+        buf[..size].copy_from_slice(&self.chunk[self.offset..self.offset + size]);
+        self.offset += size;
+        Ok(size)
+    }
+}
+
+/// Decoded path of href, which is absolute path or full URL
+fn href_path(href: &str) -> String {
+    let path = match href.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/').unwrap_or(rest.len())..],
+        None => href,
+    };
+    uri_decode(path).trim_end_matches('/').to_string()
+}
+
+fn error(path: &Path, err: ureq::Error) -> anyhow::Error {
+    match err {
+        ureq::Error::Status(401, _) => anyhow::anyhow!("Authentication failed"),
+        ureq::Error::Status(404, _) => anyhow::anyhow!("{}: No such file", path.display()),
+        ureq::Error::Status(status, response) => anyhow::anyhow!(
+            "{}: Server responded with {} {}",
+            path.display(),
+            status,
+            response.status_text()
+        ),
+        err => anyhow::Error::new(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, path::PathBuf};
+
+    use super::*;
+
+    #[test]
+    fn names_test() {
+        let dav = WebDavFs::new(
+            "https://cloud.example.com/remote.php/dav/files/joe%20doe/",
+            DavAuth::Basic {
+                user: String::from("joe"),
+                password: String::from("secret"),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            dav.prefix(),
+            "https://joe@cloud.example.com/remote.php/dav/files/joe%20doe"
+        );
+        assert_eq!(
+            dav.url(Path::new("/My Documents"), true),
+            "https://cloud.example.com/remote.php/dav/files/joe%20doe/My%20Documents/"
+        );
+        assert_eq!(
+            dav.resource_path(Path::new("/My Documents")),
+            "/remote.php/dav/files/joe doe/My Documents"
+        );
+        assert_eq!(
+            href_path("https://cloud.example.com/remote.php/dav/files/joe%20doe/a%26b.txt"),
+            "/remote.php/dav/files/joe doe/a&b.txt"
+        );
+        assert_eq!(href_path("/dav/dir/"), "/dav/dir");
+        assert!(WebDavFs::new("cloud.example.com", DavAuth::None).is_err());
+    }
+
+    /// Runs against a share given as `user:password@http://host:port/path` in
+    /// FIR_TEST_WEBDAV, e.g. a container of Nextcloud or rclone serve webdav
+    #[test]
+    #[ignore = "needs FIR_TEST_WEBDAV server"]
+    fn webdav_test() {
+        let server = env::var("FIR_TEST_WEBDAV").expect("FIR_TEST_WEBDAV should be set");
+        let (login, url) = server.split_once('@').unwrap();
+        let (user, password) = login.split_once(':').unwrap();
+        let auth = DavAuth::Basic {
+            user: user.to_string(),
+            password: password.to_string(),
+        };
+        let dav = WebDavFs::new(url, auth).unwrap();
+
+        let dir = PathBuf::from(format!("/fir test {}", std::process::id()));
+        dav.mkdir(&dir).unwrap();
+        assert!(dav.stat(&dir).unwrap().is_dir);
+        let mut writer = dav.open_write(&dir.join("a.txt")).unwrap();
+        writer.write_all(b"remote").unwrap();
+        writer.finish().unwrap();
+        dav.rename(&dir.join("a.txt"), &dir.join("b&c.txt"))
+            .unwrap();
+        let entries = dav.list(&dir).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["b&c.txt"]);
+        assert_eq!(entries[0].info.size, 6);
+        let mut content = String::new();
+        dav.open_read(&dir.join("b&c.txt"))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "remote");
+        dav.remove(&dir.join("b&c.txt")).unwrap();
+        dav.remove(&dir).unwrap();
+    }
+}